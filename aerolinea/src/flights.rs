@@ -0,0 +1,360 @@
+use alglobo_common_utils::journal::{self, Journal};
+use alglobo_common_utils::seat::Seat;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const HOLD: &str = "hold";
+const CONFIRM: &str = "confirm";
+const RELEASE: &str = "release";
+const KEEP: &str = "keep";
+
+// Los asientos de una clase en un vuelo, y cuantos ya estan vendidos o retenidos
+struct FareClass {
+    seats: u32,
+    taken: u32,
+}
+
+// El asiento que retuvo un prepare, hasta que llegue la decision o venza.
+// El vencimiento va en segundos desde 1970, asi sobrevive a un reinicio
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hold {
+    seat: Seat,
+    expires_at: Option<u64>,
+}
+
+// Un cambio en los asientos, tal como queda en el journal:
+// "hold,<id>,<intento>,<vencimiento>,<clase>,<vuelo>", "confirm,<id>,<intento>",
+// "release,<id>,<intento>" o "keep,<id>,<intento>" (la retencion deja de vencer). Sin
+// vencimiento el campo queda vacio, y el vuelo va ultimo porque puede tener comas
+enum JournalEntry {
+    Hold(u64, u32, Hold),
+    Confirm(u64, u32),
+    Release(u64, u32),
+    Keep(u64, u32),
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalEntry::Hold(id, attempt, hold) => write!(
+                f,
+                "{},{},{},{},{},{}",
+                HOLD,
+                id,
+                attempt,
+                hold.expires_at
+                    .map(|expires_at| expires_at.to_string())
+                    .unwrap_or_default(),
+                hold.seat.fare_class,
+                hold.seat.flight
+            ),
+            JournalEntry::Confirm(id, attempt) => write!(f, "{},{},{}", CONFIRM, id, attempt),
+            JournalEntry::Release(id, attempt) => write!(f, "{},{},{}", RELEASE, id, attempt),
+            JournalEntry::Keep(id, attempt) => write!(f, "{},{},{}", KEEP, id, attempt),
+        }
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let malformed = || format!("Malformed journal line: {}", line);
+        let fields = line.splitn(6, ',').collect::<Vec<&str>>();
+        let id = fields
+            .get(1)
+            .and_then(|field| u64::from_str(field).ok())
+            .ok_or_else(malformed)?;
+        let attempt = fields
+            .get(2)
+            .and_then(|field| u32::from_str(field).ok())
+            .ok_or_else(malformed)?;
+        match fields[..] {
+            [HOLD, _, _, expires_at, fare_class, flight] => Ok(JournalEntry::Hold(
+                id,
+                attempt,
+                Hold {
+                    seat: Seat::new(flight.to_string(), fare_class.to_string())?,
+                    expires_at: match expires_at {
+                        "" => None,
+                        expires_at => Some(u64::from_str(expires_at).map_err(|_| malformed())?),
+                    },
+                },
+            )),
+            [CONFIRM, _, _] => Ok(JournalEntry::Confirm(id, attempt)),
+            [RELEASE, _, _] => Ok(JournalEntry::Release(id, attempt)),
+            [KEEP, _, _] => Ok(JournalEntry::Keep(id, attempt)),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+// Lo que queda en memoria de los asientos: la semilla con el journal aplicado encima
+struct Seats {
+    fare_classes: HashMap<Seat, FareClass>,
+    // por transaccion e intento, como el log del participante
+    holds: HashMap<(u64, u32), Hold>,
+    // las ya vendidas, para distinguir un confirm repetido de uno sin retencion
+    confirmed: HashSet<(u64, u32)>,
+}
+
+impl Seats {
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Hold(id, attempt, hold) => {
+                if let Some(fare_class) = self.fare_classes.get_mut(&hold.seat) {
+                    fare_class.taken += 1;
+                    self.holds.insert((id, attempt), hold);
+                }
+            }
+            JournalEntry::Confirm(id, attempt) => {
+                if self.holds.remove(&(id, attempt)).is_some() {
+                    self.confirmed.insert((id, attempt));
+                }
+            }
+            JournalEntry::Release(id, attempt) => {
+                if let Some(hold) = self.holds.remove(&(id, attempt)) {
+                    if let Some(fare_class) = self.fare_classes.get_mut(&hold.seat) {
+                        fare_class.taken -= 1;
+                    }
+                }
+            }
+            JournalEntry::Keep(id, attempt) => {
+                if let Some(hold) = self.holds.get_mut(&(id, attempt)) {
+                    hold.expires_at = None;
+                }
+            }
+        }
+    }
+}
+
+// "<vuelo>,<clase>,<asientos>", de atras para adelante porque el vuelo puede tener comas
+fn parse_seats(line: &str) -> Result<(Seat, u32), String> {
+    let fields = line.rsplitn(3, ',').collect::<Vec<&str>>();
+    match fields[..] {
+        [seats, fare_class, flight] => Ok((
+            Seat::new(flight.to_string(), fare_class.to_string())?,
+            u32::from_str(seats).map_err(|e| format!("{}: {}", line, e))?,
+        )),
+        _ => Err(format!("malformed seats {}", line)),
+    }
+}
+
+// Los asientos de cada vuelo por clase. Arrancan del archivo semilla
+// ("<vuelo>,<clase>,<asientos>") y cada cambio se agrega al journal antes de aplicarse, como
+// las cuentas del banco. Se puede vender un porcentaje por encima de los asientos, y las
+// retenciones que no se deciden a tiempo vencen
+pub struct Flights {
+    seats: Seats,
+    overbooking_percent: u32,
+    hold_expiry: Option<Duration>,
+    journal: Journal,
+}
+
+impl Flights {
+    pub fn load(seed_path: &str, journal_path: &str) -> Result<Self, String> {
+        let mut seats = Seats {
+            fare_classes: journal::read_seed(seed_path, parse_seats)?
+                .into_iter()
+                .map(|(seat, seats)| (seat, FareClass { seats, taken: 0 }))
+                .collect(),
+            holds: HashMap::new(),
+            confirmed: HashSet::new(),
+        };
+        let journal = Journal::load(journal_path, |entry| seats.apply(entry))?;
+        Ok(Flights {
+            seats,
+            overbooking_percent: 0,
+            hold_expiry: None,
+            journal,
+        })
+    }
+
+    pub fn with_overbooking(mut self, percent: u32) -> Self {
+        self.overbooking_percent = percent;
+        self
+    }
+
+    // sin vencimiento las retenciones esperan la decision para siempre
+    pub fn with_hold_expiry(mut self, hold_expiry: Option<Duration>) -> Self {
+        self.hold_expiry = hold_expiry;
+        self
+    }
+
+    // los asientos mas lo que se permite sobrevender, redondeado para abajo
+    fn sellable(&self, fare_class: &FareClass) -> u64 {
+        let seats = u64::from(fare_class.seats);
+        seats + seats * u64::from(self.overbooking_percent) / 100
+    }
+
+    // los asientos que quedan para vender en esa clase
+    #[cfg(test)]
+    pub fn available(&self, seat: &Seat) -> Option<u64> {
+        self.seats
+            .fare_classes
+            .get(seat)
+            .map(|fare_class| self.sellable(fare_class) - u64::from(fare_class.taken))
+    }
+
+    // retiene un asiento hasta now + el vencimiento. Repetirlo para la misma transaccion no
+    // retiene dos veces
+    pub fn hold(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        seat: Seat,
+        now: u64,
+    ) -> Result<(), String> {
+        if let Some(existing) = self.seats.holds.get(&(transaction_id, attempt)) {
+            if existing.seat == seat {
+                return Ok(());
+            }
+            return Err(format!(
+                "Transaction {} already holds {}",
+                transaction_id, existing.seat
+            ));
+        }
+        let fare_class = self.seats.fare_classes.get(&seat).ok_or_else(|| {
+            format!(
+                "Flight {} has no fare class {}",
+                seat.flight, seat.fare_class
+            )
+        })?;
+        if u64::from(fare_class.taken) >= self.sellable(fare_class) {
+            return Err(format!(
+                "Fare class {} of flight {} is sold out",
+                seat.fare_class, seat.flight
+            ));
+        }
+        let hold = Hold {
+            seat,
+            expires_at: self.hold_expiry.map(|expiry| now + expiry.as_secs()),
+        };
+        self.write(JournalEntry::Hold(transaction_id, attempt, hold))
+    }
+
+    // el asiento queda vendido. Repetirlo no hace nada, pero sin retencion no hay que vender
+    pub fn confirm(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.seats.confirmed.contains(&key) {
+            return Ok(());
+        }
+        if !self.seats.holds.contains_key(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) holds no seat to confirm",
+                transaction_id, attempt
+            ));
+        }
+        self.write(JournalEntry::Confirm(transaction_id, attempt))
+    }
+
+    // libera el asiento retenido. Si no se retuvo nada (el prepare no paso) no hace nada
+    pub fn release(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.seats.confirmed.contains(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) was already confirmed",
+                transaction_id, attempt
+            ));
+        }
+        if !self.seats.holds.contains_key(&key) {
+            return Ok(());
+        }
+        self.write(JournalEntry::Release(transaction_id, attempt))
+    }
+
+    // la retencion queda hasta la decision, sin vencer. Sin retencion no hay que mantener nada
+    pub fn keep(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        match self.seats.holds.get(&(transaction_id, attempt)) {
+            Some(hold) if hold.expires_at.is_none() => Ok(()),
+            Some(_) => self.write(JournalEntry::Keep(transaction_id, attempt)),
+            None => Err(format!(
+                "Transaction {} (attempt {}) holds no seat to keep",
+                transaction_id, attempt
+            )),
+        }
+    }
+
+    // las retenciones vencidas. Siguen retenidas: se liberan con el abort, si llega
+    pub fn expired(&self, now: u64) -> Vec<(u64, u32)> {
+        self.seats
+            .holds
+            .iter()
+            .filter(|(_, hold)| matches!(hold.expires_at, Some(expires_at) if expires_at <= now))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    fn write(&mut self, entry: JournalEntry) -> Result<(), String> {
+        let seats = &mut self.seats;
+        self.journal.write(entry, |entry| seats.apply(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flights::Flights;
+    use alglobo_common_utils::seat::Seat;
+    use std::time::Duration;
+
+    fn seat(flight: &str, fare_class: &str) -> Seat {
+        Seat::new(flight.to_string(), fare_class.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_overbooked_holds_expire() {
+        let seed = "test_flights_seed.csv";
+        let journal = "test_flights_journal.csv";
+        let _ = std::fs::remove_file(journal);
+        std::fs::write(seed, "flight,fare_class,seats\nAR1140,Y,10\nAR1140,J,1\n").unwrap();
+        let load = || {
+            Flights::load(seed, journal)
+                .unwrap()
+                .with_overbooking(20)
+                .with_hold_expiry(Some(Duration::from_secs(60)))
+        };
+        let mut flights = load();
+
+        // 10 asientos mas un 20%
+        for id in 0..12 {
+            flights.hold(id, 0, seat("AR1140", "Y"), 1000).unwrap();
+        }
+        assert!(flights.hold(12, 0, seat("AR1140", "Y"), 1000).is_err());
+        // repetido no retiene de nuevo
+        flights.hold(11, 0, seat("AR1140", "Y"), 1000).unwrap();
+        // con un solo asiento no alcanza para sobrevender
+        flights.hold(20, 0, seat("AR1140", "J"), 1030).unwrap();
+        assert!(flights.hold(21, 0, seat("AR1140", "J"), 1030).is_err());
+        assert!(flights.hold(22, 0, seat("AR1150", "Y"), 1030).is_err());
+
+        flights.confirm(0, 0).unwrap();
+        flights.confirm(0, 0).unwrap();
+        assert!(flights.release(0, 0).is_err());
+        flights.release(1, 0).unwrap();
+        assert!(flights.confirm(1, 0).is_err());
+        assert_eq!(flights.available(&seat("AR1140", "Y")), Some(1));
+        drop(flights);
+
+        // al reiniciar queda igual, y los vencimientos siguen corriendo
+        let mut flights = load();
+        let _ = std::fs::remove_file(seed);
+        let _ = std::fs::remove_file(journal);
+        assert_eq!(flights.expired(1059), vec![]);
+        let mut expired = flights.expired(1060);
+        expired.sort();
+        assert_eq!(expired, (2..12).map(|id| (id, 0)).collect::<Vec<_>>());
+        // vencer no libera el asiento, eso lo hace el abort
+        assert_eq!(flights.available(&seat("AR1140", "Y")), Some(1));
+        flights.release(2, 0).unwrap();
+        assert_eq!(flights.available(&seat("AR1140", "Y")), Some(2));
+        assert_eq!(flights.expired(1090).len(), 10);
+        flights.confirm(20, 0).unwrap();
+        // la precommiteada ya no vence
+        flights.keep(3, 0).unwrap();
+        assert_eq!(flights.expired(1090).len(), 8);
+        assert!(flights.keep(20, 0).is_err());
+        assert_eq!(flights.available(&seat("AR1140", "J")), Some(0));
+    }
+}
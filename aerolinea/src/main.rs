@@ -1,108 +1,85 @@
-use alglobo_common_utils::entity_logger::Logger;
-use alglobo_common_utils::entity_payload::{EntityPayload, PAYLOAD_SIZE};
-use alglobo_common_utils::transaction_response::TransactionResponse;
-use alglobo_common_utils::transaction_state::TransactionState;
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
-use std::net::UdpSocket;
-use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
-use std::thread;
+mod flights;
 
-fn logger(rx: Receiver<String>) {
-    let mut logger = Logger::new("aerolinea.log");
-    for msg in rx {
-        logger.log(msg);
-    }
+use crate::flights::Flights;
+use alglobo_common_utils::cluster_config::ClusterConfig;
+use alglobo_common_utils::entity_payload::EntityPayload;
+use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::participant::{Participant, ReservationHandler};
+use alglobo_common_utils::seat::Seat;
+use std::env::args;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// El prepare retiene un asiento en la clase pedida, el commit lo vende y el abort lo libera.
+// Si la decision no llega antes del vencimiento se le pregunta al coordinador, y el asiento se
+// libera recien cuando contesta abort
+struct Airline {
+    flights: Flights,
 }
 
-fn main() {
-    let sock = UdpSocket::bind("localhost:1236").unwrap();
-    let mut rng = thread_rng();
-    let mut log = HashMap::new();
-    let (tx, rx) = mpsc::channel();
-    let _ = tx.send("Aerolinea inicializada en localhost:1236".to_string());
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
 
-    let _ = thread::spawn(|| logger(rx));
+impl ReservationHandler for Airline {
+    fn can_reserve(&mut self, payload: &EntityPayload) -> Result<(), String> {
+        let seat = Seat::from_str(&payload.details)
+            .map_err(|_| format!("Transaction {} has no seat", payload.transaction_id))?;
+        self.flights
+            .hold(payload.transaction_id, payload.attempt, seat, now())
+    }
 
-    loop {
-        let mut buf = [0; PAYLOAD_SIZE];
+    fn commit(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        self.flights.confirm(transaction_id, attempt)
+    }
 
-        let (_, addr) = sock.recv_from(&mut buf).unwrap();
+    fn abort(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        self.flights.release(transaction_id, attempt)
+    }
 
-        let payload_deserialized: EntityPayload = buf.to_vec().into();
-        let _ = tx.send(format!("payload_deserialized: {:?}", payload_deserialized));
-        let transaction_id = payload_deserialized.transaction_id;
-        let response = match payload_deserialized.transaction_state {
-            TransactionState::Prepare => {
-                let _ = tx.send("TransactionState: Prepare".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) | Some(TransactionState::Commit) => {
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Abort) => {
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    None => {
-                        let x: f64 = rng.gen();
-                        if x > 0.1 {
-                            log.insert(transaction_id, TransactionState::Accept);
-                            let _ = tx.send("TransactionResponse: Commit".to_string());
-                            TransactionResponse::new(transaction_id, TransactionState::Commit)
-                        } else {
-                            log.insert(transaction_id, TransactionState::Abort);
-                            let _ = tx.send("FAILED. TransactionResponse: Abort".to_string());
-                            TransactionResponse::new(transaction_id, TransactionState::Abort)
-                        }
-                    }
-                    _ => panic!("Invalid transacciont state"),
-                }
-            }
-            TransactionState::Commit => {
-                let _ = tx.send("TransactionState: Commit".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) => {
-                        log.insert(transaction_id, TransactionState::Commit);
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Commit) => {
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Abort) | None => {
-                        let _ = tx.send("PANICK; TransactionState::Abort cannot be handled by two fase transactionality algorithm".to_string());
-                        panic!("This cannot be handled by two fase transactionality algorithm!");
-                    }
-                    _ => panic!("This cannot be handled by two fase transactionality algorithm!"),
-                }
-            }
-            TransactionState::Abort => {
-                let _ = tx.send("TransactionState: Abort".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) => {
-                        log.insert(transaction_id, TransactionState::Abort);
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    Some(TransactionState::Abort) => {
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    Some(TransactionState::Commit) | None => {
-                        println!("{} {:?}", transaction_id, log.get(&transaction_id));
-                        let _ = tx.send("PANICK; TransactionState::Commit cannot be handled by two fase transactionality algorithm".to_string());
-                        panic!("This cannot be handled by two fase transactionality algorithm!");
-                    }
-                    _ => panic!("This cannot be handled by two fase transactionality algorithm!"),
-                }
-            }
-            _ => panic!("TransactionState Unknow"),
-        };
+    fn precommit(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        self.flights.keep(transaction_id, attempt)
+    }
 
-        let response_payload: Vec<u8> = response.into();
-        let _ = sock.send_to(response_payload.as_slice(), addr);
+    fn expired(&self) -> Vec<(u64, u32)> {
+        self.flights.expired(now())
     }
 }
+
+fn main() {
+    let config_path = args().nth(1);
+    let config = match ClusterConfig::load_or_default(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => panic!("ERROR leyendo la configuracion: {}", e),
+    };
+    let entity = match config.entity(EntityType::Airline) {
+        Some(entity) => entity,
+        None => panic!("ERROR: la configuracion no tiene la entidad Airline"),
+    };
+    let (seed_file, journal_file) = match (&entity.seed_file, &entity.journal_file) {
+        (Some(seed_file), Some(journal_file)) => (seed_file, journal_file),
+        _ => panic!("ERROR: la entidad Airline necesita seed_file y journal_file"),
+    };
+    let flights = match Flights::load(seed_file, journal_file) {
+        Ok(flights) => flights
+            .with_overbooking(entity.overbooking_percent)
+            .with_hold_expiry(entity.hold_expiry_s.map(Duration::from_secs)),
+        Err(e) => panic!("ERROR cargando los asientos: {}", e),
+    };
+    Participant::new(
+        "Aerolinea",
+        &entity.address,
+        &entity.log_file,
+        &entity.state_file,
+        Airline { flights },
+    )
+    .with_currencies(entity.currencies.clone())
+    .with_decision_inquiry(
+        config.coordinator_addresses(),
+        Duration::from_secs(config.timeouts.decision_s),
+    )
+    .run();
+}
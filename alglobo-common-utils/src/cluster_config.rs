@@ -0,0 +1,530 @@
+use crate::entity_type::EntityType;
+use crate::money::Currency;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// en los nombres de archivo se reemplaza por el pid de la replica
+const PID_PLACEHOLDER: &str = "{pid}";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfig {
+    pub id: u8,
+    // mensajes de eleccion (Election/Ok/Coordinator)
+    pub control_address: String,
+    // ping/pong contra el lider
+    pub data_address: String,
+    // estado que el lider replica en las demas
+    pub replication_address: String,
+    // desde donde habla con las entidades cuando es lider
+    pub entity_address: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EntityConfig {
+    #[serde(rename = "type")]
+    pub entity_type: EntityType,
+    pub address: String,
+    pub log_file: String,
+    pub state_file: String,
+    // monedas en las que acepta reservas; sin la lista acepta cualquiera
+    #[serde(default)]
+    pub currencies: Vec<Currency>,
+    // lo que la entidad tiene para reservar al arrancar por primera vez (las cuentas del
+    // banco, las habitaciones del hotel, los asientos de la aerolinea), y el registro de lo que se fue reservando desde entonces
+    #[serde(default)]
+    pub seed_file: Option<String>,
+    #[serde(default)]
+    pub journal_file: Option<String>,
+    // cuanto se puede vender por encima de la capacidad, en porcentaje (solo la aerolinea)
+    #[serde(default)]
+    pub overbooking_percent: u32,
+    // cuanto dura una reserva sin decision antes de preguntarle la decision al coordinador;
+    // sin esto no vencen. No se libera hasta que conteste abort
+    #[serde(default)]
+    pub hold_expiry_s: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    // cuanto espera el coordinador los votos antes de abortar
+    pub transaction_s: u64,
+    // cuanto espera el pong del lider antes de arrancar una eleccion
+    pub ping_s: u64,
+    pub ping_rate_s: u64,
+    // cuanto espera un Ok antes de declararse lider
+    pub election_s: u64,
+    // cuanto espera un participante la decision de algo que voto antes de preguntarla,
+    // y despues cada cuanto vuelve a preguntar
+    pub decision_s: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            transaction_s: 30,
+            ping_s: 10,
+            ping_rate_s: 2,
+            election_s: 10,
+            decision_s: 60,
+        }
+    }
+}
+
+// Reintento automatico de las transacciones abortadas. El intento n se vuelve a mandar
+// despues de initial_backoff_s * 2^n segundos, sin pasar de max_backoff_s
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub enabled: bool,
+    // contando el intento original
+    pub max_attempts: u32,
+    pub initial_backoff_s: u64,
+    pub max_backoff_s: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            enabled: false,
+            max_attempts: 3,
+            initial_backoff_s: 5,
+            max_backoff_s: 60,
+        }
+    }
+}
+
+// Que hacer con una fila cuyo id ya aparecio antes en la entrada
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    // se rechaza siempre
+    Reject,
+    // si trae lo mismo que la primera es la misma transaccion y se ignora; si no, se rechaza
+    Idempotent,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    // cuantas transacciones puede haber entre el prepare y la decision antes de dejar de leer
+    pub max_in_flight: usize,
+    // prepares por segundo a cada entidad; las que no estan no tienen limite.
+    // toml no deja usar el enum como clave, se valida al cargar
+    pub rate_limits: HashMap<String, f64>,
+    // cada cuanto se guarda hasta donde se resolvio el archivo de transacciones
+    pub checkpoint_interval_s: u64,
+    // cada cuanto se reescriben el registro de terminadas con una fila por transaccion y el log
+    // de decisiones sin lo que ya termino
+    pub ledger_compaction_interval_s: u64,
+    pub duplicate_policy: DuplicatePolicy,
+}
+
+impl PipelineConfig {
+    pub fn entity_rate_limits(&self) -> Result<HashMap<EntityType, f64>, String> {
+        self.rate_limits
+            .iter()
+            .map(|(entity, rate)| {
+                let entity = EntityType::from_str(entity)?;
+                if !rate.is_finite() || *rate <= 0.0 {
+                    return Err(format!("Invalid rate limit for {}: {}", entity, rate));
+                }
+                Ok((entity, *rate))
+            })
+            .collect()
+    }
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            max_in_flight: 100,
+            rate_limits: HashMap::new(),
+            checkpoint_interval_s: 5,
+            ledger_compaction_interval_s: 60,
+            duplicate_policy: DuplicatePolicy::Reject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FileConfig {
+    pub failed_transactions: String,
+    // las que agotaron los reintentos
+    pub dead_letter_transactions: String,
+    // las filas que no pasaron la validacion, con su linea y el error
+    pub rejected_transactions: String,
+    pub done_transactions: String,
+    pub decision_log: String,
+    pub replica_log: String,
+    pub checkpoint: String,
+}
+
+impl Default for FileConfig {
+    fn default() -> Self {
+        FileConfig {
+            failed_transactions: "failed_transactions.csv".to_string(),
+            dead_letter_transactions: "dead_letter_transactions.csv".to_string(),
+            rejected_transactions: "rejected_transactions.csv".to_string(),
+            done_transactions: "done_transactions_pid_{pid}.csv".to_string(),
+            decision_log: "coordinator_decisions_pid_{pid}.csv".to_string(),
+            replica_log: "log_alglobo_replica_pid_{pid}.log".to_string(),
+            checkpoint: "transactions_checkpoint_pid_{pid}.csv".to_string(),
+        }
+    }
+}
+
+impl FileConfig {
+    pub fn done_transactions(&self, pid: u8) -> String {
+        self.done_transactions
+            .replace(PID_PLACEHOLDER, &pid.to_string())
+    }
+
+    pub fn decision_log(&self, pid: u8) -> String {
+        self.decision_log.replace(PID_PLACEHOLDER, &pid.to_string())
+    }
+
+    pub fn replica_log(&self, pid: u8) -> String {
+        self.replica_log.replace(PID_PLACEHOLDER, &pid.to_string())
+    }
+
+    pub fn checkpoint(&self, pid: u8) -> String {
+        self.checkpoint.replace(PID_PLACEHOLDER, &pid.to_string())
+    }
+
+    // mueve los archivos de salida a otro directorio; las rutas absolutas quedan como estan
+    pub fn in_directory(&mut self, directory: &str) {
+        let directory = Path::new(directory);
+        for path in [
+            &mut self.failed_transactions,
+            &mut self.dead_letter_transactions,
+            &mut self.rejected_transactions,
+            &mut self.done_transactions,
+            &mut self.decision_log,
+            &mut self.replica_log,
+            &mut self.checkpoint,
+        ] {
+            *path = directory.join(path.as_str()).to_string_lossy().to_string();
+        }
+    }
+}
+
+// Topologia del cluster: las replicas de alglobo, las entidades y los timeouts y archivos.
+// La leen tanto alglobo como las entidades, asi se pueden levantar varios clusters en el mismo host
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClusterConfig {
+    pub replicas: Vec<ReplicaConfig>,
+    pub entities: Vec<EntityConfig>,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub files: FileConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub pipeline: PipelineConfig,
+}
+
+impl ClusterConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let config: ClusterConfig = toml::from_str(&content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // sin archivo se usa la configuracion de siempre en localhost
+    pub fn load_or_default(path: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        match path {
+            Some(path) => ClusterConfig::load(path),
+            None => Ok(ClusterConfig::default()),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.pipeline.max_in_flight == 0 {
+            return Err("pipeline.max_in_flight must be at least 1".to_string());
+        }
+        self.pipeline.entity_rate_limits()?;
+        // se usan como periodo de un intervalo, que no puede ser 0
+        if self.pipeline.checkpoint_interval_s == 0 {
+            return Err("pipeline.checkpoint_interval_s must be at least 1".to_string());
+        }
+        if self.pipeline.ledger_compaction_interval_s == 0 {
+            return Err("pipeline.ledger_compaction_interval_s must be at least 1".to_string());
+        }
+        if self.retry.max_attempts == 0 {
+            return Err("retry.max_attempts must be at least 1".to_string());
+        }
+        if self.replicas.is_empty() {
+            return Err("The cluster has no replicas".to_string());
+        }
+        let mut ids = HashSet::new();
+        for replica in &self.replicas {
+            if !ids.insert(replica.id) {
+                return Err(format!("Duplicated replica id: {}", replica.id));
+            }
+        }
+        let mut entity_types = HashSet::new();
+        for entity in &self.entities {
+            if !entity_types.insert(entity.entity_type) {
+                return Err(format!("Duplicated entity: {}", entity.entity_type));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn pids(&self) -> Vec<u8> {
+        let mut pids = self
+            .replicas
+            .iter()
+            .map(|replica| replica.id)
+            .collect::<Vec<u8>>();
+        pids.sort_unstable();
+        pids
+    }
+
+    pub fn replica(&self, pid: u8) -> Option<&ReplicaConfig> {
+        self.replicas.iter().find(|replica| replica.id == pid)
+    }
+
+    pub fn control_address(&self, pid: u8) -> Option<String> {
+        self.replica(pid)
+            .map(|replica| replica.control_address.clone())
+    }
+
+    pub fn data_address(&self, pid: u8) -> Option<String> {
+        self.replica(pid)
+            .map(|replica| replica.data_address.clone())
+    }
+
+    pub fn entity(&self, entity_type: EntityType) -> Option<&EntityConfig> {
+        self.entities
+            .iter()
+            .find(|entity| entity.entity_type == entity_type)
+    }
+
+    // donde escucha a las entidades cada replica; solo el lider esta escuchando
+    pub fn coordinator_addresses(&self) -> Vec<String> {
+        let mut addresses = self
+            .replicas
+            .iter()
+            .map(|replica| replica.entity_address.clone())
+            .collect::<Vec<String>>();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    pub fn entity_addresses(&self) -> HashMap<EntityType, String> {
+        self.entities
+            .iter()
+            .map(|entity| (entity.entity_type, entity.address.clone()))
+            .collect()
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        let replicas = (0..4)
+            .map(|id| ReplicaConfig {
+                id,
+                control_address: format!("localhost:1234{}", id),
+                data_address: format!("localhost:1235{}", id),
+                replication_address: format!("localhost:1236{}", id),
+                entity_address: "localhost:8888".to_string(),
+            })
+            .collect();
+        let entity = |entity_type, address: &str, name: &str| EntityConfig {
+            entity_type,
+            address: address.to_string(),
+            log_file: format!("{}.log", name),
+            state_file: format!("{}_transactions.csv", name),
+            currencies: vec![],
+            seed_file: None,
+            journal_file: None,
+            overbooking_percent: 0,
+            hold_expiry_s: None,
+        };
+        let hotel = EntityConfig {
+            seed_file: Some("hotel_rooms.csv".to_string()),
+            journal_file: Some("hotel_journal.csv".to_string()),
+            ..entity(EntityType::Hotel, "localhost:1234", "hotel")
+        };
+        let bank = EntityConfig {
+            seed_file: Some("banco_accounts.csv".to_string()),
+            journal_file: Some("banco_journal.csv".to_string()),
+            ..entity(EntityType::Bank, "localhost:1235", "banco")
+        };
+        let airline = EntityConfig {
+            seed_file: Some("aerolinea_seats.csv".to_string()),
+            journal_file: Some("aerolinea_journal.csv".to_string()),
+            overbooking_percent: 5,
+            hold_expiry_s: Some(120),
+            ..entity(EntityType::Airline, "localhost:1236", "aerolinea")
+        };
+        ClusterConfig {
+            replicas,
+            entities: vec![hotel, bank, airline],
+            timeouts: TimeoutConfig::default(),
+            files: FileConfig::default(),
+            retry: RetryConfig::default(),
+            pipeline: PipelineConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster_config::{ClusterConfig, DuplicatePolicy};
+    use crate::entity_type::EntityType;
+    use crate::money::Currency;
+    use std::str::FromStr;
+
+    const CONFIG: &str = r#"
+        [[replicas]]
+        id = 1
+        control_address = "10.0.0.1:5000"
+        data_address = "10.0.0.1:5001"
+        replication_address = "10.0.0.1:5002"
+        entity_address = "10.0.0.1:5003"
+
+        [[replicas]]
+        id = 0
+        control_address = "10.0.0.2:5000"
+        data_address = "10.0.0.2:5001"
+        replication_address = "10.0.0.2:5002"
+        entity_address = "10.0.0.2:5003"
+
+        [[entities]]
+        type = "Bank"
+        address = "10.0.0.3:6000"
+        log_file = "banco.log"
+        state_file = "banco_transactions.csv"
+        currencies = ["ARS", "USD"]
+
+        [timeouts]
+        transaction_s = 5
+
+        [files]
+        done_transactions = "cluster_a/done_{pid}.csv"
+
+        [retry]
+        enabled = true
+        max_attempts = 5
+
+        [pipeline]
+        max_in_flight = 10
+        duplicate_policy = "idempotent"
+
+        [pipeline.rate_limits]
+        Airline = 2.5
+    "#;
+
+    #[test]
+    fn test_parse_config() {
+        let config: ClusterConfig = toml::from_str(CONFIG).unwrap();
+        assert!(config.validate().is_ok());
+
+        assert_eq!(config.pids(), vec![0, 1]);
+        assert_eq!(config.data_address(1).unwrap(), "10.0.0.1:5001");
+        assert!(config.control_address(7).is_none());
+        assert_eq!(
+            config.entity(EntityType::Bank).unwrap().address,
+            "10.0.0.3:6000"
+        );
+        assert!(config.entity(EntityType::Hotel).is_none());
+        assert_eq!(config.entity(EntityType::Bank).unwrap().hold_expiry_s, None);
+        assert_eq!(
+            config.entity(EntityType::Bank).unwrap().currencies,
+            vec![
+                Currency::from_str("ARS").unwrap(),
+                Currency::from_str("USD").unwrap()
+            ]
+        );
+
+        // lo que no esta en el archivo queda con el valor por defecto
+        assert_eq!(config.timeouts.transaction_s, 5);
+        assert_eq!(config.timeouts.ping_s, 10);
+        assert_eq!(config.timeouts.decision_s, 60);
+        assert_eq!(
+            config.coordinator_addresses(),
+            vec!["10.0.0.1:5003", "10.0.0.2:5003"]
+        );
+        assert_eq!(config.files.done_transactions(1), "cluster_a/done_1.csv");
+        assert_eq!(
+            config.files.decision_log(1),
+            "coordinator_decisions_pid_1.csv"
+        );
+        assert!(config.retry.enabled);
+        assert_eq!(config.retry.max_attempts, 5);
+        assert_eq!(config.retry.initial_backoff_s, 5);
+        assert_eq!(config.pipeline.max_in_flight, 10);
+        assert_eq!(
+            config.pipeline.duplicate_policy,
+            DuplicatePolicy::Idempotent
+        );
+        let rate_limits = config.pipeline.entity_rate_limits().unwrap();
+        assert_eq!(rate_limits.get(&EntityType::Airline), Some(&2.5));
+        assert!(!rate_limits.contains_key(&EntityType::Bank));
+    }
+
+    #[test]
+    fn test_files_in_directory() {
+        let mut config = ClusterConfig::default();
+        config.files.replica_log = "/var/log/alglobo_{pid}.log".to_string();
+        config.files.in_directory("out");
+        assert_eq!(
+            config.files.done_transactions(2),
+            "out/done_transactions_pid_2.csv"
+        );
+        assert_eq!(
+            config.files.failed_transactions,
+            "out/failed_transactions.csv"
+        );
+        assert_eq!(config.files.replica_log(2), "/var/log/alglobo_2.log");
+    }
+
+    #[test]
+    fn test_sample_config_matches_default() {
+        let sample = ClusterConfig::load("../cluster.toml").unwrap();
+        let default = ClusterConfig::default();
+        assert_eq!(sample.pids(), default.pids());
+        assert_eq!(sample.entity_addresses(), default.entity_addresses());
+        assert_eq!(sample.control_address(3), default.control_address(3));
+        assert_eq!(sample.coordinator_addresses(), vec!["localhost:8888"]);
+        assert_eq!(sample.timeouts.decision_s, default.timeouts.decision_s);
+        let (sample, default) = (
+            sample.entity(EntityType::Airline).unwrap(),
+            default.entity(EntityType::Airline).unwrap(),
+        );
+        assert_eq!(sample.overbooking_percent, default.overbooking_percent);
+        assert_eq!(sample.hold_expiry_s, default.hold_expiry_s);
+    }
+
+    #[test]
+    fn test_duplicated_replicas_are_rejected() {
+        let mut config = ClusterConfig::default();
+        assert!(config.validate().is_ok());
+        config.replicas[1].id = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_rate_limits_are_rejected() {
+        let mut config = ClusterConfig::default();
+        config.pipeline.rate_limits.insert("Bank".to_string(), 0.0);
+        assert!(config.validate().is_err());
+
+        config.pipeline.rate_limits.clear();
+        config
+            .pipeline
+            .rate_limits
+            .insert("Train".to_string(), 10.0);
+        assert!(config.validate().is_err());
+    }
+}
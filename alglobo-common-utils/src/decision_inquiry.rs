@@ -0,0 +1,73 @@
+use crate::entity_payload::be_byte_buffer_to_u64;
+use crate::frame::{INQUIRY_VERSION, PRECOMMIT_VERSION, PROTOCOL_VERSION};
+use crate::protocol_error::ProtocolError;
+
+// id (8 bytes) + intento (4 bytes) + si llego el precommit (1 byte)
+pub const DECISION_INQUIRY_SIZE: usize = 13;
+// antes del commit en tres fases no se mandaba si llego el precommit
+pub const DECISION_INQUIRY_WITHOUT_PRECOMMIT_SIZE: usize = 12;
+
+// Lo que manda un participante que voto commit y no recibio la decision: pregunta como
+// termino ese intento de la transaccion. El coordinador contesta con la decision como si
+// fuera el broadcast, o no contesta si todavia no decidio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecisionInquiry {
+    pub transaction_id: u64,
+    pub attempt: u32,
+    // en tres fases: el participante sabe que todos votaron commit
+    pub precommitted: bool,
+}
+
+impl DecisionInquiry {
+    pub fn new(transaction_id: u64, attempt: u32) -> Self {
+        DecisionInquiry {
+            transaction_id,
+            attempt,
+            precommitted: false,
+        }
+    }
+
+    pub fn with_precommit(mut self, precommitted: bool) -> Self {
+        self.precommitted = precommitted;
+        self
+    }
+
+    // las consultas aparecieron en la version 6, y el precommit en la 7
+    pub fn decode(version: u8, buffer: Vec<u8>) -> Result<Self, ProtocolError> {
+        if version < INQUIRY_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(version));
+        }
+        let expected = match version < PRECOMMIT_VERSION {
+            true => DECISION_INQUIRY_WITHOUT_PRECOMMIT_SIZE,
+            false => DECISION_INQUIRY_SIZE,
+        };
+        if buffer.len() != expected {
+            return Err(ProtocolError::InvalidLength {
+                expected,
+                received: buffer.len(),
+            });
+        }
+        let attempt = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]);
+        Ok(
+            DecisionInquiry::new(be_byte_buffer_to_u64(&buffer[..8]), attempt)
+                .with_precommit(buffer.get(12).is_some_and(|byte| *byte != 0)),
+        )
+    }
+}
+
+impl TryFrom<Vec<u8>> for DecisionInquiry {
+    type Error = ProtocolError;
+
+    fn try_from(buffer: Vec<u8>) -> Result<Self, Self::Error> {
+        DecisionInquiry::decode(PROTOCOL_VERSION, buffer)
+    }
+}
+
+impl From<DecisionInquiry> for Vec<u8> {
+    fn from(inquiry: DecisionInquiry) -> Self {
+        let mut res = inquiry.transaction_id.to_be_bytes().to_vec();
+        res.extend_from_slice(&inquiry.attempt.to_be_bytes());
+        res.push(u8::from(inquiry.precommitted));
+        res
+    }
+}
@@ -86,7 +86,7 @@ fn seconds_to_datetime(ts: i64, tm: &mut DateTime) {
             break;
         }
     }
-    tm.year = year as i32;
+    tm.year = year;
     let mut mon = 0;
     while dayno >= MONTHS[if leapyear(year) { 1 } else { 0 }][mon] {
         dayno -= MONTHS[if leapyear(year) { 1 } else { 0 }][mon];
@@ -142,7 +142,6 @@ mod tests {
     use crate::entity_logger::Logger;
     use std::io;
     use std::io::BufRead;
-    use std::time::SystemTime;
 
     use super::seconds_to_datetime;
     use super::DateTime;
@@ -154,22 +153,17 @@ mod tests {
         logger.log("TEST".to_string());
 
         let file = File::open(filename).unwrap();
-        let lines = io::BufReader::new(file).lines();
-        for line in lines {
-            let l = line.unwrap();
-            let slice = &l[26..30];
-            assert_eq!(slice, "TEST");
-            break;
-        }
+        let mut lines = io::BufReader::new(file).lines();
+        let l = lines.next().unwrap().unwrap();
+        let slice = &l[26..30];
+        assert_eq!(slice, "TEST");
     }
 
     #[test]
     fn test_datetime() {
         let mut tm = DateTime::new();
-        if let Ok(n) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-            let n_secs = n.as_secs() as i64;
-            seconds_to_datetime(n_secs, &mut tm);
-        }
+        // 2022-06-12 02:13:20 UTC
+        seconds_to_datetime(1655000000, &mut tm);
         let datetime = format!("{}", tm);
         assert_eq!("2022", &datetime[0..4]);
         assert_eq!("06", &datetime[5..7]);
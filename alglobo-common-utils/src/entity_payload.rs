@@ -1,22 +1,105 @@
+use crate::frame::{ATTEMPT_VERSION, CURRENCY_VERSION, DETAILS_VERSION, PROTOCOL_VERSION};
+use crate::money::{Currency, Money, DEFAULT_CURRENCY};
+use crate::protocol_error::ProtocolError;
 use crate::transaction_state::TransactionState;
 
-pub const PAYLOAD_SIZE: usize = 17;
+// el payload mas corto, sin datos de la reserva; los datos van despues con su largo adelante
+pub const PAYLOAD_SIZE: usize = 26;
+// hasta la version 4 del protocolo no habia datos de la reserva
+pub const PAYLOAD_WITHOUT_DETAILS_SIZE: usize = 24;
+// hasta la version 3 del protocolo el costo no decia la moneda
+pub const PAYLOAD_WITHOUT_CURRENCY_SIZE: usize = 21;
+// hasta la version 2 del protocolo el payload no tenia numero de intento
+pub const PAYLOAD_WITHOUT_ATTEMPT_SIZE: usize = 17;
 
 #[derive(Debug)]
 pub struct EntityPayload {
     pub transaction_state: TransactionState,
     pub transaction_id: u64,
-    pub cost: u64,
+    pub cost: Money,
+    // 0 la primera vez, y se incrementa cada vez que se reintenta una transaccion fallida
+    pub attempt: u32,
+    // lo que la entidad necesita para reservar, en su propio formato (la cuenta del banco).
+    // Vacio en las decisiones y en los mensajes de los pares viejos
+    pub details: String,
 }
 
 impl EntityPayload {
-    pub fn new(transaction_id: u64, cost: u64) -> Self {
+    pub fn new(transaction_id: u64, cost: Money) -> Self {
         EntityPayload {
             transaction_state: TransactionState::Prepare, // si la transaccion es nueva empieza en estado prepare
             transaction_id,
             cost,
+            attempt: 0,
+            details: String::new(),
         }
     }
+
+    pub fn with_attempt(mut self, attempt: u32) -> Self {
+        self.attempt = attempt;
+        self
+    }
+
+    pub fn with_details(mut self, details: String) -> Self {
+        self.details = details;
+        self
+    }
+
+    // Lee el cuerpo con el formato de esa version del protocolo; un largo que no corresponde
+    // a la version es un error, aunque coincida con el de otra
+    pub fn decode(version: u8, v: Vec<u8>) -> Result<Self, ProtocolError> {
+        let length = v.len();
+        let expected = match version {
+            version if version < ATTEMPT_VERSION => PAYLOAD_WITHOUT_ATTEMPT_SIZE,
+            version if version < CURRENCY_VERSION => PAYLOAD_WITHOUT_CURRENCY_SIZE,
+            version if version < DETAILS_VERSION => PAYLOAD_WITHOUT_DETAILS_SIZE,
+            _ => PAYLOAD_SIZE,
+        };
+        // solo los datos de la reserva tienen largo variable
+        if length < expected || (version < DETAILS_VERSION && length != expected) {
+            return Err(ProtocolError::InvalidLength {
+                expected,
+                received: length,
+            });
+        }
+        let attempt = match version >= ATTEMPT_VERSION {
+            true => u32::from_be_bytes([v[17], v[18], v[19], v[20]]),
+            false => 0,
+        };
+        // los pares viejos solo manejaban pesos
+        let currency = match version >= CURRENCY_VERSION {
+            true => {
+                let code = &v[PAYLOAD_WITHOUT_CURRENCY_SIZE..PAYLOAD_WITHOUT_DETAILS_SIZE];
+                Currency::from_bytes(code)
+                    .map_err(|_| ProtocolError::InvalidCurrency(code.to_vec()))?
+            }
+            false => DEFAULT_CURRENCY,
+        };
+        let details = match version >= DETAILS_VERSION {
+            true => {
+                let size = u16::from_be_bytes([
+                    v[PAYLOAD_WITHOUT_DETAILS_SIZE],
+                    v[PAYLOAD_WITHOUT_DETAILS_SIZE + 1],
+                ]) as usize;
+                if length != PAYLOAD_SIZE + size {
+                    return Err(ProtocolError::InvalidLength {
+                        expected: PAYLOAD_SIZE + size,
+                        received: length,
+                    });
+                }
+                String::from_utf8(v[PAYLOAD_SIZE..].to_vec())
+                    .map_err(|_| ProtocolError::InvalidDetails)?
+            }
+            false => String::new(),
+        };
+        Ok(EntityPayload {
+            transaction_state: v[0].try_into()?,
+            transaction_id: be_byte_buffer_to_u64(&v[1..9]),
+            cost: Money::new(be_byte_buffer_to_u64(&v[9..17]), currency),
+            attempt,
+            details,
+        })
+    }
 }
 
 pub fn be_byte_buffer_to_u64(buffer: &[u8]) -> u64 {
@@ -25,17 +108,12 @@ pub fn be_byte_buffer_to_u64(buffer: &[u8]) -> u64 {
     u64::from_be_bytes(mask_buffer)
 }
 
-// se entiende en big endian
-impl From<Vec<u8>> for EntityPayload {
-    fn from(v: Vec<u8>) -> Self {
-        if v.len() != PAYLOAD_SIZE {
-            panic!();
-        }
-        EntityPayload {
-            transaction_state: v[0].into(),
-            transaction_id: be_byte_buffer_to_u64(&v[1..9]),
-            cost: be_byte_buffer_to_u64(&v[9..]),
-        }
+// se entiende en big endian, con el formato de la version actual
+impl TryFrom<Vec<u8>> for EntityPayload {
+    type Error = ProtocolError;
+
+    fn try_from(v: Vec<u8>) -> Result<Self, Self::Error> {
+        EntityPayload::decode(PROTOCOL_VERSION, v)
     }
 }
 
@@ -43,7 +121,11 @@ impl From<EntityPayload> for Vec<u8> {
     fn from(data: EntityPayload) -> Self {
         let mut res = vec![data.transaction_state.into()];
         res.extend_from_slice(&data.transaction_id.to_be_bytes());
-        res.extend_from_slice(&data.cost.to_be_bytes());
+        res.extend_from_slice(&data.cost.amount.to_be_bytes());
+        res.extend_from_slice(&data.attempt.to_be_bytes());
+        res.extend_from_slice(&data.cost.currency.to_bytes());
+        res.extend_from_slice(&(data.details.len() as u16).to_be_bytes());
+        res.extend_from_slice(data.details.as_bytes());
         res
     }
 }
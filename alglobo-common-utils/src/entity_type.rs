@@ -1,6 +1,29 @@
-#[derive(Eq, Hash, PartialEq, Debug, Copy, Clone)]
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Eq, Hash, PartialEq, Debug, Copy, Clone, Deserialize)]
 pub enum EntityType {
     Hotel,
     Bank,
     Airline,
 }
+
+impl fmt::Display for EntityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for EntityType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Hotel" => Ok(EntityType::Hotel),
+            "Bank" => Ok(EntityType::Bank),
+            "Airline" => Ok(EntityType::Airline),
+            _ => Err(format!("Unknown entity type: {}", s)),
+        }
+    }
+}
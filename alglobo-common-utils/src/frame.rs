@@ -0,0 +1,404 @@
+use crate::decision_inquiry::DecisionInquiry;
+use crate::entity_payload::{be_byte_buffer_to_u64, EntityPayload};
+use crate::money::{Money, DEFAULT_CURRENCY};
+use crate::protocol_error::ProtocolError;
+use crate::transaction_response::TransactionResponse;
+use crate::transaction_state::TransactionState;
+
+// Formato de los mensajes entre coordinador y entidades:
+// magic (2 bytes) | version (1 byte) | tipo de mensaje (1 byte) | secuencia (8 bytes) | largo del cuerpo (2 bytes) | cuerpo
+// todo en big endian. La version 1 no tiene secuencia, y la version 0 es el formato viejo,
+// sin header: el cuerpo solo, y el tipo se deduce por el largo.
+// La version 3 tiene el mismo header que la 2, pero el payload trae el numero de intento,
+// en la 4 tambien la moneda del costo y en la 5 los datos de la reserva para cada entidad.
+// La 6 agrega las consultas de los participantes por decisiones que no les llegaron, y la 7
+// el estado PreCommit del commit en tres fases (que tambien viaja en las consultas) y las
+// consultas del coordinador por el estado de cada participante
+pub const MAGIC: [u8; 2] = *b"AG";
+pub const LEGACY_VERSION: u8 = 0;
+const UNSEQUENCED_VERSION: u8 = 1;
+pub const PROTOCOL_VERSION: u8 = 7;
+// la primera version de cada cambio en el cuerpo de los mensajes: el cuerpo se lee segun la
+// version del header, no segun el largo
+pub const ATTEMPT_VERSION: u8 = 3;
+pub const CURRENCY_VERSION: u8 = 4;
+pub const DETAILS_VERSION: u8 = 5;
+pub const INQUIRY_VERSION: u8 = 6;
+pub const PRECOMMIT_VERSION: u8 = 7;
+const UNSEQUENCED_HEADER_SIZE: usize = 6;
+pub const HEADER_SIZE: usize = 14;
+// alcanza para cualquier frame que mandamos, y para detectar los que vienen de mas
+pub const MAX_FRAME_SIZE: usize = 512;
+
+const PAYLOAD: u8 = 0x1;
+const RESPONSE: u8 = 0x2;
+const INQUIRY: u8 = 0x3;
+// en v0 el broadcast de commit/abort mandaba solo estado + id
+const LEGACY_DECISION_SIZE: usize = 9;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
+    Legacy, // v0, no viaja por el socket
+    Payload,
+    Response,
+    Inquiry,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub version: u8,
+    pub message_type: MessageType,
+    // 0 para las versiones que no tienen secuencia
+    pub sequence: u64,
+    pub body: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(message_type: MessageType, body: Vec<u8>) -> Self {
+        Frame::with_version(PROTOCOL_VERSION, message_type, body)
+    }
+
+    // se usa para contestarle a un par en la misma version en la que nos hablo
+    pub fn with_version(version: u8, message_type: MessageType, body: Vec<u8>) -> Self {
+        Frame {
+            version,
+            message_type,
+            sequence: 0,
+            body,
+        }
+    }
+
+    pub fn sequenced(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+}
+
+impl From<Frame> for Vec<u8> {
+    fn from(frame: Frame) -> Self {
+        if frame.version == LEGACY_VERSION {
+            return frame.body;
+        }
+        let message_type = match frame.message_type {
+            MessageType::Payload => PAYLOAD,
+            MessageType::Response => RESPONSE,
+            MessageType::Inquiry => INQUIRY,
+            MessageType::Legacy => panic!("Legacy frames have no header"),
+        };
+        let mut res = MAGIC.to_vec();
+        res.push(frame.version);
+        res.push(message_type);
+        if frame.version > UNSEQUENCED_VERSION {
+            res.extend_from_slice(&frame.sequence.to_be_bytes());
+        }
+        res.extend_from_slice(&(frame.body.len() as u16).to_be_bytes());
+        res.extend_from_slice(frame.body.as_slice());
+        res
+    }
+}
+
+impl TryFrom<&[u8]> for Frame {
+    type Error = ProtocolError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        if !buffer.starts_with(&MAGIC) {
+            // sin magic number es un par viejo
+            return Ok(Frame::with_version(
+                LEGACY_VERSION,
+                MessageType::Legacy,
+                buffer.to_vec(),
+            ));
+        }
+        if buffer.len() < UNSEQUENCED_HEADER_SIZE {
+            return Err(ProtocolError::InvalidLength {
+                expected: UNSEQUENCED_HEADER_SIZE,
+                received: buffer.len(),
+            });
+        }
+        let version = buffer[2];
+        if version > PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(version));
+        }
+        let header_size = if version > UNSEQUENCED_VERSION {
+            HEADER_SIZE
+        } else {
+            UNSEQUENCED_HEADER_SIZE
+        };
+        if buffer.len() < header_size {
+            return Err(ProtocolError::InvalidLength {
+                expected: header_size,
+                received: buffer.len(),
+            });
+        }
+        let message_type = match buffer[3] {
+            PAYLOAD => MessageType::Payload,
+            RESPONSE => MessageType::Response,
+            INQUIRY => MessageType::Inquiry,
+            other => return Err(ProtocolError::UnknownMessageType(other)),
+        };
+        let sequence = if version > UNSEQUENCED_VERSION {
+            be_byte_buffer_to_u64(&buffer[4..12])
+        } else {
+            0
+        };
+        let length =
+            u16::from_be_bytes([buffer[header_size - 2], buffer[header_size - 1]]) as usize;
+        if buffer.len() != header_size + length {
+            return Err(ProtocolError::InvalidLength {
+                expected: header_size + length,
+                received: buffer.len(),
+            });
+        }
+        Ok(
+            Frame::with_version(version, message_type, buffer[header_size..].to_vec())
+                .sequenced(sequence),
+        )
+    }
+}
+
+impl From<EntityPayload> for Frame {
+    fn from(payload: EntityPayload) -> Self {
+        Frame::new(MessageType::Payload, payload.into())
+    }
+}
+
+impl From<TransactionResponse> for Frame {
+    fn from(response: TransactionResponse) -> Self {
+        Frame::new(MessageType::Response, response.into())
+    }
+}
+
+impl From<DecisionInquiry> for Frame {
+    fn from(inquiry: DecisionInquiry) -> Self {
+        Frame::new(MessageType::Inquiry, inquiry.into())
+    }
+}
+
+impl TryFrom<Frame> for EntityPayload {
+    type Error = ProtocolError;
+
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        match (frame.message_type, frame.body.len()) {
+            (MessageType::Legacy, LEGACY_DECISION_SIZE) => Ok(EntityPayload {
+                transaction_state: TransactionState::try_from(frame.body[0])?,
+                transaction_id: be_byte_buffer_to_u64(&frame.body[1..]),
+                cost: Money::new(0, DEFAULT_CURRENCY),
+                attempt: 0,
+                details: String::new(),
+            }),
+            (MessageType::Legacy, _) | (MessageType::Payload, _) => {
+                EntityPayload::decode(frame.version, frame.body)
+            }
+            (other, _) => Err(ProtocolError::UnexpectedMessageType(other)),
+        }
+    }
+}
+
+impl TryFrom<Frame> for TransactionResponse {
+    type Error = ProtocolError;
+
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        match frame.message_type {
+            MessageType::Legacy | MessageType::Response => {
+                TransactionResponse::try_from(frame.body)
+            }
+            other => Err(ProtocolError::UnexpectedMessageType(other)),
+        }
+    }
+}
+
+impl TryFrom<Frame> for DecisionInquiry {
+    type Error = ProtocolError;
+
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        match frame.message_type {
+            MessageType::Inquiry => DecisionInquiry::decode(frame.version, frame.body),
+            other => Err(ProtocolError::UnexpectedMessageType(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decision_inquiry::DecisionInquiry;
+    use crate::entity_payload::EntityPayload;
+    use crate::frame::{Frame, MessageType, LEGACY_VERSION, PROTOCOL_VERSION};
+    use crate::money::{Currency, Money, DEFAULT_CURRENCY};
+    use crate::protocol_error::ProtocolError;
+    use crate::transaction_response::TransactionResponse;
+    use crate::transaction_state::TransactionState;
+    use std::str::FromStr;
+
+    fn ars(amount: u64) -> Money {
+        Money::new(amount, DEFAULT_CURRENCY)
+    }
+
+    // lo que leeria un par de esa version si le llega el payload cortado a ese largo
+    fn payload_of_version(
+        version: u8,
+        mut payload: Vec<u8>,
+        length: usize,
+    ) -> Result<EntityPayload, ProtocolError> {
+        payload.truncate(length);
+        EntityPayload::try_from(Frame::with_version(version, MessageType::Payload, payload))
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(42, ars(1000))).into();
+        assert_eq!(&buffer[0..2], b"AG");
+
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(frame.version, PROTOCOL_VERSION);
+        assert_eq!(frame.message_type, MessageType::Payload);
+        let payload = EntityPayload::try_from(frame).unwrap();
+        assert_eq!(payload.transaction_id, 42);
+        assert_eq!(payload.cost, ars(1000));
+        assert_eq!(payload.attempt, 0);
+
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(42, ars(1000)).with_attempt(3)).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(EntityPayload::try_from(frame).unwrap().attempt, 3);
+
+        let usd = Money::new(1000, Currency::from_str("USD").unwrap());
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(42, usd)).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(EntityPayload::try_from(frame).unwrap().cost, usd);
+
+        let payload = EntityPayload::new(42, usd).with_details("cuenta 7".to_string());
+        let buffer: Vec<u8> = Frame::from(payload).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(EntityPayload::try_from(frame).unwrap().details, "cuenta 7");
+    }
+
+    #[test]
+    fn test_sequence_roundtrip_and_v1_frames() {
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(42, ars(1000)))
+            .sequenced(99)
+            .into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(frame.sequence, 99);
+
+        // v1: mismo header pero sin secuencia, y el payload sin intento
+        let mut payload: Vec<u8> = EntityPayload::new(42, ars(1000)).into();
+        payload.truncate(17);
+        let buffer: Vec<u8> = Frame::with_version(1, MessageType::Payload, payload)
+            .sequenced(99)
+            .into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(frame.version, 1);
+        assert_eq!(frame.sequence, 0);
+        assert_eq!(EntityPayload::try_from(frame).unwrap().cost, ars(1000));
+    }
+
+    #[test]
+    fn test_legacy_layouts_decode_as_v0() {
+        let payload: Vec<u8> = EntityPayload::new(7, ars(10)).into();
+        let frame = Frame::try_from(&payload[..17]).unwrap();
+        assert_eq!(frame.version, LEGACY_VERSION);
+        assert_eq!(EntityPayload::try_from(frame).unwrap().cost, ars(10));
+
+        // el payload de la version 2, sin numero de intento
+        let payload: Vec<u8> = EntityPayload::new(7, ars(10)).with_attempt(5).into();
+        let payload = payload_of_version(2, payload, 17).unwrap();
+        assert_eq!(payload.attempt, 0);
+
+        // el payload de la version 3, sin moneda: eran pesos
+        let usd = Money::new(10, Currency::from_str("USD").unwrap());
+        let payload: Vec<u8> = EntityPayload::new(7, usd).with_attempt(5).into();
+        let payload = payload_of_version(3, payload, 21).unwrap();
+        assert_eq!(payload.attempt, 5);
+        assert_eq!(payload.cost, ars(10));
+
+        // el payload de la version 4, sin datos de la reserva
+        let payload: Vec<u8> = EntityPayload::new(7, usd)
+            .with_details("1234".to_string())
+            .into();
+        let payload = payload_of_version(4, payload, 24).unwrap();
+        assert_eq!(payload.cost, usd);
+        assert_eq!(payload.details, "");
+
+        // el broadcast viejo de commit/abort: estado + id
+        let response: Vec<u8> = TransactionResponse::new(7, TransactionState::Commit).into();
+        let frame = Frame::try_from(response.as_slice()).unwrap();
+        let decision = EntityPayload::try_from(frame).unwrap();
+        assert_eq!(format!("{:?}", decision.transaction_state), "Commit");
+        assert_eq!(decision.transaction_id, 7);
+
+        let frame = Frame::try_from(response.as_slice()).unwrap();
+        let response = TransactionResponse::try_from(frame).unwrap();
+        assert_eq!(response.transaction_id, 7);
+    }
+
+    #[test]
+    fn test_inquiry_roundtrip() {
+        let buffer: Vec<u8> = Frame::from(DecisionInquiry::new(42, 3).with_precommit(true)).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert_eq!(frame.message_type, MessageType::Inquiry);
+        assert_eq!(
+            DecisionInquiry::try_from(frame).unwrap(),
+            DecisionInquiry::new(42, 3).with_precommit(true)
+        );
+
+        // una consulta no es una respuesta ni un payload
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert!(TransactionResponse::try_from(frame).is_err());
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert!(EntityPayload::try_from(frame).is_err());
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(1, ars(1))).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert!(DecisionInquiry::try_from(frame).is_err());
+    }
+
+    #[test]
+    fn test_newer_versions_and_garbage_are_rejected() {
+        let mut buffer: Vec<u8> = Frame::from(EntityPayload::new(1, ars(1))).into();
+        buffer[2] = PROTOCOL_VERSION + 1;
+        assert!(Frame::try_from(buffer.as_slice()).is_err());
+
+        let mut buffer: Vec<u8> = Frame::from(EntityPayload::new(1, ars(1))).into();
+        buffer.push(0);
+        assert!(Frame::try_from(buffer.as_slice()).is_err());
+
+        let buffer: Vec<u8> = Frame::from(EntityPayload::new(1, ars(1))).into();
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert!(TransactionResponse::try_from(frame).is_err());
+
+        let mut buffer: Vec<u8> = Frame::from(EntityPayload::new(1, ars(1))).into();
+        // la ultima letra de la moneda, antes del largo de los datos de la reserva
+        let last = buffer.len() - 3;
+        buffer[last] = b'?';
+        let frame = Frame::try_from(buffer.as_slice()).unwrap();
+        assert!(EntityPayload::try_from(frame).is_err());
+    }
+
+    #[test]
+    fn test_lengths_of_other_versions_are_rejected() {
+        let payload: Vec<u8> = EntityPayload::new(7, ars(10))
+            .with_attempt(5)
+            .with_details("1234".to_string())
+            .into();
+        // cada version tiene un solo largo posible, aunque otra version use ese largo
+        for (version, length) in [(2, 21), (3, 17), (3, 24), (4, 21), (5, 24), (7, 17)] {
+            assert!(matches!(
+                payload_of_version(version, payload.clone(), length),
+                Err(ProtocolError::InvalidLength { .. })
+            ));
+        }
+        assert!(payload_of_version(LEGACY_VERSION, payload.clone(), 21).is_err());
+
+        // las consultas sin el byte del precommit son de la version 6, y antes no existian
+        let inquiry: Vec<u8> = DecisionInquiry::new(42, 3).with_precommit(true).into();
+        let frame = Frame::with_version(6, MessageType::Inquiry, inquiry[..12].to_vec());
+        assert_eq!(
+            DecisionInquiry::try_from(frame).unwrap(),
+            DecisionInquiry::new(42, 3)
+        );
+        let frame = Frame::with_version(7, MessageType::Inquiry, inquiry[..12].to_vec());
+        assert!(DecisionInquiry::try_from(frame).is_err());
+        let frame = Frame::with_version(6, MessageType::Inquiry, inquiry.clone());
+        assert!(DecisionInquiry::try_from(frame).is_err());
+        let frame = Frame::with_version(5, MessageType::Inquiry, inquiry);
+        assert!(DecisionInquiry::try_from(frame).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+// Registro en disco de los cambios en el inventario de una entidad, una linea por cambio.
+// Cada linea queda en disco antes de aplicarse, asi al reiniciar se vuelve al mismo estado
+// aplicando las lineas en orden sobre el inventario inicial
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    // abre el journal y le pasa a apply, en orden, los cambios que ya tenia
+    pub fn load<E: FromStr>(path: &str, mut apply: impl FnMut(E)) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let lines = BufReader::new(File::open(path).map_err(|e| format!("{}: {}", path, e))?)
+            .lines()
+            .map_while(Result::ok);
+        for line in lines {
+            // una linea cortada por una caida no llego a aplicarse
+            if let Ok(entry) = E::from_str(&line) {
+                apply(entry);
+            }
+        }
+        Ok(Journal { file })
+    }
+
+    // primero al disco, despues a memoria
+    pub fn write<E: Display>(&mut self, entry: E, apply: impl FnOnce(E)) -> Result<(), String> {
+        writeln!(self.file, "{}", entry)
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| e.to_string())?;
+        apply(entry);
+        Ok(())
+    }
+}
+
+// Lee el archivo semilla del inventario, una linea por elemento. Si la primera linea no se
+// puede leer se toma como el header
+pub fn read_seed<T>(
+    path: &str,
+    mut parse: impl FnMut(&str) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let seed = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut result = vec![];
+    for (index, line) in BufReader::new(seed).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", path, e))?;
+        match parse(&line) {
+            Ok(item) => result.push(item),
+            Err(_) if index == 0 => {}
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::{read_seed, Journal};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_seed_header_is_skipped_and_entries_are_replayed() {
+        let seed = "test_journal_seed.csv";
+        let journal = "test_journal.csv";
+        let _ = std::fs::remove_file(journal);
+        std::fs::write(seed, "amount\n1\n2\n").unwrap();
+        assert_eq!(
+            read_seed(seed, |line| u32::from_str(line).map_err(|e| e.to_string())),
+            Ok(vec![1, 2])
+        );
+        std::fs::write(seed, "amount\n1\nx\n").unwrap();
+        assert!(read_seed(seed, |line| u32::from_str(line).map_err(|e| e.to_string())).is_err());
+        let _ = std::fs::remove_file(seed);
+
+        let mut total = 0;
+        let mut file = Journal::load(journal, |entry: u32| total += entry).unwrap();
+        file.write(3, |entry| total += entry).unwrap();
+        file.write(4, |entry| total += entry).unwrap();
+        drop(file);
+        std::fs::write(journal, std::fs::read_to_string(journal).unwrap() + "5x").unwrap();
+
+        let mut replayed = 0;
+        Journal::load(journal, |entry: u32| replayed += entry).unwrap();
+        let _ = std::fs::remove_file(journal);
+        assert_eq!((total, replayed), (7, 7));
+    }
+}
@@ -1,8 +1,19 @@
 extern crate core;
 
+pub mod cluster_config;
+pub mod decision_inquiry;
 pub mod entity_logger;
 pub mod entity_payload;
 pub mod entity_type;
+pub mod frame;
+pub mod journal;
+pub mod money;
+pub mod participant;
+pub mod participant_log;
+pub mod protocol_error;
+pub mod reliable_delivery;
+pub mod seat;
+pub mod stay;
 pub mod transaction_request;
 pub mod transaction_response;
 pub mod transaction_state;
@@ -0,0 +1,119 @@
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+pub const CURRENCY_CODE_SIZE: usize = 3;
+// la de las filas que no dicen en que moneda estan
+pub const DEFAULT_CURRENCY: Currency = Currency(*b"ARS");
+
+// Codigo ISO 4217 de tres letras mayusculas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Currency([u8; CURRENCY_CODE_SIZE]);
+
+impl Currency {
+    pub fn code(&self) -> &str {
+        // siempre son letras ascii, lo chequea from_bytes
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    pub fn to_bytes(self) -> [u8; CURRENCY_CODE_SIZE] {
+        self.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        match <[u8; CURRENCY_CODE_SIZE]>::try_from(bytes) {
+            Ok(code) if code.iter().all(u8::is_ascii_uppercase) => Ok(Currency(code)),
+            _ => Err(format!(
+                "Invalid currency code: {}",
+                String::from_utf8_lossy(bytes)
+            )),
+        }
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Currency::from_bytes(code.as_bytes())
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = String;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        Currency::from_str(&code)
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+// Un monto en la unidad mas chica de su moneda (centavos para ARS o USD), sin decimales
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Money {
+    pub amount: u64,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: u64, currency: Currency) -> Self {
+        Money { amount, currency }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount == 0
+    }
+
+    // None si las monedas no coinciden o si la suma no entra en 64 bits
+    pub fn checked_add(&self, other: &Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        Some(Money::new(
+            self.amount.checked_add(other.amount)?,
+            self.currency,
+        ))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::{Currency, Money, DEFAULT_CURRENCY};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_currency_codes_are_three_uppercase_letters() {
+        assert_eq!(Currency::from_str("ARS").unwrap(), DEFAULT_CURRENCY);
+        assert_eq!(Currency::from_str("USD").unwrap().to_string(), "USD");
+        assert!(Currency::from_str("usd").is_err());
+        assert!(Currency::from_str("US").is_err());
+        assert!(Currency::from_str("USDT").is_err());
+        assert!(Currency::from_bytes(&[0x55, 0x53, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_only_amounts_in_the_same_currency_add_up() {
+        let usd = Currency::from_str("USD").unwrap();
+        let total = Money::new(150, usd).checked_add(&Money::new(50, usd));
+        assert_eq!(total, Some(Money::new(200, usd)));
+        assert_eq!(total.unwrap().to_string(), "200 USD");
+        assert!(Money::new(1, usd)
+            .checked_add(&Money::new(1, DEFAULT_CURRENCY))
+            .is_none());
+        assert!(Money::new(u64::MAX, usd)
+            .checked_add(&Money::new(1, usd))
+            .is_none());
+    }
+}
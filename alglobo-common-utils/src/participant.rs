@@ -0,0 +1,872 @@
+use crate::decision_inquiry::DecisionInquiry;
+use crate::entity_logger::Logger;
+use crate::entity_payload::EntityPayload;
+use crate::frame::{Frame, MessageType, MAX_FRAME_SIZE, PROTOCOL_VERSION};
+use crate::money::Currency;
+use crate::participant_log::ParticipantLog;
+use crate::protocol_error::ProtocolError;
+use crate::reliable_delivery::DuplicateFilter;
+use crate::transaction_response::TransactionResponse;
+use crate::transaction_state::TransactionState;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// cuantas respuestas recordamos para contestar los reenvios sin reprocesarlos
+const ANSWERED_CAPACITY: usize = 4096;
+// cada cuanto revisamos las reservas vencidas y las transacciones sin decision
+const TICK: Duration = Duration::from_secs(1);
+
+// Lo unico que cambia entre entidades: si pueden reservar lo que les piden o no, y que
+// hacer con la reserva cuando se decide la transaccion
+pub trait ReservationHandler {
+    // Err con el motivo si no se puede reservar
+    fn can_reserve(&mut self, payload: &EntityPayload) -> Result<(), String>;
+
+    // commit y abort se llaman antes de registrar la decision, asi que despues de una caida
+    // pueden repetirse, o llegar para una transaccion que nunca se llego a aceptar
+    fn commit(&mut self, _transaction_id: u64, _attempt: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn abort(&mut self, _transaction_id: u64, _attempt: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    // despues del precommit la transaccion solo puede commitearse: la reserva ya no vence
+    fn precommit(&mut self, _transaction_id: u64, _attempt: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    // las reservas que vencieron sin que llegue la decision. No se liberan solas: despues de
+    // votar commit solo el coordinador puede abortar, asi que el participante le pregunta y
+    // las libera con el abort que conteste
+    fn expired(&self) -> Vec<(u64, u32)> {
+        vec![]
+    }
+}
+
+// Participante del commit en dos fases (o en tres, si el coordinador manda PreCommit). Se
+// encarga del loop de UDP, del log de estados por transaccion y de las transiciones
+// Prepare/PreCommit/Commit/Abort
+pub struct Participant<R: ReservationHandler> {
+    name: String,
+    address: String,
+    reservation_handler: R,
+    // vacio acepta cualquier moneda
+    currencies: Vec<Currency>,
+    // estado de cada intento de cada transaccion
+    log: HashMap<(u64, u32), TransactionState>,
+    // desde cuando espera la decision cada intento que votamos, o desde la ultima vez que
+    // la preguntamos
+    prepared_at: HashMap<(u64, u32), Instant>,
+    // las reservas vencidas por las que ya preguntamos, para no preguntar en cada tick
+    expired: HashSet<(u64, u32)>,
+    // a quien preguntarle las decisiones que no llegan, y despues de cuanto. Sin esto se
+    // espera a que el coordinador la reenvie
+    coordinators: Vec<String>,
+    decision_timeout: Option<Duration>,
+    state_file: ParticipantLog,
+    answered: DuplicateFilter<SocketAddr, Vec<u8>>,
+    tx: Sender<String>,
+}
+
+// lo que nos manda el coordinador: un payload con lo que hay que hacer, o la consulta por el
+// estado de una transaccion que quedo sin decidir
+enum Received {
+    Payload(EntityPayload),
+    Inquiry(DecisionInquiry),
+}
+
+impl TryFrom<Frame> for Received {
+    type Error = ProtocolError;
+
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        match frame.message_type {
+            MessageType::Inquiry => DecisionInquiry::try_from(frame).map(Received::Inquiry),
+            _ => EntityPayload::try_from(frame).map(Received::Payload),
+        }
+    }
+}
+
+// sin el estado en disco no se promete nada: se contesta Error en vez del voto o el ack
+fn answer(recorded: io::Result<()>, response: TransactionState) -> TransactionState {
+    match recorded {
+        Ok(()) => response,
+        Err(_) => TransactionState::Error,
+    }
+}
+
+fn logger(log_file: String, rx: Receiver<String>) {
+    let mut logger = Logger::new(&log_file);
+    for msg in rx {
+        logger.log(msg);
+    }
+}
+
+impl<R: ReservationHandler> Participant<R> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        log_file: &str,
+        state_file: &str,
+        reservation_handler: R,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let log_file = log_file.to_string();
+        let _ = thread::spawn(move || logger(log_file, rx));
+        // recuperamos lo que habiamos votado antes de reiniciar
+        let log = ParticipantLog::load(state_file);
+        let _ = tx.send(format!(
+            "Recovered {} transactions from {}",
+            log.len(),
+            state_file
+        ));
+        let state_file = match ParticipantLog::new(state_file) {
+            Ok(state_file) => state_file,
+            Err(e) => panic!("ERROR abriendo {}: {}", state_file, e),
+        };
+        // lo que quedo votado sin decision empieza a esperar de nuevo
+        let prepared_at = log
+            .iter()
+            .filter(|(_, state)| {
+                matches!(
+                    state,
+                    TransactionState::Accept | TransactionState::PreCommit
+                )
+            })
+            .map(|(key, _)| (*key, Instant::now()))
+            .collect();
+        Participant {
+            name: name.to_string(),
+            address: address.to_string(),
+            reservation_handler,
+            currencies: vec![],
+            log,
+            prepared_at,
+            expired: HashSet::new(),
+            coordinators: vec![],
+            decision_timeout: None,
+            state_file,
+            answered: DuplicateFilter::new(ANSWERED_CAPACITY),
+            tx,
+        }
+    }
+
+    // los prepares en otras monedas se abortan sin preguntarle al reservation handler
+    pub fn with_currencies(mut self, currencies: Vec<Currency>) -> Self {
+        self.currencies = currencies;
+        self
+    }
+
+    // las replicas del coordinador: contesta la que sea lider, que es la que esta escuchando
+    pub fn with_decision_inquiry(mut self, coordinators: Vec<String>, timeout: Duration) -> Self {
+        self.coordinators = coordinators;
+        self.decision_timeout = Some(timeout);
+        self
+    }
+
+    fn supports(&self, currency: &Currency) -> bool {
+        self.currencies.is_empty() || self.currencies.contains(currency)
+    }
+
+    // el estado tiene que quedar en disco antes de responder: si no se pudo escribir no cambia
+    // nada y no hay que contestar lo que se iba a contestar
+    fn record(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        state: TransactionState,
+    ) -> io::Result<()> {
+        if let Err(e) = self.state_file.append(transaction_id, attempt, state) {
+            let _ = self.tx.send(format!(
+                "ERROR persisting {:?} for transaction {} (attempt {}): {}",
+                state, transaction_id, attempt, e
+            ));
+            return Err(e);
+        }
+        self.log.insert((transaction_id, attempt), state);
+        if !matches!(state, TransactionState::Accept) {
+            self.expired.remove(&(transaction_id, attempt));
+        }
+        match state {
+            TransactionState::Accept | TransactionState::PreCommit => {
+                self.prepared_at
+                    .insert((transaction_id, attempt), Instant::now());
+            }
+            _ => {
+                self.prepared_at.remove(&(transaction_id, attempt));
+            }
+        }
+        Ok(())
+    }
+
+    // lo que votamos hace mas de decision_timeout sin que llegue la decision. Vuelve a contar
+    // desde ahora, asi se pregunta de nuevo si tampoco llega la respuesta
+    fn in_doubt(&mut self, now: Instant) -> Vec<(u64, u32)> {
+        let timeout = match self.decision_timeout {
+            Some(timeout) => timeout,
+            None => return vec![],
+        };
+        let mut in_doubt = vec![];
+        for (key, prepared_at) in self.prepared_at.iter_mut() {
+            if now.duration_since(*prepared_at) >= timeout {
+                *prepared_at = now;
+                in_doubt.push(*key);
+            }
+        }
+        in_doubt
+    }
+
+    // el coordinador contesta con la decision como si fuera el broadcast, y la procesamos igual.
+    // Por las reservas que acaban de vencer se pregunta ya, sin esperar el timeout
+    fn inquire(&mut self, sock: &UdpSocket, expired: Vec<(u64, u32)>) {
+        let now = Instant::now();
+        let mut in_doubt = self.in_doubt(now);
+        for key in expired {
+            if !in_doubt.contains(&key) {
+                self.prepared_at.insert(key, now);
+                in_doubt.push(key);
+            }
+        }
+        for (transaction_id, attempt) in in_doubt {
+            let _ = self.tx.send(format!(
+                "No decision for transaction {} (attempt {}), asking the coordinator",
+                transaction_id, attempt
+            ));
+            // si llego el precommit el coordinador nuevo sabe que todos votaron commit
+            let precommitted = matches!(
+                self.log.get(&(transaction_id, attempt)),
+                Some(TransactionState::PreCommit)
+            );
+            let inquiry: Vec<u8> = Frame::from(
+                DecisionInquiry::new(transaction_id, attempt).with_precommit(precommitted),
+            )
+            .into();
+            for coordinator in &self.coordinators {
+                if let Err(e) = sock.send_to(inquiry.as_slice(), coordinator) {
+                    let _ = self.tx.send(format!(
+                        "ERROR asking {} for a decision: {}",
+                        coordinator, e
+                    ));
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, transaction_id: u64, attempt: u32) {
+        if let Err(e) = self.reservation_handler.abort(transaction_id, attempt) {
+            let _ = self.tx.send(format!(
+                "ERROR releasing the reservation of transaction {}: {}",
+                transaction_id, e
+            ));
+        }
+    }
+
+    // las reservas vencidas de lo que votamos commit, por las que todavia no preguntamos.
+    // Liberarlas sin la decision romperia la atomicidad si el resto commitea
+    fn expire_holds(&mut self) -> Vec<(u64, u32)> {
+        let mut expired = vec![];
+        for key in self.reservation_handler.expired() {
+            let accepted = matches!(self.log.get(&key), Some(TransactionState::Accept));
+            if !accepted || !self.expired.insert(key) {
+                continue;
+            }
+            let _ = self.tx.send(format!(
+                "The reservation of transaction {} (attempt {}) expired without a decision, asking the coordinator before releasing it",
+                key.0, key.1
+            ));
+            if self.coordinators.is_empty() {
+                let _ = self.tx.send(format!(
+                    "ERROR there is no coordinator to ask about transaction {} (attempt {}), its reservation stays held",
+                    key.0, key.1
+                ));
+            }
+            expired.push(key);
+        }
+        expired
+    }
+
+    pub fn run(mut self) {
+        let sock = UdpSocket::bind(&self.address).unwrap();
+        // sin esto no podriamos vencer reservas ni preguntar decisiones mientras no llegue nada
+        if let Err(e) = sock.set_read_timeout(Some(TICK)) {
+            panic!("ERROR configurando el socket: {}", e);
+        }
+        let _ = self
+            .tx
+            .send(format!("{} inicializado en {}", self.name, self.address));
+
+        let mut last_tick = Instant::now();
+        loop {
+            if last_tick.elapsed() >= TICK {
+                let expired = self.expire_holds();
+                self.inquire(&sock, expired);
+                last_tick = Instant::now();
+            }
+            let mut buf = [0; MAX_FRAME_SIZE];
+
+            let (size, addr) = match sock.recv_from(&mut buf) {
+                Ok(res) => res,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(e) => {
+                    let _ = self.tx.send(format!("ERROR receiving datagram: {}", e));
+                    continue;
+                }
+            };
+
+            // le contestamos al coordinador en la misma version y con la misma secuencia,
+            // que es lo que usa como ack
+            let (version, sequence, message) = match Frame::try_from(&buf[..size]) {
+                Ok(frame) => (frame.version, frame.sequence, Received::try_from(frame)),
+                Err(e) => (PROTOCOL_VERSION, 0, Err(e)),
+            };
+            if sequence != 0 {
+                if let Some(response_payload) = self.answered.get(&addr, sequence) {
+                    // reenvio de algo que ya procesamos: repetimos la respuesta
+                    let _ = self
+                        .tx
+                        .send(format!("Duplicate sequence {} from {}", sequence, addr));
+                    let _ = sock.send_to(response_payload.as_slice(), addr);
+                    continue;
+                }
+            }
+            let response = match message {
+                Ok(Received::Payload(payload_deserialized)) => self.handle(payload_deserialized),
+                Ok(Received::Inquiry(inquiry)) => self.report(inquiry),
+                Err(e) => {
+                    // sin payload valido no sabemos de que transaccion se trata
+                    let _ = self.tx.send(format!("ERROR decoding payload: {}", e));
+                    TransactionResponse::new(0, TransactionState::Error)
+                }
+            };
+
+            let response_payload: Vec<u8> =
+                Frame::with_version(version, MessageType::Response, response.into())
+                    .sequenced(sequence)
+                    .into();
+            let _ = sock.send_to(response_payload.as_slice(), addr);
+            if sequence != 0 {
+                self.answered.insert(addr, sequence, response_payload);
+            }
+        }
+    }
+
+    // un coordinador nuevo que tiene que terminar una transaccion en tres fases pregunta como
+    // quedo cada participante, sin cambiar nada: PreCommit o Commit si ya paso el precommit,
+    // Prepare si voto commit y espera, y Abort si la aborto o nunca la voto
+    fn report(&mut self, inquiry: DecisionInquiry) -> TransactionResponse {
+        let state = match self.log.get(&(inquiry.transaction_id, inquiry.attempt)) {
+            Some(TransactionState::Accept) => TransactionState::Prepare,
+            Some(TransactionState::PreCommit) => TransactionState::PreCommit,
+            Some(TransactionState::Commit) => TransactionState::Commit,
+            _ => TransactionState::Abort,
+        };
+        let _ = self.tx.send(format!(
+            "The coordinator asked for the state of transaction {} (attempt {}): {:?}",
+            inquiry.transaction_id, inquiry.attempt, state
+        ));
+        TransactionResponse::new(inquiry.transaction_id, state)
+    }
+
+    fn handle(&mut self, payload: EntityPayload) -> TransactionResponse {
+        let _ = self.tx.send(format!("payload_deserialized: {:?}", payload));
+        let transaction_id = payload.transaction_id;
+        let _ = self
+            .tx
+            .send(format!("TransactionState: {:?}", payload.transaction_state));
+        let state = match self.transition(&payload) {
+            Ok(state) => state,
+            Err(e) => {
+                let _ = self.tx.send(format!("PROTOCOL ERROR: {}", e));
+                TransactionState::Error
+            }
+        };
+        let _ = self.tx.send(format!("TransactionResponse: {:?}", state));
+        TransactionResponse::new(transaction_id, state)
+    }
+
+    fn transition(&mut self, payload: &EntityPayload) -> Result<TransactionState, ProtocolError> {
+        let transaction_id = payload.transaction_id;
+        let attempt = payload.attempt;
+        // un reintento de una transaccion fallida arranca de cero
+        let current = self.log.get(&(transaction_id, attempt)).copied();
+        match (payload.transaction_state, current) {
+            // un prepare repetido responde lo mismo que la primera vez
+            (TransactionState::Prepare, Some(TransactionState::Accept))
+            | (TransactionState::Prepare, Some(TransactionState::PreCommit))
+            | (TransactionState::Prepare, Some(TransactionState::Commit)) => {
+                Ok(TransactionState::Commit)
+            }
+            (TransactionState::Prepare, Some(TransactionState::Abort)) => {
+                Ok(TransactionState::Abort)
+            }
+            (TransactionState::Prepare, None) => {
+                if !self.supports(&payload.cost.currency) {
+                    let _ = self.tx.send(format!(
+                        "FAILED. Unsupported currency {}",
+                        payload.cost.currency
+                    ));
+                    Ok(answer(
+                        self.record(transaction_id, attempt, TransactionState::Abort),
+                        TransactionState::Abort,
+                    ))
+                } else if let Err(e) = self.reservation_handler.can_reserve(payload) {
+                    let _ = self.tx.send(format!("FAILED. Cannot reserve: {}", e));
+                    Ok(answer(
+                        self.record(transaction_id, attempt, TransactionState::Abort),
+                        TransactionState::Abort,
+                    ))
+                } else {
+                    Ok(answer(
+                        self.record(transaction_id, attempt, TransactionState::Accept),
+                        TransactionState::Commit,
+                    ))
+                }
+            }
+            // en tres fases todos votaron commit: la reserva sigue tomada hasta la decision
+            (TransactionState::PreCommit, Some(TransactionState::Accept)) => {
+                if let Err(e) = self.reservation_handler.precommit(transaction_id, attempt) {
+                    let _ = self.tx.send(format!(
+                        "ERROR keeping the reservation of transaction {}: {}",
+                        transaction_id, e
+                    ));
+                    return Ok(TransactionState::Error);
+                }
+                Ok(answer(
+                    self.record(transaction_id, attempt, TransactionState::PreCommit),
+                    TransactionState::PreCommit,
+                ))
+            }
+            (TransactionState::PreCommit, Some(TransactionState::PreCommit)) => {
+                Ok(TransactionState::PreCommit)
+            }
+            // la respuesta a una consulta pudo adelantarse al precommit
+            (TransactionState::PreCommit, Some(TransactionState::Commit)) => {
+                Ok(TransactionState::Commit)
+            }
+            (TransactionState::Commit, Some(TransactionState::Accept))
+            | (TransactionState::Commit, Some(TransactionState::PreCommit)) => {
+                // sin registrar el commit sigue en duda: se vuelve a preguntar y se reintenta
+                if let Err(e) = self.reservation_handler.commit(transaction_id, attempt) {
+                    let _ = self.tx.send(format!(
+                        "ERROR confirming the reservation of transaction {}: {}",
+                        transaction_id, e
+                    ));
+                    return Ok(TransactionState::Error);
+                }
+                Ok(answer(
+                    self.record(transaction_id, attempt, TransactionState::Commit),
+                    TransactionState::Commit,
+                ))
+            }
+            (TransactionState::Commit, Some(TransactionState::Commit)) => {
+                Ok(TransactionState::Commit)
+            }
+            (TransactionState::Abort, Some(TransactionState::Accept)) => {
+                self.release(transaction_id, attempt);
+                Ok(answer(
+                    self.record(transaction_id, attempt, TransactionState::Abort),
+                    TransactionState::Abort,
+                ))
+            }
+            (TransactionState::Abort, Some(TransactionState::Abort)) => Ok(TransactionState::Abort),
+            // si nunca nos llego el prepare el abort es valido, y si el prepare llega tarde lo rechazamos
+            // (pudo haber quedado una reserva si nos caimos antes de registrar el Accept)
+            (TransactionState::Abort, None) => {
+                self.release(transaction_id, attempt);
+                Ok(answer(
+                    self.record(transaction_id, attempt, TransactionState::Abort),
+                    TransactionState::Abort,
+                ))
+            }
+            // commit de algo que abortamos o que no conocemos, o abort de algo commiteado o
+            // precommiteado: esto no lo puede resolver el algoritmo
+            (received, current) => Err(ProtocolError::UnexpectedMessage {
+                transaction_id,
+                received,
+                current,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decision_inquiry::DecisionInquiry;
+    use crate::entity_payload::EntityPayload;
+    use crate::money::{Currency, Money, DEFAULT_CURRENCY};
+    use crate::participant::{Participant, ReservationHandler};
+    use crate::participant_log::ParticipantLog;
+    use crate::transaction_state::TransactionState;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+    use std::time::{Duration, Instant};
+
+    struct AcceptBelow(u64);
+
+    impl ReservationHandler for AcceptBelow {
+        fn can_reserve(&mut self, payload: &EntityPayload) -> Result<(), String> {
+            match payload.cost.amount < self.0 {
+                true => Ok(()),
+                false => Err(format!("{} is too expensive", payload.cost)),
+            }
+        }
+    }
+
+    // anota lo que le queda reservado
+    #[derive(Default)]
+    struct Holds(HashSet<u64>);
+
+    // reserva todo pero no puede confirmar nada
+    struct Unconfirmable;
+
+    impl ReservationHandler for Unconfirmable {
+        fn can_reserve(&mut self, _payload: &EntityPayload) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn commit(&mut self, _transaction_id: u64, _attempt: u32) -> Result<(), String> {
+            Err("journal unavailable".to_string())
+        }
+    }
+
+    impl ReservationHandler for Holds {
+        fn can_reserve(&mut self, payload: &EntityPayload) -> Result<(), String> {
+            self.0.insert(payload.transaction_id);
+            Ok(())
+        }
+
+        fn commit(&mut self, transaction_id: u64, _attempt: u32) -> Result<(), String> {
+            self.0.remove(&transaction_id);
+            Ok(())
+        }
+
+        fn abort(&mut self, transaction_id: u64, _attempt: u32) -> Result<(), String> {
+            self.0.remove(&transaction_id);
+            Ok(())
+        }
+
+        // todo lo que queda reservado vence
+        fn expired(&self) -> Vec<(u64, u32)> {
+            self.0.iter().map(|id| (*id, 0)).collect()
+        }
+    }
+
+    fn payload(transaction_id: u64, cost: u64, state: TransactionState) -> EntityPayload {
+        retry(transaction_id, cost, state, 0)
+    }
+
+    fn retry(
+        transaction_id: u64,
+        cost: u64,
+        state: TransactionState,
+        attempt: u32,
+    ) -> EntityPayload {
+        let mut payload = EntityPayload::new(transaction_id, Money::new(cost, DEFAULT_CURRENCY))
+            .with_attempt(attempt);
+        payload.transaction_state = state;
+        payload
+    }
+
+    fn participant(state_file: &str) -> Participant<AcceptBelow> {
+        let _ = std::fs::remove_file(state_file);
+        Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            AcceptBelow(10),
+        )
+    }
+
+    #[test]
+    fn test_prepare_asks_the_reservation_handler_once() {
+        let state_file = "test_participant_prepare.csv";
+        let mut participant = participant(state_file);
+
+        let res = participant.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = participant.handle(payload(1, 50, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+
+        // un prepare repetido responde lo mismo que la primera vez
+        let res = participant.handle(payload(1, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_commit_and_abort_after_accept() {
+        let state_file = "test_participant_commit.csv";
+        let mut participant = participant(state_file);
+
+        participant.handle(payload(0, 5, TransactionState::Prepare));
+        participant.handle(payload(1, 5, TransactionState::Prepare));
+
+        let res = participant.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = participant.handle(payload(1, 5, TransactionState::Abort));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_nothing_is_promised_without_the_state_on_disk() {
+        let state_file = "test_participant_unwritable.csv";
+        let mut participant = participant(state_file);
+        participant.handle(payload(1, 5, TransactionState::Prepare));
+        // un disco lleno
+        participant.state_file = ParticipantLog::new("/dev/full").unwrap();
+
+        let res = participant.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+        let res = participant.handle(payload(1, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+        // no quedo nada registrado: cuando se puede escribir se contesta de nuevo
+        participant.state_file = ParticipantLog::new(state_file).unwrap();
+        let res = participant.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = participant.handle(payload(1, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_unexpected_messages_are_answered_with_error() {
+        let state_file = "test_participant_error.csv";
+        let mut participant = participant(state_file);
+
+        // commit de una transaccion que nunca se preparo
+        let res = participant.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+
+        // commit de una transaccion abortada
+        participant.handle(payload(1, 50, TransactionState::Prepare));
+        let res = participant.handle(payload(1, 50, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+
+        // abort de una transaccion commiteada
+        participant.handle(payload(2, 5, TransactionState::Prepare));
+        participant.handle(payload(2, 5, TransactionState::Commit));
+        let res = participant.handle(payload(2, 5, TransactionState::Abort));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+
+        // abort sin prepare es valido, y el prepare que llega tarde se rechaza
+        let res = participant.handle(payload(3, 5, TransactionState::Abort));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+        let res = participant.handle(payload(3, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_retried_transaction_is_prepared_again() {
+        let state_file = "test_participant_retry.csv";
+        let mut participant = participant(state_file);
+
+        participant.handle(payload(0, 50, TransactionState::Prepare));
+        participant.handle(payload(0, 50, TransactionState::Abort));
+
+        // el reintento se evalua de nuevo, sin mirar el abort del intento anterior
+        let res = participant.handle(retry(0, 5, TransactionState::Prepare, 1));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = participant.handle(retry(0, 5, TransactionState::Commit, 1));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+
+        // un prepare atrasado del primer intento sigue abortado
+        let res = participant.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_restarted_participant_remembers_its_votes() {
+        let state_file = "test_participant_restart.csv";
+        let mut participant = participant(state_file);
+        participant.handle(payload(0, 5, TransactionState::Prepare));
+        drop(participant);
+
+        let mut restarted = Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            AcceptBelow(0),
+        );
+        // no vuelve a preguntar, ya habia aceptado
+        let res = restarted.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = restarted.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_votes_without_decision_are_in_doubt() {
+        let state_file = "test_participant_in_doubt.csv";
+        let timeout = Duration::from_secs(60);
+        let mut participant = participant(state_file).with_decision_inquiry(vec![], timeout);
+        participant.handle(payload(0, 5, TransactionState::Prepare));
+        participant.handle(payload(1, 5, TransactionState::Prepare));
+        participant.handle(payload(2, 50, TransactionState::Prepare));
+        participant.handle(payload(1, 5, TransactionState::Commit));
+
+        let now = Instant::now();
+        assert!(participant.in_doubt(now).is_empty());
+        assert_eq!(participant.in_doubt(now + timeout), vec![(0, 0)]);
+        // se vuelve a preguntar recien despues de otro timeout
+        assert!(participant.in_doubt(now + timeout).is_empty());
+        assert_eq!(participant.in_doubt(now + timeout * 2), vec![(0, 0)]);
+
+        // la respuesta del coordinador llega como la decision
+        participant.handle(payload(0, 5, TransactionState::Abort));
+        assert!(participant.in_doubt(now + timeout * 3).is_empty());
+        participant.handle(payload(3, 5, TransactionState::Prepare));
+        drop(participant);
+
+        // al reiniciar lo que estaba votado vuelve a esperar
+        let mut restarted = Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            AcceptBelow(10),
+        )
+        .with_decision_inquiry(vec![], timeout);
+        assert_eq!(restarted.in_doubt(Instant::now() + timeout), vec![(3, 0)]);
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_precommit_only_leads_to_commit() {
+        let state_file = "test_participant_precommit.csv";
+        let timeout = Duration::from_secs(60);
+        let mut participant = participant(state_file).with_decision_inquiry(vec![], timeout);
+
+        // precommit sin haber votado
+        let res = participant.handle(payload(0, 5, TransactionState::PreCommit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+
+        participant.handle(payload(1, 5, TransactionState::Prepare));
+        participant.handle(payload(2, 5, TransactionState::Prepare));
+        for _ in 0..2 {
+            let res = participant.handle(payload(1, 5, TransactionState::PreCommit));
+            assert_eq!(format!("{:?}", res.transaction_state), "PreCommit");
+        }
+        let res = participant.handle(payload(1, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let res = participant.handle(payload(1, 5, TransactionState::Abort));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+        drop(participant);
+
+        // al reiniciar sigue esperando la decision
+        let mut restarted = Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            AcceptBelow(10),
+        )
+        .with_decision_inquiry(vec![], timeout);
+        let mut in_doubt = restarted.in_doubt(Instant::now() + timeout);
+        in_doubt.sort();
+        assert_eq!(in_doubt, vec![(1, 0), (2, 0)]);
+        // lo que contesta a un coordinador nuevo que pregunta como quedo cada una
+        let report = |participant: &mut Participant<AcceptBelow>, id| {
+            let inquiry = DecisionInquiry::new(id, 0);
+            format!("{:?}", participant.report(inquiry).transaction_state)
+        };
+        assert_eq!(report(&mut restarted, 1), "PreCommit");
+        assert_eq!(report(&mut restarted, 2), "Prepare");
+        assert_eq!(report(&mut restarted, 3), "Abort");
+        let res = restarted.handle(payload(1, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        assert_eq!(report(&mut restarted, 1), "Commit");
+        let res = restarted.handle(payload(1, 5, TransactionState::PreCommit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_unsupported_currencies_are_aborted() {
+        let state_file = "test_participant_currency.csv";
+        let usd = Currency::from_str("USD").unwrap();
+        let mut participant = participant(state_file).with_currencies(vec![usd]);
+
+        let res = participant.handle(payload(0, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", res.transaction_state), "Abort");
+
+        let mut in_dollars = EntityPayload::new(1, Money::new(5, usd));
+        in_dollars.transaction_state = TransactionState::Prepare;
+        let res = participant.handle(in_dollars);
+        assert_eq!(format!("{:?}", res.transaction_state), "Commit");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_decisions_reach_the_reservation_handler() {
+        let state_file = "test_participant_hooks.csv";
+        let _ = std::fs::remove_file(state_file);
+        let mut participant = Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            Holds::default(),
+        );
+        participant.handle(payload(0, 5, TransactionState::Prepare));
+        participant.handle(payload(1, 5, TransactionState::Prepare));
+        participant.handle(payload(2, 5, TransactionState::Prepare));
+        assert_eq!(participant.reservation_handler.0.len(), 3);
+
+        participant.handle(payload(0, 5, TransactionState::Commit));
+        participant.handle(payload(1, 5, TransactionState::Abort));
+        assert_eq!(participant.reservation_handler.0, HashSet::from([2]));
+
+        // la reserva que vencio sigue retenida hasta que el coordinador conteste, y se pregunta
+        // una sola vez por vencimiento
+        assert_eq!(participant.expire_holds(), vec![(2, 0)]);
+        assert!(participant.expire_holds().is_empty());
+        assert_eq!(participant.reservation_handler.0, HashSet::from([2]));
+        let response = participant.handle(payload(2, 5, TransactionState::Prepare));
+        assert_eq!(format!("{:?}", response.transaction_state), "Commit");
+        let response = participant.handle(payload(2, 5, TransactionState::Abort));
+        assert_eq!(format!("{:?}", response.transaction_state), "Abort");
+        assert!(participant.reservation_handler.0.is_empty());
+        assert!(participant.expire_holds().is_empty());
+        let response = participant.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", response.transaction_state), "Commit");
+        let _ = std::fs::remove_file(state_file);
+    }
+
+    #[test]
+    fn test_failed_commits_stay_in_doubt() {
+        let state_file = "test_participant_failed_commit.csv";
+        let _ = std::fs::remove_file(state_file);
+        let timeout = Duration::from_secs(60);
+        let mut participant = Participant::new(
+            "Test",
+            "",
+            "test_participant.log",
+            state_file,
+            Unconfirmable,
+        )
+        .with_decision_inquiry(vec![], timeout);
+        participant.handle(payload(0, 5, TransactionState::Prepare));
+
+        let res = participant.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+        // no quedo commiteada: se sigue preguntando por la decision
+        assert_eq!(participant.in_doubt(Instant::now() + timeout), vec![(0, 0)]);
+        let res = participant.handle(payload(0, 5, TransactionState::Commit));
+        assert_eq!(format!("{:?}", res.transaction_state), "Error");
+        let _ = std::fs::remove_file(state_file);
+    }
+}
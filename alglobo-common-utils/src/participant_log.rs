@@ -0,0 +1,105 @@
+use crate::transaction_state::TransactionState;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+const ACCEPT: &str = "Accept";
+const COMMIT: &str = "Commit";
+const ABORT: &str = "Abort";
+const PRECOMMIT: &str = "PreCommit";
+
+// Log en disco de los votos y decisiones de un participante, una linea "<id>,<estado>,<intento>"
+// por cambio. Al arrancar se vuelve a leer, asi un participante reiniciado responde igual que antes
+// de caerse. Cada reintento de una transaccion es independiente de los anteriores
+pub struct ParticipantLog {
+    file: File,
+}
+
+impl ParticipantLog {
+    pub fn new(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(ParticipantLog { file })
+    }
+
+    pub fn append(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        state: TransactionState,
+    ) -> io::Result<()> {
+        let state = match state {
+            TransactionState::Accept => ACCEPT,
+            TransactionState::Commit => COMMIT,
+            TransactionState::Abort => ABORT,
+            TransactionState::PreCommit => PRECOMMIT,
+            _ => return Ok(()),
+        };
+        writeln!(self.file, "{},{},{}", transaction_id, state, attempt)?;
+        self.file.sync_data()
+    }
+
+    // la ultima linea de cada transaccion es la que vale
+    pub fn load(path: &str) -> HashMap<(u64, u32), TransactionState> {
+        let mut log = HashMap::new();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return log,
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let mut fields = line.split(',');
+            let id = match fields.next().map(u64::from_str) {
+                Some(Ok(id)) => id,
+                _ => continue,
+            };
+            let state = match fields.next() {
+                Some(ACCEPT) => TransactionState::Accept,
+                Some(COMMIT) => TransactionState::Commit,
+                Some(ABORT) => TransactionState::Abort,
+                Some(PRECOMMIT) => TransactionState::PreCommit,
+                _ => continue,
+            };
+            // las lineas viejas no tienen intento
+            let attempt = match fields.next().map(u32::from_str) {
+                Some(Ok(attempt)) => attempt,
+                Some(Err(_)) => continue,
+                None => 0,
+            };
+            log.insert((id, attempt), state);
+        }
+        log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::participant_log::ParticipantLog;
+    use crate::transaction_state::TransactionState;
+
+    #[test]
+    fn test_last_state_of_each_transaction_is_loaded() {
+        let path = "test_participant_log_load.csv";
+        let _ = std::fs::remove_file(path);
+        let mut log = ParticipantLog::new(path).unwrap();
+        log.append(0, 0, TransactionState::Accept).unwrap();
+        log.append(1, 0, TransactionState::Abort).unwrap();
+        log.append(0, 0, TransactionState::Commit).unwrap();
+        log.append(1, 1, TransactionState::Accept).unwrap();
+        log.append(3, 0, TransactionState::PreCommit).unwrap();
+        drop(log);
+        // una linea escrita antes de que existieran los intentos
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        std::io::Write::write_all(&mut file, b"2,Abort\n").unwrap();
+
+        let loaded = ParticipantLog::load(path);
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(loaded.len(), 5);
+        assert_eq!(format!("{:?}", loaded[&(0, 0)]), "Commit");
+        assert_eq!(format!("{:?}", loaded[&(1, 0)]), "Abort");
+        assert_eq!(format!("{:?}", loaded[&(1, 1)]), "Accept");
+        assert_eq!(format!("{:?}", loaded[&(2, 0)]), "Abort");
+        assert_eq!(format!("{:?}", loaded[&(3, 0)]), "PreCommit");
+    }
+}
@@ -0,0 +1,63 @@
+use crate::frame::MessageType;
+use crate::transaction_state::TransactionState;
+use std::fmt;
+
+// Errores de protocolo: datagramas que no se pueden decodificar o mensajes
+// que no tienen sentido para el estado en el que esta la transaccion
+#[derive(Debug, Clone)]
+pub enum ProtocolError {
+    UnknownState(u8),
+    UnsupportedVersion(u8),
+    UnknownMessageType(u8),
+    UnexpectedMessageType(MessageType),
+    InvalidCurrency(Vec<u8>),
+    InvalidDetails,
+    InvalidLength {
+        expected: usize,
+        received: usize,
+    },
+    UnexpectedMessage {
+        transaction_id: u64,
+        received: TransactionState,
+        current: Option<TransactionState>,
+    },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::UnknownState(byte) => {
+                write!(f, "Could not deserialize unknown byte {} into state", byte)
+            }
+            ProtocolError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported protocol version {}", version)
+            }
+            ProtocolError::UnknownMessageType(byte) => {
+                write!(f, "Unknown message type {}", byte)
+            }
+            ProtocolError::UnexpectedMessageType(message_type) => {
+                write!(f, "Unexpected message type {:?}", message_type)
+            }
+            ProtocolError::InvalidCurrency(code) => {
+                write!(f, "Invalid currency code {}", String::from_utf8_lossy(code))
+            }
+            ProtocolError::InvalidDetails => write!(f, "Reservation details are not UTF-8"),
+            ProtocolError::InvalidLength { expected, received } => write!(
+                f,
+                "Invalid buffer length: expected {} bytes, received {}",
+                expected, received
+            ),
+            ProtocolError::UnexpectedMessage {
+                transaction_id,
+                received,
+                current,
+            } => write!(
+                f,
+                "Unexpected {:?} for transaction {} in state {:?}",
+                received, transaction_id, current
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
@@ -0,0 +1,234 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime};
+
+const INITIAL_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_MS: u64 = 8000;
+
+// Mensaje enviado que todavia no fue confirmado. La respuesta de la entidad, que viaja con la
+// misma secuencia, hace de ack
+#[derive(Debug, Clone)]
+pub struct PendingMessage {
+    pub transaction_id: u64,
+    pub attempt: u32,
+    pub address: String,
+    pub buffer: Vec<u8>,
+    attempts: u32,
+    // None: se reintenta hasta que llegue el ack (las decisiones de la fase dos)
+    max_attempts: Option<u32>,
+    backoff: Duration,
+    next_retry: Instant,
+}
+
+// Lado del que envia: numera los mensajes y decide cuando reenviarlos, con backoff exponencial
+pub struct RetransmissionQueue {
+    next_sequence: u64,
+    pending: HashMap<u64, PendingMessage>,
+    // los que agotaron sus intentos sin ack, hasta que alguien los levante
+    exhausted: Vec<PendingMessage>,
+}
+
+impl RetransmissionQueue {
+    pub fn new() -> Self {
+        // arrancamos desde el reloj para que un lider nuevo no reuse las secuencias del anterior
+        let next_sequence = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(1);
+        RetransmissionQueue {
+            next_sequence,
+            pending: HashMap::new(),
+            exhausted: vec![],
+        }
+    }
+
+    pub fn next_sequence(&mut self) -> u64 {
+        self.next_sequence += 1;
+        self.next_sequence
+    }
+
+    pub fn track(
+        &mut self,
+        sequence: u64,
+        // id e intento de la transaccion
+        (transaction_id, attempt): (u64, u32),
+        address: String,
+        buffer: Vec<u8>,
+        max_attempts: Option<u32>,
+        now: Instant,
+    ) {
+        let backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+        self.pending.insert(
+            sequence,
+            PendingMessage {
+                transaction_id,
+                attempt,
+                address,
+                buffer,
+                attempts: 1,
+                max_attempts,
+                backoff,
+                next_retry: now + backoff,
+            },
+        );
+    }
+
+    // devuelve el mensaje confirmado, o None si ya lo habiamos confirmado (ack duplicado)
+    pub fn acknowledge(&mut self, sequence: u64) -> Option<PendingMessage> {
+        self.pending.remove(&sequence)
+    }
+
+    // se deja de reenviar todo lo de un intento de una transaccion (por ejemplo los prepare ya
+    // decididos). Lo de otros intentos sigue, como el abort del anterior que no confirmaron
+    pub fn forget_attempt(&mut self, transaction_id: u64, attempt: u32) {
+        self.pending.retain(|_, message| {
+            message.transaction_id != transaction_id || message.attempt != attempt
+        });
+    }
+
+    pub fn pending_for(&self, transaction_id: u64) -> usize {
+        self.pending
+            .values()
+            .filter(|message| message.transaction_id == transaction_id)
+            .count()
+    }
+
+    // mensajes a reenviar ahora, los que agotaron sus intentos pasan a exhausted
+    pub fn due(&mut self, now: Instant) -> Vec<(String, Vec<u8>)> {
+        let mut res = vec![];
+        let exhausted = &mut self.exhausted;
+        self.pending.retain(|_, message| {
+            if message.next_retry > now {
+                return true;
+            }
+            if let Some(max_attempts) = message.max_attempts {
+                if message.attempts >= max_attempts {
+                    exhausted.push(message.clone());
+                    return false;
+                }
+            }
+            message.attempts += 1;
+            message.backoff = (message.backoff * 2).min(Duration::from_millis(MAX_BACKOFF_MS));
+            message.next_retry = now + message.backoff;
+            res.push((message.address.clone(), message.buffer.clone()));
+            true
+        });
+        res
+    }
+
+    pub fn take_exhausted(&mut self) -> Vec<PendingMessage> {
+        std::mem::take(&mut self.exhausted)
+    }
+}
+
+impl Default for RetransmissionQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Lado del que recibe: recuerda las ultimas secuencias vistas de cada par (y lo que se les
+// contesto), asi un mensaje reenviado no se procesa dos veces
+pub struct DuplicateFilter<P: Hash + Eq + Clone, T> {
+    capacity: usize,
+    order: VecDeque<(P, u64)>,
+    seen: HashMap<(P, u64), T>,
+}
+
+impl<P: Hash + Eq + Clone, T> DuplicateFilter<P, T> {
+    pub fn new(capacity: usize) -> Self {
+        DuplicateFilter {
+            capacity,
+            order: VecDeque::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, peer: &P, sequence: u64) -> Option<&T> {
+        self.seen.get(&(peer.clone(), sequence))
+    }
+
+    pub fn insert(&mut self, peer: P, sequence: u64, value: T) {
+        let key = (peer, sequence);
+        if self.seen.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reliable_delivery::{DuplicateFilter, RetransmissionQueue};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_unacknowledged_messages_are_retransmitted_with_backoff() {
+        let mut queue = RetransmissionQueue::new();
+        let now = Instant::now();
+        let sequence = queue.next_sequence();
+        queue.track(sequence, (1, 0), "addr".to_string(), vec![1], None, now);
+
+        assert!(queue.due(now).is_empty());
+        assert_eq!(queue.due(now + Duration::from_millis(500)).len(), 1);
+        // la segunda espera es el doble
+        assert!(queue.due(now + Duration::from_millis(1000)).is_empty());
+        assert_eq!(queue.due(now + Duration::from_millis(1500)).len(), 1);
+
+        assert!(queue.acknowledge(sequence).is_some());
+        assert!(queue.acknowledge(sequence).is_none());
+        assert!(queue.due(now + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_limited_messages_are_dropped_after_max_attempts() {
+        let mut queue = RetransmissionQueue::new();
+        let now = Instant::now();
+        let sequence = queue.next_sequence();
+        queue.track(sequence, (1, 0), "addr".to_string(), vec![1], Some(2), now);
+
+        assert_eq!(queue.due(now + Duration::from_secs(1)).len(), 1);
+        assert!(queue.take_exhausted().is_empty());
+        assert!(queue.due(now + Duration::from_secs(10)).is_empty());
+        assert_eq!(queue.pending_for(1), 0);
+        let exhausted = queue.take_exhausted();
+        assert_eq!(exhausted.len(), 1);
+        assert_eq!(exhausted[0].transaction_id, 1);
+        assert!(queue.take_exhausted().is_empty());
+    }
+
+    #[test]
+    fn test_forget_attempt() {
+        let mut queue = RetransmissionQueue::new();
+        let now = Instant::now();
+        let first = queue.next_sequence();
+        let second = queue.next_sequence();
+        let previous = queue.next_sequence();
+        queue.track(first, (1, 1), "a".to_string(), vec![], None, now);
+        queue.track(second, (2, 1), "a".to_string(), vec![], None, now);
+        // el abort del intento anterior, todavia sin confirmar
+        queue.track(previous, (1, 0), "a".to_string(), vec![], None, now);
+
+        queue.forget_attempt(1, 1);
+        assert_eq!(queue.pending_for(1), 1);
+        assert_eq!(queue.pending_for(2), 1);
+        assert_eq!(queue.acknowledge(previous).unwrap().attempt, 0);
+    }
+
+    #[test]
+    fn test_duplicate_filter_evicts_oldest() {
+        let mut filter = DuplicateFilter::new(2);
+        filter.insert("peer", 1, "uno");
+        filter.insert("peer", 2, "dos");
+        assert_eq!(filter.get(&"peer", 1), Some(&"uno"));
+        assert_eq!(filter.get(&"other", 1), None);
+
+        filter.insert("peer", 3, "tres");
+        assert_eq!(filter.get(&"peer", 1), None);
+        assert_eq!(filter.get(&"peer", 3), Some(&"tres"));
+    }
+}
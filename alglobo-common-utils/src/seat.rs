@@ -0,0 +1,57 @@
+use std::fmt;
+use std::str::FromStr;
+
+const SEAT_SEPARATOR: char = ';';
+
+// Un asiento en un vuelo, en una clase tarifaria. Viaja a la aerolinea como "<vuelo>;<clase>"
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Seat {
+    pub flight: String,
+    pub fare_class: String,
+}
+
+impl Seat {
+    pub fn new(flight: String, fare_class: String) -> Result<Self, String> {
+        if flight.is_empty() {
+            return Err("The seat has no flight".to_string());
+        }
+        // la clase va ultima, no puede tener el separador
+        if fare_class.is_empty() || fare_class.contains(SEAT_SEPARATOR) {
+            return Err(format!("Invalid fare class: {}", fare_class));
+        }
+        Ok(Seat { flight, fare_class })
+    }
+}
+
+impl fmt::Display for Seat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}", self.flight, SEAT_SEPARATOR, self.fare_class)
+    }
+}
+
+impl FromStr for Seat {
+    type Err = String;
+
+    fn from_str(seat: &str) -> Result<Self, Self::Err> {
+        match seat.rsplit_once(SEAT_SEPARATOR) {
+            Some((flight, fare_class)) => Seat::new(flight.to_string(), fare_class.to_string()),
+            None => Err(format!("Invalid seat: {}", seat)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::seat::Seat;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_seat_roundtrip() {
+        let seat = Seat::new("AR;1140".to_string(), "Y".to_string()).unwrap();
+        assert_eq!(seat.to_string(), "AR;1140;Y");
+        assert_eq!(Seat::from_str("AR;1140;Y").unwrap(), seat);
+        assert!(Seat::from_str("AR1140").is_err());
+        assert!(Seat::from_str("AR1140;").is_err());
+        assert!(Seat::from_str(";Y").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+const STAY_SEPARATOR: char = ';';
+
+// Un dia del calendario, guardado como dias desde 1970-01-01. Se escribe YYYY-MM-DD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Date(i64);
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    // el algoritmo de calendario civil de Howard Hinnant
+    fn from_civil(year: i64, month: i64, day: i64) -> Self {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Date(era * 146097 + day_of_era - 719468)
+    }
+
+    fn to_civil(self) -> (i64, i64, i64) {
+        let days = self.0 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+
+    pub fn next(self) -> Self {
+        Date(self.0 + 1)
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(date: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date (YYYY-MM-DD): {}", date);
+        let parts = date
+            .split('-')
+            .map(|part| i64::from_str(part).map_err(|_| invalid()))
+            .collect::<Result<Vec<i64>, String>>()?;
+        match parts[..] {
+            [year, month, day]
+                if (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day) =>
+            {
+                Ok(Date::from_civil(year, month, day))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(date: String) -> Result<Self, Self::Error> {
+        Date::from_str(&date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.to_civil();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+// Una estadia en un hotel: se ocupa una habitacion cada noche desde check_in hasta la
+// anterior a check_out. Viaja al hotel como "<hotel>;<check_in>;<check_out>"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stay {
+    pub property: String,
+    pub check_in: Date,
+    pub check_out: Date,
+}
+
+impl Stay {
+    pub fn new(property: String, check_in: Date, check_out: Date) -> Result<Self, String> {
+        if property.is_empty() {
+            return Err("The stay has no property".to_string());
+        }
+        if check_out <= check_in {
+            return Err(format!(
+                "check_out {} is not after check_in {}",
+                check_out, check_in
+            ));
+        }
+        Ok(Stay {
+            property,
+            check_in,
+            check_out,
+        })
+    }
+
+    pub fn nights(&self) -> Vec<Date> {
+        let mut nights = vec![];
+        let mut night = self.check_in;
+        while night < self.check_out {
+            nights.push(night);
+            night = night.next();
+        }
+        nights
+    }
+}
+
+impl fmt::Display for Stay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}",
+            self.property, STAY_SEPARATOR, self.check_in, STAY_SEPARATOR, self.check_out
+        )
+    }
+}
+
+impl FromStr for Stay {
+    type Err = String;
+
+    // el nombre del hotel puede tener el separador, las fechas no
+    fn from_str(stay: &str) -> Result<Self, Self::Err> {
+        let mut parts = stay.rsplitn(3, STAY_SEPARATOR);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(check_out), Some(check_in), Some(property)) => Stay::new(
+                property.to_string(),
+                Date::from_str(check_in)?,
+                Date::from_str(check_out)?,
+            ),
+            _ => Err(format!("Invalid stay: {}", stay)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stay::{Date, Stay};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_dates_roundtrip_and_are_validated() {
+        for date in ["1970-01-01", "2000-02-29", "2024-12-31", "1969-07-20"] {
+            assert_eq!(Date::from_str(date).unwrap().to_string(), date);
+        }
+        assert_eq!(
+            Date::from_str("2024-02-28").unwrap().next().next(),
+            Date::from_str("2024-03-01").unwrap()
+        );
+        assert!(Date::from_str("2023-02-29").is_err());
+        assert!(Date::from_str("2024-13-01").is_err());
+        assert!(Date::from_str("2024-1").is_err());
+        assert!(Date::from_str("ayer").is_err());
+    }
+
+    #[test]
+    fn test_stay_nights() {
+        let stay = Stay::from_str("Hotel; Centro;2024-12-30;2025-01-02").unwrap();
+        assert_eq!(stay.property, "Hotel; Centro");
+        let nights = stay
+            .nights()
+            .iter()
+            .map(|night| night.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(nights, vec!["2024-12-30", "2024-12-31", "2025-01-01"]);
+        assert_eq!(Stay::from_str(&stay.to_string()).unwrap(), stay);
+
+        assert!(Stay::from_str("Centro;2025-01-02;2025-01-02").is_err());
+        assert!(Stay::from_str(";2025-01-01;2025-01-02").is_err());
+        assert!(Stay::from_str("2025-01-01;2025-01-02").is_err());
+    }
+}
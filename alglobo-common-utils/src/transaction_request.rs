@@ -1,47 +1,120 @@
 use crate::entity_payload::EntityPayload;
 use crate::entity_type::EntityType;
+use crate::money::{Currency, Money};
+use crate::seat::Seat;
+use crate::stay::{Date, Stay};
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct TransactionRequest {
     id: u64,
     hotel_cost: u64,
     bank_cost: u64,
     airline_cost: u64,
+    // los tres costos estan en unidades minimas de esta moneda
+    currency: Currency,
+    // la cuenta del cliente en el banco
+    #[serde(default)]
+    customer: Option<u64>,
+    // la estadia en el hotel: el hotel y las noches desde check_in hasta antes de check_out
+    #[serde(default)]
+    property: Option<String>,
+    #[serde(default)]
+    check_in: Option<Date>,
+    #[serde(default)]
+    check_out: Option<Date>,
+    // el asiento en el avion
+    #[serde(default)]
+    flight: Option<String>,
+    #[serde(default)]
+    fare_class: Option<String>,
+    // las filas reintentadas traen el intento, las del archivo original no
+    #[serde(default)]
+    attempt: u32,
 }
 
 impl TransactionRequest {
-    pub fn get_entities_data(&self) -> Vec<(EntityType, EntityPayload)> {
-        let mut data = vec![];
-        if self.hotel_cost > 0 {
-            data.push((
+    fn costs(&self) -> [(EntityType, Money); 3] {
+        [
+            (
                 EntityType::Hotel,
-                EntityPayload::new(self.id, self.hotel_cost),
-            ));
-        }
-        if self.bank_cost > 0 {
-            data.push((
-                EntityType::Bank,
-                EntityPayload::new(self.id, self.bank_cost),
-            ));
-        }
-        if self.airline_cost > 0 {
-            data.push((
+                Money::new(self.hotel_cost, self.currency),
+            ),
+            (EntityType::Bank, Money::new(self.bank_cost, self.currency)),
+            (
                 EntityType::Airline,
-                EntityPayload::new(self.id, self.airline_cost),
-            ));
+                Money::new(self.airline_cost, self.currency),
+            ),
+        ]
+    }
+
+    pub fn get_entities_data(&self) -> Vec<(EntityType, EntityPayload)> {
+        self.costs()
+            .into_iter()
+            .filter(|(_, cost)| !cost.is_zero())
+            .map(|(entity_type, cost)| {
+                (
+                    entity_type,
+                    EntityPayload::new(self.id, cost)
+                        .with_attempt(self.attempt)
+                        .with_details(self.details(entity_type)),
+                )
+            })
+            .collect()
+    }
+
+    // lo que necesita cada entidad para reservar, en el formato que entiende cada una
+    fn details(&self, entity_type: EntityType) -> String {
+        let details = match entity_type {
+            EntityType::Bank => self.customer.map(|customer| customer.to_string()),
+            EntityType::Hotel => self.stay().map(|stay| stay.to_string()),
+            EntityType::Airline => self.seat().map(|seat| seat.to_string()),
+        };
+        details.unwrap_or_default()
+    }
+
+    // None si la fila no trae la estadia completa y valida
+    pub fn stay(&self) -> Option<Stay> {
+        match (&self.property, self.check_in, self.check_out) {
+            (Some(property), Some(check_in), Some(check_out)) => {
+                Stay::new(property.clone(), check_in, check_out).ok()
+            }
+            _ => None,
+        }
+    }
+
+    // lo que cuesta la transaccion entera; None si no entra en 64 bits
+    // None si la fila no trae vuelo y clase validos
+    pub fn seat(&self) -> Option<Seat> {
+        match (&self.flight, &self.fare_class) {
+            (Some(flight), Some(fare_class)) => Seat::new(flight.clone(), fare_class.clone()).ok(),
+            _ => None,
         }
-        data
+    }
+
+    pub fn get_total(&self) -> Option<Money> {
+        self.costs()
+            .iter()
+            .try_fold(Money::new(0, self.currency), |total, (_, cost)| {
+                total.checked_add(cost)
+            })
     }
 
     pub fn get_transaction_id(&self) -> u64 {
         self.id
     }
+
+    pub fn get_attempt(&self) -> u32 {
+        self.attempt
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::TransactionRequest;
+    use crate::money::{Money, DEFAULT_CURRENCY};
+    use crate::stay::Date;
+    use std::str::FromStr;
 
     #[test]
     fn test_get_entities_data() {
@@ -50,32 +123,102 @@ mod tests {
             hotel_cost: 10,
             bank_cost: 0,
             airline_cost: 0,
+            currency: DEFAULT_CURRENCY,
+            customer: None,
+            property: None,
+            check_in: None,
+            check_out: None,
+            flight: None,
+            fare_class: None,
+            attempt: 0,
         };
         let data = &t_hotel.get_entities_data()[0];
         assert_eq!(format!("{:?}", data.0), "Hotel");
-        assert_eq!(data.1.cost, 10);
+        assert_eq!(data.1.cost, Money::new(10, DEFAULT_CURRENCY));
         assert_eq!(data.1.transaction_id, 0);
+        assert_eq!(data.1.details, "");
+
+        let t_stay = TransactionRequest {
+            property: Some("Centro".to_string()),
+            check_in: Date::from_str("2025-01-01").ok(),
+            check_out: Date::from_str("2025-01-03").ok(),
+            ..t_hotel
+        };
+        assert_eq!(
+            t_stay.get_entities_data()[0].1.details,
+            "Centro;2025-01-01;2025-01-03"
+        );
 
         let t_banco = TransactionRequest {
             id: 1,
             hotel_cost: 0,
             bank_cost: 10,
             airline_cost: 0,
+            currency: DEFAULT_CURRENCY,
+            customer: Some(77),
+            property: None,
+            check_in: None,
+            check_out: None,
+            flight: None,
+            fare_class: None,
+            attempt: 0,
         };
         let data = &t_banco.get_entities_data()[0];
         assert_eq!(format!("{:?}", data.0), "Bank");
-        assert_eq!(data.1.cost, 10);
+        assert_eq!(data.1.cost, Money::new(10, DEFAULT_CURRENCY));
         assert_eq!(data.1.transaction_id, 1);
+        assert_eq!(data.1.details, "77");
 
         let t_airline = TransactionRequest {
             id: 2,
             hotel_cost: 0,
             bank_cost: 0,
             airline_cost: 10,
+            currency: DEFAULT_CURRENCY,
+            customer: None,
+            property: None,
+            check_in: None,
+            check_out: None,
+            flight: None,
+            fare_class: None,
+            attempt: 0,
         };
         let data = &t_airline.get_entities_data()[0];
         assert_eq!(format!("{:?}", data.0), "Airline");
-        assert_eq!(data.1.cost, 10);
+        assert_eq!(data.1.cost, Money::new(10, DEFAULT_CURRENCY));
         assert_eq!(data.1.transaction_id, 2);
+        let t_seat = TransactionRequest {
+            flight: Some("AR1140".to_string()),
+            fare_class: Some("Y".to_string()),
+            ..t_airline
+        };
+        assert_eq!(t_seat.get_entities_data()[0].1.details, "AR1140;Y");
+    }
+
+    #[test]
+    fn test_total_adds_every_cost() {
+        let transaction = TransactionRequest {
+            id: 0,
+            hotel_cost: 10,
+            bank_cost: 20,
+            airline_cost: 30,
+            currency: DEFAULT_CURRENCY,
+            customer: None,
+            property: None,
+            check_in: None,
+            check_out: None,
+            flight: None,
+            fare_class: None,
+            attempt: 0,
+        };
+        assert_eq!(
+            transaction.get_total(),
+            Some(Money::new(60, DEFAULT_CURRENCY))
+        );
+        let transaction = TransactionRequest {
+            airline_cost: u64::MAX,
+            ..transaction
+        };
+        assert_eq!(transaction.get_total(), None);
     }
 }
@@ -1,4 +1,5 @@
 use crate::entity_payload::be_byte_buffer_to_u64;
+use crate::protocol_error::ProtocolError;
 use crate::transaction_state::TransactionState;
 
 // 9 bytes de repuesta
@@ -19,14 +20,19 @@ impl TransactionResponse {
     }
 }
 
-impl From<Vec<u8>> for TransactionResponse {
-    fn from(payload_buffer: Vec<u8>) -> Self {
+impl TryFrom<Vec<u8>> for TransactionResponse {
+    type Error = ProtocolError;
+
+    fn try_from(payload_buffer: Vec<u8>) -> Result<Self, Self::Error> {
         if payload_buffer.len() != TRANSACTION_RESPONSE_PAYLOAD_SIZE {
-            panic!("Invalid buffer received when deserializing response");
+            return Err(ProtocolError::InvalidLength {
+                expected: TRANSACTION_RESPONSE_PAYLOAD_SIZE,
+                received: payload_buffer.len(),
+            });
         }
-        let state: TransactionState = payload_buffer[0].into();
+        let state = TransactionState::try_from(payload_buffer[0])?;
         let id = be_byte_buffer_to_u64(&payload_buffer[1..]);
-        TransactionResponse::new(id, state)
+        Ok(TransactionResponse::new(id, state))
     }
 }
 
@@ -1,6 +1,10 @@
+use crate::protocol_error::ProtocolError;
+
 const PREPARE: u8 = 0x0;
 const COMMIT: u8 = 0x1;
 const ABORT: u8 = 0x2;
+const ERROR: u8 = 0x3;
+const PRECOMMIT: u8 = 0x4;
 
 #[derive(Debug, Copy, Clone)]
 pub enum TransactionState {
@@ -9,15 +13,22 @@ pub enum TransactionState {
     Accept, // idem wait
     Commit,
     Abort,
+    Error, // NACK: el mensaje recibido no respeta el protocolo
+    // solo en tres fases: todos votaron commit, pero todavia no se commitea
+    PreCommit,
 }
 
-impl From<u8> for TransactionState {
-    fn from(payload_byte: u8) -> Self {
+impl TryFrom<u8> for TransactionState {
+    type Error = ProtocolError;
+
+    fn try_from(payload_byte: u8) -> Result<Self, ProtocolError> {
         match payload_byte {
-            PREPARE => TransactionState::Prepare,
-            COMMIT => TransactionState::Commit,
-            ABORT => TransactionState::Abort,
-            _ => panic!("Could not deserialize unknown byte into state"),
+            PREPARE => Ok(TransactionState::Prepare),
+            COMMIT => Ok(TransactionState::Commit),
+            ABORT => Ok(TransactionState::Abort),
+            ERROR => Ok(TransactionState::Error),
+            PRECOMMIT => Ok(TransactionState::PreCommit),
+            _ => Err(ProtocolError::UnknownState(payload_byte)),
         }
     }
 }
@@ -28,21 +39,33 @@ impl From<TransactionState> for u8 {
             TransactionState::Prepare => PREPARE,
             TransactionState::Commit => COMMIT,
             TransactionState::Abort => ABORT,
+            TransactionState::Error => ERROR,
+            TransactionState::PreCommit => PRECOMMIT,
             _ => panic!("State is not serializable"),
         }
     }
 }
 
+#[cfg(test)]
 mod tests {
 
     #[test]
     fn test_transaction_state() {
         let mut s: crate::transaction_state::TransactionState;
-        s = crate::transaction_state::TransactionState::from(0);
+        s = crate::transaction_state::TransactionState::try_from(0).unwrap();
         assert_eq!(format!("{:?}", s), "Prepare");
-        s = crate::transaction_state::TransactionState::from(1);
+        s = crate::transaction_state::TransactionState::try_from(1).unwrap();
         assert_eq!(format!("{:?}", s), "Commit");
-        s = crate::transaction_state::TransactionState::from(2);
+        s = crate::transaction_state::TransactionState::try_from(2).unwrap();
         assert_eq!(format!("{:?}", s), "Abort");
+        s = crate::transaction_state::TransactionState::try_from(3).unwrap();
+        assert_eq!(format!("{:?}", s), "Error");
+        s = crate::transaction_state::TransactionState::try_from(4).unwrap();
+        assert_eq!(format!("{:?}", s), "PreCommit");
+    }
+
+    #[test]
+    fn test_unknown_byte_is_a_protocol_error() {
+        assert!(crate::transaction_state::TransactionState::try_from(0xff).is_err());
     }
 }
@@ -0,0 +1,162 @@
+use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::transaction_state::TransactionState;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ENTITY_SEPARATOR: &str = ";";
+
+// Por que el coordinador aborto una transaccion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbortReason {
+    // entidades que votaron en contra
+    Refused(Vec<EntityType>),
+    // entidades que no votaron antes del timeout
+    Timeout(Vec<EntityType>),
+    // no se le pudo hacer llegar el prepare
+    SendFailure(EntityType),
+    // contesto algo que no respeta el protocolo
+    ProtocolError(EntityType),
+    // no se pudo persistir la decision
+    NotPersisted,
+    // la abortamos al recuperar lo que dejo en vuelo el lider anterior
+    Recovered,
+}
+
+fn join_entities(entities: &[EntityType]) -> String {
+    entities
+        .iter()
+        .map(|entity| entity.to_string())
+        .collect::<Vec<String>>()
+        .join(ENTITY_SEPARATOR)
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbortReason::Refused(entities) => write!(f, "refused:{}", join_entities(entities)),
+            AbortReason::Timeout(entities) => write!(f, "timeout:{}", join_entities(entities)),
+            AbortReason::SendFailure(entity) => write!(f, "send_failure:{}", entity),
+            AbortReason::ProtocolError(entity) => write!(f, "protocol_error:{}", entity),
+            AbortReason::NotPersisted => write!(f, "not_persisted"),
+            AbortReason::Recovered => write!(f, "recovered"),
+        }
+    }
+}
+
+impl AbortReason {
+    // a partir de los votos, None si todos votaron lo esperado
+    pub fn from_votes(
+        votes: &HashMap<EntityType, TransactionState>,
+        expected: TransactionState,
+    ) -> Option<AbortReason> {
+        let mut errors = votes
+            .iter()
+            .filter(|(_, state)| matches!(state, TransactionState::Error))
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<EntityType>>();
+        errors.sort_by_key(|entity| entity.to_string());
+        if let Some(entity) = errors.first() {
+            return Some(AbortReason::ProtocolError(*entity));
+        }
+        let mut refused = votes
+            .iter()
+            .filter(|(_, state)| {
+                std::mem::discriminant(*state) != std::mem::discriminant(&expected)
+            })
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<EntityType>>();
+        if refused.is_empty() {
+            return None;
+        }
+        refused.sort_by_key(|entity| entity.to_string());
+        Some(AbortReason::Refused(refused))
+    }
+}
+
+// Lo que se guarda de una transaccion abortada: el motivo, lo que votaron y cuando se decidio
+#[derive(Debug, Clone)]
+pub struct TransactionFailure {
+    pub reason: AbortReason,
+    pub votes: HashMap<EntityType, TransactionState>,
+    pub decided_at: SystemTime,
+}
+
+impl TransactionFailure {
+    pub fn new(reason: AbortReason, votes: HashMap<EntityType, TransactionState>) -> Self {
+        TransactionFailure {
+            reason,
+            votes,
+            decided_at: SystemTime::now(),
+        }
+    }
+
+    // "Bank:Abort;Hotel:Commit", ordenado para que sea estable entre corridas
+    pub fn votes_column(&self) -> String {
+        let mut votes = self
+            .votes
+            .iter()
+            .map(|(entity, state)| format!("{}:{:?}", entity, state))
+            .collect::<Vec<String>>();
+        votes.sort();
+        votes.join(ENTITY_SEPARATOR)
+    }
+
+    // milisegundos desde epoch
+    pub fn timestamp_column(&self) -> String {
+        self.decided_at
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abort_reason::{AbortReason, TransactionFailure};
+    use alglobo_common_utils::entity_type::EntityType;
+    use alglobo_common_utils::transaction_state::TransactionState;
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_reason_from_votes() {
+        let mut votes = HashMap::new();
+        votes.insert(EntityType::Hotel, TransactionState::Commit);
+        votes.insert(EntityType::Bank, TransactionState::Commit);
+        assert_eq!(
+            AbortReason::from_votes(&votes, TransactionState::Commit),
+            None
+        );
+
+        votes.insert(EntityType::Hotel, TransactionState::Abort);
+        votes.insert(EntityType::Bank, TransactionState::Abort);
+        let reason = AbortReason::from_votes(&votes, TransactionState::Commit).unwrap();
+        assert_eq!(
+            reason,
+            AbortReason::Refused(vec![EntityType::Bank, EntityType::Hotel])
+        );
+        assert_eq!(reason.to_string(), "refused:Bank;Hotel");
+
+        // un error de protocolo pesa mas que un voto en contra
+        votes.insert(EntityType::Airline, TransactionState::Error);
+        assert_eq!(
+            AbortReason::from_votes(&votes, TransactionState::Commit),
+            Some(AbortReason::ProtocolError(EntityType::Airline))
+        );
+    }
+
+    #[test]
+    fn test_failure_columns() {
+        let mut votes = HashMap::new();
+        votes.insert(EntityType::Hotel, TransactionState::Commit);
+        votes.insert(EntityType::Bank, TransactionState::Abort);
+        let mut failure = TransactionFailure::new(AbortReason::Timeout(vec![]), votes);
+        failure.decided_at = UNIX_EPOCH + Duration::from_millis(1500);
+
+        assert_eq!(failure.votes_column(), "Bank:Abort;Hotel:Commit");
+        assert_eq!(failure.timestamp_column(), "1500");
+        assert_eq!(failure.reason.to_string(), "timeout:");
+    }
+}
@@ -1,12 +1,15 @@
-use crate::beater_responder::ResponderState::{Continue, FindNew, StartPing};
+use crate::beater_responder::ResponderState::{Continue, FindNew, StartPing, Status};
+use crate::cluster_status::{encode_status, STATUS};
 use crate::ok_timeout_handler::{OkTimeoutHandler, RegisterOkReceived};
 use crate::pinger_finder::{Find, PingerFinder, SetNewLeader};
-use crate::{id_to_ctrladdr, Ping};
+use crate::Ping;
 use actix::{
     Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, ResponseActFuture,
     WrapFuture,
 };
+use alglobo_common_utils::cluster_config::ClusterConfig;
 use futures::future::join_all;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::UdpSocket;
 
@@ -15,10 +18,13 @@ use tokio::net::UdpSocket;
 // y viceversa
 pub struct BeaterResponder {
     pid: u8,
+    // el ultimo lider conocido, solo para contestar las consultas de estado
+    leader: Option<u8>,
     data_socket: Arc<UdpSocket>,
     coordinator_socket: Arc<UdpSocket>,
     ok_timeout_handler_addr: Addr<OkTimeoutHandler>,
     pinger_finder_addr: Addr<PingerFinder>,
+    config: Arc<ClusterConfig>,
 }
 
 impl BeaterResponder {
@@ -28,13 +34,16 @@ impl BeaterResponder {
         coordinator_socket: Arc<UdpSocket>,
         ok_timeout_handler_addr: Addr<OkTimeoutHandler>,
         pinger_finder_addr: Addr<PingerFinder>,
+        config: Arc<ClusterConfig>,
     ) -> Self {
         BeaterResponder {
             pid,
+            leader: None,
             data_socket,
             coordinator_socket,
             ok_timeout_handler_addr,
             pinger_finder_addr,
+            config,
         }
     }
 }
@@ -87,6 +96,7 @@ enum ResponderState {
     FindNew,
     Continue,
     StartPing(u8),
+    Status(SocketAddr),
 }
 
 impl Handler<Responder> for BeaterResponder {
@@ -101,7 +111,7 @@ impl Handler<Responder> for BeaterResponder {
             let mut buf = [0; 2];
             if let Ok((_, addr)) = sock.recv_from(&mut buf).await {
                 let id_from = buf[1];
-                match &buf[0] {
+                match buf[0] {
                     b'O' => {
                         println!("[PID {}] received OK from {}", my_pid, id_from);
                         addr_timeout_handler.do_send(RegisterOkReceived {});
@@ -125,6 +135,7 @@ impl Handler<Responder> for BeaterResponder {
                         // notifico por queue que hay un lider nuevo
                         StartPing(id_from)
                     }
+                    STATUS => Status(addr),
                     _ => Continue,
                 }
             } else {
@@ -134,13 +145,25 @@ impl Handler<Responder> for BeaterResponder {
         Box::pin(fut.into_actor(self).map(|state, me, ctx| {
             match state {
                 FindNew => {
+                    me.leader = None;
                     me.pinger_finder_addr.do_send(Find::new(ctx.address()));
                 }
                 StartPing(ping_id) => {
+                    me.leader = Some(ping_id);
                     me.pinger_finder_addr.do_send(SetNewLeader::new(ping_id));
                     me.pinger_finder_addr
                         .do_send(Ping::new(ping_id, ctx.address()));
                 }
+                Status(addr) => {
+                    let socket = me.coordinator_socket.clone();
+                    let response = encode_status(me.pid, me.leader);
+                    ctx.spawn(
+                        async move {
+                            let _ = socket.send_to(response.as_slice(), addr).await;
+                        }
+                        .into_actor(me),
+                    );
+                }
                 _ => {}
             }
             ctx.address().do_send(msg)
@@ -164,19 +187,21 @@ impl Handler<BroadcastCoordinator> for BeaterResponder {
     type Result = ResponseActFuture<Self, ()>;
 
     fn handle(&mut self, msg: BroadcastCoordinator, _ctx: &mut Self::Context) -> Self::Result {
+        self.leader = Some(self.pid);
         let socket = self.coordinator_socket.clone();
         let my_pid = self.pid;
+        let addresses = msg
+            .all_pids
+            .iter()
+            .filter(|&&other_pid| other_pid != my_pid)
+            .filter_map(|other_pid| self.config.control_address(*other_pid))
+            .collect::<Vec<String>>();
 
         let fut = async move {
             let buffer_coordinator = vec![b'C', my_pid];
             let mut futures_buffer = vec![];
-            for other_pid in msg.all_pids {
-                if other_pid != my_pid {
-                    futures_buffer.push(socket.send_to(
-                        buffer_coordinator.as_slice(),
-                        id_to_ctrladdr(other_pid as usize),
-                    ));
-                }
+            for addr in &addresses {
+                futures_buffer.push(socket.send_to(buffer_coordinator.as_slice(), addr));
             }
             join_all(futures_buffer).await;
         };
@@ -1,6 +1,15 @@
-use crate::entity_sender::RegisterFileHandles;
-use crate::file_reader::ReadDoneTransactions;
+use crate::abort_reason::{AbortReason, TransactionFailure};
+use crate::decision_log::DecisionLog;
+use crate::done_ledger::FinalState;
+use crate::entity_sender::{BroadcastTransactionState, RegisterFileReader};
+use crate::file_reader::{EnableCheckpoints, ReadDoneTransactions};
 use crate::file_writer::FileWriter;
+use crate::in_flight_limit::InFlightLimit;
+use crate::input_source::InputSpec;
+use crate::replicator::{Replicator, SyncDecisionLog};
+use crate::retry_policy::RetryPolicy;
+use crate::transaction_coordinator::{CommitProtocol, RecoverPendingDecisions};
+use crate::transaction_dispatcher::{SaveDoneTransactions, SetRateLimit};
 use crate::{
     EntityReceiver, EntitySender, FileReader, LogMessage, LoggerActor, ReadStatus,
     ReceiveEntityResponse, ServeNextTransaction, StatisticsHandler, TransactionCoordinator,
@@ -8,14 +17,21 @@ use crate::{
 };
 use actix::{Actor, Addr, Context, Handler, Message};
 use actix_rt::Arbiter;
-use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::cluster_config::ClusterConfig;
+use alglobo_common_utils::transaction_state::TransactionState;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
 use tokio::sync::oneshot;
 
 pub struct Bootstrapper {
-    file_path: String,
+    input: InputSpec,
+    pid: u8,
+    replicator: Addr<Replicator>,
+    config: Arc<ClusterConfig>,
+    // se elige al levantar la replica, y vale para todo lo que decida como lider
+    commit_protocol: CommitProtocol,
 }
 
 impl Actor for Bootstrapper {
@@ -23,21 +39,44 @@ impl Actor for Bootstrapper {
 }
 
 impl Bootstrapper {
-    pub fn new(file_path: String) -> Self {
-        Bootstrapper { file_path }
+    pub fn new(
+        input: InputSpec,
+        pid: u8,
+        replicator: Addr<Replicator>,
+        config: Arc<ClusterConfig>,
+    ) -> Self {
+        Bootstrapper {
+            input,
+            pid,
+            replicator,
+            config,
+            commit_protocol: CommitProtocol::TwoPhase,
+        }
     }
 
-    async fn run(logger_addr: Addr<LoggerActor>, file_path: String) {
-        let addr = "localhost:8888".to_string();
-        let mut entity_addresses = HashMap::new();
-        entity_addresses.insert(EntityType::Hotel, "localhost:1234".to_string());
-        entity_addresses.insert(EntityType::Bank, "localhost:1235".to_string());
-        entity_addresses.insert(EntityType::Airline, "localhost:1236".to_string());
+    pub fn with_commit_protocol(mut self, commit_protocol: CommitProtocol) -> Self {
+        self.commit_protocol = commit_protocol;
+        self
+    }
+
+    async fn run(
+        logger_addr: Addr<LoggerActor>,
+        input: InputSpec,
+        pid: u8,
+        replicator: Addr<Replicator>,
+        config: Arc<ClusterConfig>,
+        commit_protocol: CommitProtocol,
+    ) {
+        let addr = match config.replica(pid) {
+            Some(replica) => replica.entity_address.clone(),
+            None => panic!("ERROR: la replica {} no esta en la configuracion", pid),
+        };
+        let entity_addresses = config.entity_addresses();
 
         let sock = match UdpSocket::bind(&addr).await {
             Ok(sock) => sock,
             Err(what) => {
-                logger_addr.do_send(LogMessage::new(format!(
+                logger_addr.do_send(LogMessage::error(format!(
                     "ERROR bindeando en {}: {}",
                     addr, what
                 )));
@@ -51,7 +90,24 @@ impl Bootstrapper {
 
         let log_c = logger_addr.clone();
 
-        let coordinator_addr = TransactionCoordinator::new(log_c).start();
+        let decision_log = match DecisionLog::new(&config.files.decision_log(pid)) {
+            Ok(decision_log) => decision_log,
+            Err(e) => {
+                logger_addr.do_send(LogMessage::error(format!("ERROR: {}", e)));
+                panic!("ERROR: {}", e);
+            }
+        };
+        let coordinator_addr = TransactionCoordinator::new(
+            log_c,
+            decision_log,
+            replicator.clone(),
+            Duration::from_secs(config.timeouts.transaction_s),
+        )
+        .with_commit_protocol(commit_protocol)
+        .with_log_compaction(Duration::from_secs(
+            config.pipeline.ledger_compaction_interval_s,
+        ))
+        .start();
 
         let log_c = logger_addr.clone();
         let write_stream = sock.clone();
@@ -60,23 +116,40 @@ impl Bootstrapper {
 
         let sender_addr = EntitySender::new(
             write_stream,
-            entity_addresses,
+            entity_addresses.clone(),
             log_c,
             coordinator_c,
-            statistics_handler_addr,
+            statistics_handler_addr.clone(),
         )
         .start();
 
-        let log_c = logger_addr.clone();
+        // el receiver le pasa al sender los acks de lo que mando
         let coordinator_c = coordinator_addr.clone();
-
-        let receiver_addr = EntityReceiver::new(read_stream, log_c, coordinator_c).start();
+        let receiver_addr = EntityReceiver::new(
+            read_stream,
+            &entity_addresses,
+            logger_addr.clone(),
+            coordinator_c,
+            sender_addr.clone(),
+        )
+        .start();
 
         receiver_addr.do_send(ReceiveEntityResponse {});
 
         let log_c = logger_addr.clone();
         let sender_clone = sender_addr.clone();
-        let transaction_dispatcher = TransactionDispatcher::new(sender_addr, log_c).start();
+        let transaction_dispatcher = TransactionDispatcher::new(
+            sender_addr,
+            statistics_handler_addr,
+            config.pipeline.duplicate_policy,
+            log_c,
+        )
+        .start();
+        // ya se validaron al cargar la configuracion
+        for (entity, rate) in config.pipeline.entity_rate_limits().unwrap_or_default() {
+            transaction_dispatcher.do_send(SetRateLimit::new(entity, Some(rate)));
+        }
+        let dispatcher_clone = transaction_dispatcher.clone();
 
         let log_c = logger_addr.clone();
         let log_c2 = logger_addr.clone();
@@ -84,38 +157,129 @@ impl Bootstrapper {
         let reader_writer_arbiter = Arbiter::new();
 
         let (tx_rd, rx_rd) = oneshot::channel();
-        let (tx_wr, rx_wr) = oneshot::channel();
 
+        let files = config.files.clone();
+        let done_transactions_path = config.files.done_transactions(pid);
+        let in_flight_limit = InFlightLimit::new(config.pipeline.max_in_flight);
+        let reader_limit = in_flight_limit.clone();
+        let retry_policy = if config.retry.enabled {
+            Some(RetryPolicy::new(&config.retry))
+        } else {
+            None
+        };
+        let ledger_compaction_interval =
+            Duration::from_secs(config.pipeline.ledger_compaction_interval_s);
+        let writer_replicator = replicator.clone();
         let reader_writer_execution = async move {
-            let file_writer = match FileWriter::new("failed_transactions.csv".to_string(), log_c) {
+            let file_writer = match FileWriter::new(
+                &files,
+                pid,
+                ledger_compaction_interval,
+                writer_replicator,
+                log_c,
+            ) {
                 Ok(file_writer) => file_writer,
                 Err(e) => {
-                    logger_addr.do_send(LogMessage::new(format!("ERROR: {}", e)));
+                    logger_addr.do_send(LogMessage::error(format!("ERROR: {}", e)));
                     panic!("ERROR: {}", e);
                 }
             }
             .start();
-            let file_writer_clone = file_writer.clone();
-
-            let file_reader =
-                match FileReader::new(file_path, transaction_dispatcher, file_writer, log_c2) {
-                    Ok(file_reader) => file_reader,
-                    Err(e) => {
-                        logger_addr.do_send(LogMessage::new(format!("ERROR: {}", e)));
-                        panic!("ERROR: {}", e);
-                    }
+
+            let file_reader = match FileReader::new(
+                input,
+                transaction_dispatcher,
+                file_writer,
+                done_transactions_path,
+                retry_policy,
+                reader_limit,
+                log_c2,
+            ) {
+                Ok(file_reader) => file_reader,
+                Err(e) => {
+                    logger_addr.do_send(LogMessage::error(format!("ERROR: {}", e)));
+                    panic!("ERROR: {}", e);
                 }
-                .start();
+            }
+            .start();
             file_reader.do_send(ReadDoneTransactions {});
             let _ = tx_rd.send(file_reader);
-            let _ = tx_wr.send(file_writer_clone);
         };
 
         let _ = reader_writer_arbiter.spawn(reader_writer_execution);
         let file_reader = rx_rd.await.unwrap();
-        let file_writer = rx_wr.await.unwrap();
 
-        sender_clone.do_send(RegisterFileHandles::new(file_reader.clone(), file_writer));
+        sender_clone.do_send(RegisterFileReader::new(file_reader.clone()));
+
+        // antes de leer filas nuevas terminamos lo que dejo en vuelo el lider anterior, con lo
+        // que tengan las demas replicas por si a esta no le llego alguna entrada
+        let merged = replicator
+            .send(SyncDecisionLog {})
+            .await
+            .unwrap_or_default();
+        log_c3.do_send(LogMessage::new(format!(
+            "Pulled {} decisions from the other replicas",
+            merged
+        )));
+        let pending = coordinator_addr
+            .send(RecoverPendingDecisions::new(sender_clone.clone()))
+            .await
+            .unwrap_or_default();
+        log_c3.do_send(LogMessage::new(format!(
+            "Recovered {} pending decisions",
+            pending.len()
+        )));
+        // las que todavia se estan terminando quedan como hechas hasta saber como terminaron
+        let recovered_ids = pending
+            .iter()
+            .map(|decision| {
+                let state = match decision.transaction_state {
+                    TransactionState::Commit => FinalState::Commit,
+                    TransactionState::Abort => FinalState::Abort,
+                    _ => FinalState::Unknown,
+                };
+                (decision.transaction_id, (state, decision.attempt))
+            })
+            .collect::<HashMap<u64, (FinalState, u32)>>();
+        dispatcher_clone.do_send(SaveDoneTransactions::new(recovered_ids));
+        for decision in pending {
+            // las que quedaron en prepared en tres fases las termina el coordinador
+            // preguntandole a los participantes
+            if matches!(decision.transaction_state, TransactionState::Wait) {
+                continue;
+            }
+            // las que el lider anterior no llego a decidir se abortaron al recuperarlas
+            let failure = match decision.transaction_state {
+                TransactionState::Abort => Some(TransactionFailure::new(
+                    AbortReason::Recovered,
+                    HashMap::new(),
+                )),
+                _ => None,
+            };
+            sender_clone.do_send(BroadcastTransactionState::new(
+                decision.transaction_id,
+                decision.attempt,
+                decision.transaction_state,
+                decision.participants,
+                failure,
+            ));
+        }
+
+        // si el lider anterior dejo un checkpoint de este archivo se sigue desde ahi
+        let resumed = file_reader
+            .send(EnableCheckpoints::new(
+                config.files.checkpoint(pid),
+                Duration::from_secs(config.pipeline.checkpoint_interval_s),
+                replicator,
+            ))
+            .await
+            .unwrap_or_default();
+        if let Some(record) = resumed {
+            log_c3.do_send(LogMessage::new(format!(
+                "Resuming the transactions file after row {}",
+                record
+            )));
+        }
 
         // esta logica no se donde debería ir
         let msg = ServeNextTransaction {};
@@ -123,16 +287,18 @@ impl Bootstrapper {
         while let Ok(res) = file_reader.send(msg).await {
             match res {
                 ReadStatus::KeepReading => {
-                    log_c3.do_send(LogMessage::new("KeepReading".to_string()));
+                    log_c3.do_send(LogMessage::debug("KeepReading".to_string()));
+                }
+                ReadStatus::Full => {
+                    log_c3.do_send(LogMessage::debug(
+                        "Too many transactions in flight, waiting".to_string(),
+                    ));
+                    in_flight_limit.wait().await;
                 }
                 ReadStatus::Eof => {
                     log_c3.do_send(LogMessage::new("EOF".to_string()));
                     break;
                 }
-                ReadStatus::ParseError(e) => {
-                    log_c3.do_send(LogMessage::new(format!("ERROR: {}", e)));
-                    continue;
-                }
             }
         }
         actix_rt::signal::ctrl_c()
@@ -161,7 +327,14 @@ impl Handler<RunAlGlobo> for Bootstrapper {
             "[BOOTSTRAPPER] spawning alglobo schedule".to_string(),
         ));
         println!("[BOOTSTRAPPER] spawning alglobo schedule");
-        let path = self.file_path.clone();
-        actix_rt::spawn(Bootstrapper::run(msg.logger_addr, path));
+        let input = self.input.clone();
+        actix_rt::spawn(Bootstrapper::run(
+            msg.logger_addr,
+            input,
+            self.pid,
+            self.replicator.clone(),
+            self.config.clone(),
+            self.commit_protocol,
+        ));
     }
 }
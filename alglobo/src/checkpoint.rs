@@ -0,0 +1,187 @@
+use csv::{Position, ReaderBuilder, Writer};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+
+// cuanto del principio del archivo se usa para reconocerlo
+const FINGERPRINT_BYTES: u64 = 4096;
+
+// Hasta donde esta resuelto el archivo de transacciones: todas las filas antes de esta posicion
+// ya tienen su decision. Se guarda con la ruta y una huella del archivo para no usarlo con otro
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub input: String,
+    pub fingerprint: u64,
+    pub byte: u64,
+    pub line: u64,
+    pub record: u64,
+}
+
+impl Checkpoint {
+    pub fn new(input: String, fingerprint: u64, position: &Position) -> Self {
+        Checkpoint {
+            input,
+            fingerprint,
+            byte: position.byte(),
+            line: position.line(),
+            record: position.record(),
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        let mut position = Position::new();
+        position
+            .set_byte(self.byte)
+            .set_line(self.line)
+            .set_record(self.record);
+        position
+    }
+
+    pub fn load(path: &str) -> Result<Option<Checkpoint>, Box<dyn std::error::Error>> {
+        let mut reader = match ReaderBuilder::new().has_headers(false).from_path(path) {
+            Ok(reader) => reader,
+            Err(e) => match e.kind() {
+                csv::ErrorKind::Io(io) if io.kind() == ErrorKind::NotFound => return Ok(None),
+                _ => return Err(e.into()),
+            },
+        };
+        let record = match reader.records().next() {
+            Some(record) => record?,
+            None => return Ok(None),
+        };
+        let field = |index: usize| record.get(index).ok_or("Malformed checkpoint");
+        Ok(Some(Checkpoint {
+            byte: u64::from_str(field(0)?)?,
+            line: u64::from_str(field(1)?)?,
+            record: u64::from_str(field(2)?)?,
+            fingerprint: u64::from_str(field(3)?)?,
+            input: field(4)?.to_string(),
+        }))
+    }
+
+    // se escribe aparte y se renombra, asi una caida a mitad de camino deja el checkpoint anterior
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_path = format!("{}.tmp", path);
+        let mut writer = Writer::from_path(&tmp_path)?;
+        writer.write_record([
+            self.byte.to_string(),
+            self.line.to_string(),
+            self.record.to_string(),
+            self.fingerprint.to_string(),
+            self.input.clone(),
+        ])?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    // true si el checkpoint se tomo sobre este mismo archivo
+    pub fn matches(&self, input: &str, fingerprint: u64) -> bool {
+        self.input == input && self.fingerprint == fingerprint
+    }
+
+    // true si va mas adelante que el otro, o es de otro archivo y entonces vale el ultimo
+    pub fn supersedes(&self, other: &Checkpoint) -> bool {
+        !other.matches(&self.input, self.fingerprint) || self.record > other.record
+    }
+}
+
+pub fn fingerprint(path: &str) -> std::io::Result<u64> {
+    let mut head = vec![];
+    File::open(path)?
+        .take(FINGERPRINT_BYTES)
+        .read_to_end(&mut head)?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&head);
+    Ok(hasher.finish())
+}
+
+// Lleva las filas leidas que todavia no se resolvieron. La marca avanza hasta el final de la
+// ultima fila resuelta sin ninguna pendiente antes
+#[derive(Debug, Default)]
+pub struct CheckpointTracker {
+    // numero de fila -> (donde termina, si ya se resolvio)
+    rows: BTreeMap<u64, (Position, bool)>,
+    watermark: Option<Position>,
+}
+
+impl CheckpointTracker {
+    pub fn new(start: Option<Position>) -> Self {
+        CheckpointTracker {
+            rows: BTreeMap::new(),
+            watermark: start,
+        }
+    }
+
+    pub fn served(&mut self, row: u64, end: Position) {
+        self.rows.insert(row, (end, false));
+    }
+
+    pub fn resolved(&mut self, row: u64) {
+        if let Some((_, resolved)) = self.rows.get_mut(&row) {
+            *resolved = true;
+        }
+        while let Some(entry) = self.rows.first_entry() {
+            if !entry.get().1 {
+                break;
+            }
+            let (end, _) = entry.remove();
+            self.watermark = Some(end);
+        }
+    }
+
+    pub fn watermark(&self) -> Option<&Position> {
+        self.watermark.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::{Checkpoint, CheckpointTracker};
+    use csv::Position;
+
+    fn position(record: u64) -> Position {
+        let mut position = Position::new();
+        position
+            .set_byte(record * 10)
+            .set_line(record + 1)
+            .set_record(record);
+        position
+    }
+
+    #[test]
+    fn test_watermark_waits_for_the_oldest_row() {
+        let mut tracker = CheckpointTracker::new(None);
+        tracker.served(1, position(2));
+        tracker.served(2, position(3));
+        tracker.served(3, position(4));
+
+        tracker.resolved(2);
+        assert!(tracker.watermark().is_none());
+        tracker.resolved(1);
+        assert_eq!(tracker.watermark().unwrap().record(), 3);
+        tracker.resolved(3);
+        assert_eq!(tracker.watermark().unwrap().byte(), 40);
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let path = "test_checkpoint_roundtrip.csv";
+        let _ = std::fs::remove_file(path);
+        assert_eq!(Checkpoint::load(path).unwrap(), None);
+
+        let checkpoint = Checkpoint::new("transactions, 2.csv".to_string(), 42, &position(7));
+        checkpoint.save(path).unwrap();
+        let loaded = Checkpoint::load(path).unwrap().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(loaded.position().line(), 8);
+        assert!(loaded.matches("transactions, 2.csv", 42));
+        assert!(!loaded.matches("transactions, 2.csv", 43));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::input_source::{InputFormat, InputSpec};
+use crate::logger::LogLevel;
+use crate::transaction_coordinator::CommitProtocol;
+use std::str::FromStr;
+
+pub const USAGE: &str = "Uso:
+  alglobo run <pid> <transaction_file> [--input-format <formato>] [opciones]
+  alglobo replay-failed <pid> [opciones]
+  alglobo status [--config <archivo>]
+  alglobo validate <transaction_file>
+  alglobo help
+
+Comandos:
+  run             levanta la replica <pid> y procesa <transaction_file> cuando es lider.
+                  Puede ser un archivo, un directorio (sus archivos en orden de nombre) o - para stdin
+  replay-failed   levanta la replica <pid> y reintenta las transacciones fallidas
+  status          consulta que replicas del cluster estan vivas y quien es el lider
+  validate        revisa un archivo de transacciones sin ejecutarlo
+
+Opciones:
+  --config <archivo>      configuracion del cluster (TOML); sin ella se usan los puertos de localhost
+  --log-level <nivel>     error, info o debug (por defecto info)
+  --output-dir <dir>      directorio donde se escriben los logs y archivos de salida
+  --input-format <fmt>    csv, jsonl o binary; por defecto sale de la extension (.csv, .jsonl, .bin)
+                          y stdin se lee como csv
+  --commit-protocol <p>   2pc o 3pc (por defecto 2pc). Con 3pc las entidades pueden terminar las
+                          transacciones en duda aunque se caiga el lider que las decidio";
+
+// Lo que comparten los comandos que levantan una replica
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplicaOptions {
+    pub pid: u8,
+    pub config: Option<String>,
+    pub log_level: LogLevel,
+    pub output_dir: Option<String>,
+    pub commit_protocol: CommitProtocol,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Run {
+        options: ReplicaOptions,
+        input: InputSpec,
+    },
+    ReplayFailed {
+        options: ReplicaOptions,
+    },
+    Status {
+        config: Option<String>,
+    },
+    Validate {
+        transaction_file: String,
+    },
+    Help,
+}
+
+type NamedOptions = Vec<(String, String)>;
+
+// separa los parametros posicionales de las opciones "--nombre valor"
+fn split_options(args: &[String]) -> Result<(Vec<String>, NamedOptions), String> {
+    let mut positional = vec![];
+    let mut options = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(name) = arg.strip_prefix("--") {
+            match iter.next() {
+                Some(value) => options.push((name.to_string(), value.to_string())),
+                None => return Err(format!("Falta el valor de la opcion --{}", name)),
+            }
+        } else {
+            positional.push(arg.to_string());
+        }
+    }
+    Ok((positional, options))
+}
+
+fn expect_positional(positional: &[String], names: &[&str], command: &str) -> Result<(), String> {
+    if positional.len() < names.len() {
+        return Err(format!(
+            "Falta el parametro <{}> de {}",
+            names[positional.len()],
+            command
+        ));
+    }
+    if positional.len() > names.len() {
+        return Err(format!(
+            "Parametro de mas para {}: {}",
+            command,
+            positional[names.len()]
+        ));
+    }
+    Ok(())
+}
+
+fn replica_options(pid: &str, options: NamedOptions) -> Result<ReplicaOptions, String> {
+    let pid = u8::from_str(pid).map_err(|_| format!("pid invalido: {}", pid))?;
+    let mut result = ReplicaOptions {
+        pid,
+        config: None,
+        log_level: LogLevel::Info,
+        output_dir: None,
+        commit_protocol: CommitProtocol::TwoPhase,
+    };
+    for (name, value) in options {
+        match name.as_str() {
+            "config" => result.config = Some(value),
+            "log-level" => result.log_level = LogLevel::from_str(&value)?,
+            "output-dir" => result.output_dir = Some(value),
+            "commit-protocol" => result.commit_protocol = CommitProtocol::from_str(&value)?,
+            other => return Err(format!("Opcion desconocida: --{}", other)),
+        }
+    }
+    Ok(result)
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err("Falta el comando".to_string()),
+    };
+    // ./alglobo <pid> <transaction_file> [config], como antes de que hubiera comandos
+    if u8::from_str(command).is_ok() {
+        let mut legacy = vec!["run".to_string()];
+        legacy.extend(args.iter().take(2).cloned());
+        match args.get(2) {
+            Some(config) if !config.starts_with("--") => {
+                legacy.push("--config".to_string());
+                legacy.extend(args.iter().skip(2).cloned());
+            }
+            _ => legacy.extend(args.iter().skip(2).cloned()),
+        }
+        return parse(&legacy);
+    }
+    let (positional, options) = split_options(rest)?;
+    match command {
+        "run" => {
+            expect_positional(&positional, &["pid", "transaction_file"], command)?;
+            let (format, options): (NamedOptions, NamedOptions) = options
+                .into_iter()
+                .partition(|(name, _)| name == "input-format");
+            let format = match format.last() {
+                Some((_, format)) => Some(InputFormat::from_str(format)?),
+                None => None,
+            };
+            Ok(Command::Run {
+                options: replica_options(&positional[0], options)?,
+                input: InputSpec::new(positional[1].clone(), format),
+            })
+        }
+        "replay-failed" => {
+            expect_positional(&positional, &["pid"], command)?;
+            Ok(Command::ReplayFailed {
+                options: replica_options(&positional[0], options)?,
+            })
+        }
+        "status" => {
+            expect_positional(&positional, &[], command)?;
+            let mut config = None;
+            for (name, value) in options {
+                match name.as_str() {
+                    "config" => config = Some(value),
+                    other => return Err(format!("Opcion desconocida: --{}", other)),
+                }
+            }
+            Ok(Command::Status { config })
+        }
+        "validate" => {
+            expect_positional(&positional, &["transaction_file"], command)?;
+            if let Some((name, _)) = options.first() {
+                return Err(format!("Opcion desconocida: --{}", name));
+            }
+            Ok(Command::Validate {
+                transaction_file: positional[0].clone(),
+            })
+        }
+        "help" | "-h" => Ok(Command::Help),
+        other => Err(format!("Comando desconocido: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{parse, Command, ReplicaOptions};
+    use crate::input_source::{InputFormat, InputSpec};
+    use crate::logger::LogLevel;
+    use crate::transaction_coordinator::CommitProtocol;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_run_with_options() {
+        let command = parse(&args(
+            "run 2 transactions.csv --config cluster.toml --log-level debug --output-dir out --commit-protocol 3pc",
+        ))
+        .unwrap();
+        assert_eq!(
+            command,
+            Command::Run {
+                options: ReplicaOptions {
+                    pid: 2,
+                    config: Some("cluster.toml".to_string()),
+                    log_level: LogLevel::Debug,
+                    output_dir: Some("out".to_string()),
+                    commit_protocol: CommitProtocol::ThreePhase,
+                },
+                input: InputSpec::new("transactions.csv".to_string(), None),
+            }
+        );
+
+        match parse(&args("run 1 - --input-format jsonl")).unwrap() {
+            Command::Run { input, .. } => {
+                assert_eq!(input.path, "-");
+                assert_eq!(input.format, Some(InputFormat::JsonLines));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_legacy_invocation_is_run() {
+        match parse(&args("1 transactions.csv")).unwrap() {
+            Command::Run { options, input } => {
+                assert_eq!(options.pid, 1);
+                assert_eq!(options.log_level, LogLevel::Info);
+                assert_eq!(options.commit_protocol, CommitProtocol::TwoPhase);
+                assert_eq!(input.path, "transactions.csv");
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        // la configuracion iba como tercer parametro
+        match parse(&args("1 transactions.csv cluster.toml --log-level debug")).unwrap() {
+            Command::Run { options, .. } => {
+                assert_eq!(options.config, Some("cluster.toml".to_string()));
+                assert_eq!(options.log_level, LogLevel::Debug);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(parse(&args("1 transactions.csv cluster.toml extra")).is_err());
+    }
+
+    #[test]
+    fn test_other_commands() {
+        assert_eq!(
+            parse(&args("status --config c.toml")).unwrap(),
+            Command::Status {
+                config: Some("c.toml".to_string())
+            }
+        );
+        assert_eq!(
+            parse(&args("validate t.csv")).unwrap(),
+            Command::Validate {
+                transaction_file: "t.csv".to_string()
+            }
+        );
+        assert!(matches!(
+            parse(&args("replay-failed 3")).unwrap(),
+            Command::ReplayFailed { options } if options.pid == 3
+        ));
+        assert_eq!(parse(&args("help")).unwrap(), Command::Help);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&args("run 1")).is_err());
+        assert!(parse(&args("run x t.csv")).is_err());
+        assert!(parse(&args("run 1 t.csv extra")).is_err());
+        assert!(parse(&args("run 1 t.csv --log-level loud")).is_err());
+        assert!(parse(&args("run 1 t.csv --config")).is_err());
+        assert!(parse(&args("run 1 t.csv --input-format xml")).is_err());
+        assert!(parse(&args("run 1 t.csv --commit-protocol 1pc")).is_err());
+        assert!(parse(&args("status --pid 1")).is_err());
+        assert!(parse(&args("launch")).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use alglobo_common_utils::cluster_config::ClusterConfig;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+// Consulta de estado por el socket de control: se manda [STATUS, 0] y cada replica contesta
+// [STATUS, pid, 1, lider] si conoce al lider, o [STATUS, pid, 0, 0] si esta en plena eleccion
+pub const STATUS: u8 = b'S';
+pub const STATUS_RESPONSE_SIZE: usize = 4;
+const STATUS_TIMEOUT_MS: u64 = 1000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplicaStatus {
+    pub pid: u8,
+    pub leader: Option<u8>,
+}
+
+pub fn encode_status(pid: u8, leader: Option<u8>) -> [u8; STATUS_RESPONSE_SIZE] {
+    match leader {
+        Some(leader) => [STATUS, pid, 1, leader],
+        None => [STATUS, pid, 0, 0],
+    }
+}
+
+pub fn decode_status(buffer: &[u8]) -> Option<ReplicaStatus> {
+    match buffer {
+        [STATUS, pid, 1, leader] => Some(ReplicaStatus {
+            pid: *pid,
+            leader: Some(*leader),
+        }),
+        [STATUS, pid, 0, _] => Some(ReplicaStatus {
+            pid: *pid,
+            leader: None,
+        }),
+        _ => None,
+    }
+}
+
+// Le pregunta a cada replica de la configuracion; None si no contesto a tiempo
+pub fn query_cluster(config: &ClusterConfig) -> std::io::Result<Vec<(u8, Option<ReplicaStatus>)>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(Duration::from_millis(STATUS_TIMEOUT_MS)))?;
+    let mut res = vec![];
+    for replica in &config.replicas {
+        let mut status = None;
+        if socket
+            .send_to(&[STATUS, 0], &replica.control_address)
+            .is_ok()
+        {
+            let mut buf = [0; STATUS_RESPONSE_SIZE];
+            if let Ok((size, _)) = socket.recv_from(&mut buf) {
+                // una respuesta atrasada de otra replica no cuenta
+                status = decode_status(&buf[..size]).filter(|status| status.pid == replica.id);
+            }
+        }
+        res.push((replica.id, status));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster_status::{decode_status, encode_status, ReplicaStatus};
+
+    #[test]
+    fn test_status_roundtrip() {
+        assert_eq!(
+            decode_status(&encode_status(1, Some(3))),
+            Some(ReplicaStatus {
+                pid: 1,
+                leader: Some(3)
+            })
+        );
+        assert_eq!(
+            decode_status(&encode_status(2, None)),
+            Some(ReplicaStatus {
+                pid: 2,
+                leader: None
+            })
+        );
+        assert_eq!(decode_status(b"PONG"), None);
+    }
+}
@@ -0,0 +1,506 @@
+use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::transaction_state::TransactionState;
+use csv::{ReaderBuilder, Writer};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::str::FromStr;
+
+const PREPARED: &str = "prepared";
+const COMMIT: &str = "commit";
+const ABORT: &str = "abort";
+const PRECOMMIT: &str = "precommit";
+const ENDED: &str = "ended";
+const PARTICIPANT_SEPARATOR: &str = ";";
+
+// Write-ahead log del coordinador: cada transaccion pasa por
+// prepared -> [precommit ->] (commit | abort) -> ended, con el precommit solo en tres fases
+// y cada linea se fsyncea antes de seguir, asi un lider nuevo sabe como terminar lo que quedo en vuelo.
+// Las lineas son "<tipo>,<id>,<participantes>,<intento>". El archivo se lee una sola vez al
+// abrirlo: despues se consulta lo que quedo en memoria, que se actualiza con cada linea
+pub struct DecisionLog {
+    path: String,
+    file: Writer<File>,
+    transactions: HashMap<u64, Entry>,
+    // lineas leidas o escritas, para devolver las pendientes en el orden en que aparecieron
+    lines: u64,
+    // lineas escritas desde la ultima compactacion
+    appended: usize,
+}
+
+// Wait si solo llego a prepared: que hacer con esas lo decide el coordinador
+#[derive(Debug, Clone)]
+pub struct PendingDecision {
+    pub transaction_id: u64,
+    pub attempt: u32,
+    pub transaction_state: TransactionState,
+    pub participants: HashSet<EntityType>,
+}
+
+// cuanto avanzo una transaccion en el log: un intento mas alto, o una etapa mas adelante del
+// mismo intento, le gana a lo anterior
+type Progress = (u32, u8);
+
+// lo que se sabe de una transaccion por su ultima linea
+struct Entry {
+    progress: Progress,
+    // la ultima etapa, None si ya llego a "ended"
+    pending: Option<PendingDecision>,
+    // el ultimo prepare registrado y a quienes se les mando
+    prepared: Option<(u32, HashSet<EntityType>)>,
+    // la linea donde empezo a estar pendiente
+    since: u64,
+}
+
+fn stage(kind: &str) -> Option<u8> {
+    match kind {
+        PREPARED => Some(0),
+        PRECOMMIT => Some(1),
+        COMMIT | ABORT => Some(2),
+        ENDED => Some(3),
+        _ => None,
+    }
+}
+
+fn kind(transaction_state: TransactionState) -> &'static str {
+    match transaction_state {
+        TransactionState::Wait => PREPARED,
+        TransactionState::Commit => COMMIT,
+        TransactionState::PreCommit => PRECOMMIT,
+        _ => ABORT,
+    }
+}
+
+fn participants(record: &csv::StringRecord) -> HashSet<EntityType> {
+    record
+        .get(2)
+        .unwrap_or("")
+        .split(PARTICIPANT_SEPARATOR)
+        .filter_map(|entity| EntityType::from_str(entity).ok())
+        .collect()
+}
+
+fn attempt(record: &csv::StringRecord) -> u32 {
+    record
+        .get(3)
+        .and_then(|attempt| u32::from_str(attempt).ok())
+        .unwrap_or(0)
+}
+
+fn line(
+    kind: &str,
+    transaction_id: u64,
+    attempt: u32,
+    participants: &HashSet<EntityType>,
+) -> [String; 4] {
+    let participants = participants
+        .iter()
+        .map(|entity| entity.to_string())
+        .collect::<Vec<String>>()
+        .join(PARTICIPANT_SEPARATOR);
+    [
+        kind.to_string(),
+        transaction_id.to_string(),
+        participants,
+        attempt.to_string(),
+    ]
+}
+
+impl DecisionLog {
+    pub fn new(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut log = DecisionLog {
+            path: path.to_string(),
+            file: Writer::from_writer(file),
+            transactions: HashMap::new(),
+            lines: 0,
+            appended: 0,
+        };
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            // los logs viejos no tienen la columna del intento
+            .flexible(true)
+            .from_path(path)?;
+        // una linea cortada por un crash a mitad de escritura simplemente se ignora
+        for record in reader.records().flatten() {
+            if let (Some(kind), Some(Ok(id))) = (record.get(0), record.get(1).map(u64::from_str)) {
+                log.apply(kind, id, attempt(&record), participants(&record));
+            }
+        }
+        Ok(log)
+    }
+
+    // vuelve a leer el archivo, por si otro lo cambio (el replicator al traer lo de las demas)
+    pub fn reload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        *self = DecisionLog::new(&self.path)?;
+        Ok(())
+    }
+
+    pub fn prepared(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        participants: &HashSet<EntityType>,
+    ) -> std::io::Result<()> {
+        self.append(PREPARED, transaction_id, attempt, participants)
+    }
+
+    pub fn decided(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        transaction_state: TransactionState,
+        participants: &HashSet<EntityType>,
+    ) -> std::io::Result<()> {
+        let kind = match transaction_state {
+            TransactionState::Wait => ABORT,
+            state => kind(state),
+        };
+        self.append(kind, transaction_id, attempt, participants)
+    }
+
+    // Agrega lo que otra replica tiene sin terminar y aca falta o esta mas atrasado. Lo que
+    // aca ya avanzo mas (o termino) no se toca. Devuelve cuantas se agregaron
+    pub fn merge(&mut self, decisions: Vec<PendingDecision>) -> std::io::Result<usize> {
+        let mut merged = 0;
+        for decision in decisions {
+            if !self.advances(
+                decision.transaction_id,
+                decision.attempt,
+                Some(decision.transaction_state),
+            ) {
+                continue;
+            }
+            self.append(
+                kind(decision.transaction_state),
+                decision.transaction_id,
+                decision.attempt,
+                &decision.participants,
+            )?;
+            merged += 1;
+        }
+        Ok(merged)
+    }
+
+    fn progress(&self, transaction_id: u64) -> Option<Progress> {
+        self.transactions
+            .get(&transaction_id)
+            .map(|entry| entry.progress)
+    }
+
+    // si esa etapa (None para el "ended") lleva a la transaccion mas adelante de lo que hay
+    // aca. Lo que no, es algo que llego tarde y no tiene que pisar lo mas nuevo
+    pub fn advances(
+        &self,
+        transaction_id: u64,
+        attempt: u32,
+        transaction_state: Option<TransactionState>,
+    ) -> bool {
+        let kind = transaction_state.map(kind).unwrap_or(ENDED);
+        let theirs = (attempt, stage(kind).unwrap_or(0));
+        !matches!(self.progress(transaction_id), Some(ours) if ours >= theirs)
+    }
+
+    // el ultimo intento que se vio de la transaccion
+    pub fn attempt(&self, transaction_id: u64) -> Option<u32> {
+        self.progress(transaction_id).map(|(attempt, _)| attempt)
+    }
+
+    // a quienes se les mando el prepare de ese intento, si quedo registrado y no termino
+    pub fn prepared_participants(
+        &self,
+        transaction_id: u64,
+        attempt: u32,
+    ) -> Option<HashSet<EntityType>> {
+        match self.transactions.get(&transaction_id)?.prepared.as_ref()? {
+            (prepared_attempt, participants) if *prepared_attempt == attempt => {
+                Some(participants.clone())
+            }
+            _ => None,
+        }
+    }
+
+    // el "ended" de los logs viejos no trae el intento: termina el ultimo que se vio
+    pub fn ended(&mut self, transaction_id: u64, attempt: u32) -> std::io::Result<()> {
+        self.append(ENDED, transaction_id, attempt, &HashSet::new())
+    }
+
+    // lo que cambia en memoria con una linea del log; vale la ultima linea de cada transaccion
+    fn apply(&mut self, kind: &str, id: u64, attempt: u32, participants: HashSet<EntityType>) {
+        let stage = match stage(kind) {
+            Some(stage) => stage,
+            None => return,
+        };
+        self.lines += 1;
+        let lines = self.lines;
+        let entry = self.transactions.entry(id).or_insert(Entry {
+            progress: (attempt, stage),
+            pending: None,
+            prepared: None,
+            since: lines,
+        });
+        let transaction_state = match kind {
+            PREPARED => TransactionState::Wait,
+            PRECOMMIT => TransactionState::PreCommit,
+            COMMIT => TransactionState::Commit,
+            ABORT => TransactionState::Abort,
+            _ => {
+                let (last_attempt, _) = entry.progress;
+                entry.progress = (attempt.max(last_attempt), stage);
+                entry.pending = None;
+                entry.prepared = None;
+                return;
+            }
+        };
+        if entry.pending.is_none() {
+            entry.since = lines;
+        }
+        if kind == PREPARED {
+            entry.prepared = Some((attempt, participants.clone()));
+        }
+        entry.progress = (attempt, stage);
+        entry.pending = Some(PendingDecision {
+            transaction_id: id,
+            attempt,
+            transaction_state,
+            participants,
+        });
+    }
+
+    fn append(
+        &mut self,
+        kind: &str,
+        transaction_id: u64,
+        attempt: u32,
+        participants: &HashSet<EntityType>,
+    ) -> std::io::Result<()> {
+        self.file
+            .write_record(line(kind, transaction_id, attempt, participants))?;
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        self.appended += 1;
+        self.apply(kind, transaction_id, attempt, participants.clone());
+        Ok(())
+    }
+
+    // Devuelve las transacciones que no llegaron a "ended", con la ultima etapa a la que llegaron
+    pub fn pending(&self) -> Vec<PendingDecision> {
+        let mut pending = self
+            .transactions
+            .values()
+            .filter_map(|entry| entry.pending.as_ref().map(|pending| (entry.since, pending)))
+            .collect::<Vec<(u64, &PendingDecision)>>();
+        pending.sort_by_key(|(since, _)| *since);
+        pending
+            .into_iter()
+            .map(|(_, pending)| pending.clone())
+            .collect()
+    }
+
+    // Reescribe el log sin lo que ya no hace falta: de las terminadas queda solo el "ended" con
+    // su intento, y de las pendientes el prepare y la ultima etapa. Se escribe aparte y se
+    // renombra, como el registro de terminadas
+    pub fn compact(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.appended == 0 {
+            return Ok(());
+        }
+        let mut entries = self.transactions.iter().collect::<Vec<(&u64, &Entry)>>();
+        entries.sort_by_key(|(_, entry)| entry.since);
+        let tmp_path = format!("{}.tmp", self.path);
+        let mut writer = Writer::from_path(&tmp_path)?;
+        for (id, entry) in entries {
+            let pending = match &entry.pending {
+                Some(pending) => pending,
+                None => {
+                    let (attempt, _) = entry.progress;
+                    writer.write_record(line(ENDED, *id, attempt, &HashSet::new()))?;
+                    continue;
+                }
+            };
+            if let Some((attempt, participants)) = &entry.prepared {
+                writer.write_record(line(PREPARED, *id, *attempt, participants))?;
+            }
+            if !matches!(pending.transaction_state, TransactionState::Wait) {
+                writer.write_record(line(
+                    kind(pending.transaction_state),
+                    *id,
+                    pending.attempt,
+                    &pending.participants,
+                ))?;
+            }
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.reload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decision_log::{DecisionLog, PendingDecision};
+    use alglobo_common_utils::entity_type::EntityType;
+    use alglobo_common_utils::transaction_state::TransactionState;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_only_unfinished_transactions_are_pending() {
+        let path = "test_decision_log_pending.csv";
+        let _ = std::fs::remove_file(path);
+        let participants = HashSet::from([EntityType::Hotel, EntityType::Bank]);
+        let mut log = DecisionLog::new(path).unwrap();
+        log.prepared(0, 0, &participants).unwrap();
+        log.decided(0, 0, TransactionState::Commit, &participants)
+            .unwrap();
+        log.ended(0, 0).unwrap();
+        log.prepared(1, 0, &participants).unwrap();
+        log.decided(1, 0, TransactionState::Commit, &participants)
+            .unwrap();
+        log.prepared(2, 2, &participants).unwrap();
+        log.prepared(3, 0, &participants).unwrap();
+        log.decided(3, 0, TransactionState::PreCommit, &participants)
+            .unwrap();
+
+        let pending = log.pending();
+        let prepared = (
+            log.prepared_participants(2, 2),
+            log.prepared_participants(2, 0),
+            log.prepared_participants(4, 0),
+        );
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(prepared, (Some(participants.clone()), None, None));
+        assert_eq!(pending.len(), 3);
+        assert_eq!(pending[0].transaction_id, 1);
+        assert!(matches!(
+            pending[0].transaction_state,
+            TransactionState::Commit
+        ));
+        assert_eq!(pending[0].participants, participants);
+        // solo llego a prepared: todavia no se decidio
+        assert_eq!(pending[1].transaction_id, 2);
+        assert_eq!(pending[1].attempt, 2);
+        assert!(matches!(
+            pending[1].transaction_state,
+            TransactionState::Wait
+        ));
+        assert!(matches!(
+            pending[2].transaction_state,
+            TransactionState::PreCommit
+        ));
+    }
+
+    #[test]
+    fn test_merge_only_adds_what_is_missing_or_behind() {
+        let path = "test_decision_log_merge.csv";
+        let _ = std::fs::remove_file(path);
+        let participants = HashSet::from([EntityType::Bank]);
+        let mut log = DecisionLog::new(path).unwrap();
+        log.prepared(0, 0, &participants).unwrap();
+        log.decided(0, 0, TransactionState::Commit, &participants)
+            .unwrap();
+        log.ended(0, 0).unwrap();
+        log.prepared(1, 0, &participants).unwrap();
+        log.prepared(2, 1, &participants).unwrap();
+
+        let theirs = |transaction_id, attempt, transaction_state| PendingDecision {
+            transaction_id,
+            attempt,
+            transaction_state,
+            participants: participants.clone(),
+        };
+        let merged = log
+            .merge(vec![
+                // aca ya termino
+                theirs(0, 0, TransactionState::Wait),
+                // aca no llego la decision
+                theirs(1, 0, TransactionState::Commit),
+                // un intento viejo
+                theirs(2, 0, TransactionState::Abort),
+                // aca no llego nada
+                theirs(3, 0, TransactionState::Wait),
+            ])
+            .unwrap();
+        let pending = log.pending();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(merged, 2);
+        let states = pending
+            .iter()
+            .map(|decision| {
+                format!(
+                    "{}:{:?}",
+                    decision.transaction_id, decision.transaction_state
+                )
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(states, vec!["1:Commit", "2:Wait", "3:Wait"]);
+    }
+
+    #[test]
+    fn test_compaction_keeps_only_what_is_still_needed() {
+        let path = "test_decision_log_compact.csv";
+        let _ = std::fs::remove_file(path);
+        let participants = HashSet::from([EntityType::Airline]);
+        let mut log = DecisionLog::new(path).unwrap();
+        log.prepared(0, 0, &participants).unwrap();
+        log.decided(0, 0, TransactionState::Abort, &participants)
+            .unwrap();
+        log.ended(0, 0).unwrap();
+        log.prepared(0, 1, &participants).unwrap();
+        log.decided(0, 1, TransactionState::Commit, &participants)
+            .unwrap();
+        log.ended(0, 1).unwrap();
+        log.prepared(1, 0, &participants).unwrap();
+        log.decided(1, 0, TransactionState::PreCommit, &participants)
+            .unwrap();
+        log.decided(1, 0, TransactionState::Commit, &participants)
+            .unwrap();
+        log.prepared(2, 3, &participants).unwrap();
+        log.compact().unwrap();
+        log.compact().unwrap();
+        let lines = std::fs::read_to_string(path).unwrap().lines().count();
+
+        // al volver a abrirlo queda igual, y lo que termino sigue sin poder pisarse
+        let mut log = DecisionLog::new(path).unwrap();
+        let merged = log
+            .merge(vec![PendingDecision {
+                transaction_id: 0,
+                attempt: 1,
+                transaction_state: TransactionState::Wait,
+                participants: participants.clone(),
+            }])
+            .unwrap();
+        let pending = log.pending();
+        let prepared = (
+            log.prepared_participants(1, 0),
+            log.prepared_participants(2, 3),
+        );
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(lines, 4);
+        assert_eq!(merged, 0);
+        let states = pending
+            .iter()
+            .map(|decision| {
+                format!(
+                    "{}:{}:{:?}",
+                    decision.transaction_id, decision.attempt, decision.transaction_state
+                )
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(states, vec!["1:0:Commit", "2:3:Wait"]);
+        assert_eq!(
+            prepared,
+            (Some(participants.clone()), Some(participants.clone()))
+        );
+    }
+
+    #[test]
+    fn test_missing_log_has_nothing_pending() {
+        let path = "test_decision_log_missing.csv";
+        let log = DecisionLog::new(path).unwrap();
+        let pending = log.pending();
+        let _ = std::fs::remove_file(path);
+        assert!(pending.is_empty());
+    }
+}
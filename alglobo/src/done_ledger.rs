@@ -0,0 +1,295 @@
+use alglobo_common_utils::money::{Currency, Money};
+use csv::{ReaderBuilder, Writer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: [&str; 7] = [
+    "id",
+    "state",
+    "timestamp",
+    "pid",
+    "amount",
+    "currency",
+    "attempt",
+];
+const COMMIT: &str = "commit";
+const ABORT: &str = "abort";
+const UNKNOWN: &str = "unknown";
+
+// Como termino una transaccion que ya no se va a volver a intentar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalState {
+    Commit,
+    Abort,
+    // filas del formato viejo, que solo tenian el id
+    Unknown,
+}
+
+impl fmt::Display for FinalState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FinalState::Commit => write!(f, "{}", COMMIT),
+            FinalState::Abort => write!(f, "{}", ABORT),
+            FinalState::Unknown => write!(f, "{}", UNKNOWN),
+        }
+    }
+}
+
+impl FromStr for FinalState {
+    type Err = String;
+
+    fn from_str(state: &str) -> Result<Self, Self::Err> {
+        match state {
+            COMMIT => Ok(FinalState::Commit),
+            ABORT => Ok(FinalState::Abort),
+            UNKNOWN => Ok(FinalState::Unknown),
+            _ => Err(format!("Unknown final state: {}", state)),
+        }
+    }
+}
+
+// Una fila del registro de transacciones terminadas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerEntry {
+    pub transaction_id: u64,
+    pub state: FinalState,
+    // milisegundos desde epoch
+    pub timestamp: u64,
+    // el lider que la termino
+    pub pid: u8,
+    // lo que costaba la transaccion entera. Vacio en las filas viejas y cuando no se tenia
+    // la fila, como en las recuperadas del lider anterior
+    pub total: Option<Money>,
+    // el ultimo intento: una abortada se puede volver a mandar con un intento mayor
+    pub attempt: u32,
+}
+
+impl LedgerEntry {
+    pub fn new(transaction_id: u64, state: FinalState, pid: u8) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        LedgerEntry {
+            transaction_id,
+            state,
+            timestamp,
+            pid,
+            total: None,
+            attempt: 0,
+        }
+    }
+
+    pub fn with_attempt(mut self, attempt: u32) -> Self {
+        self.attempt = attempt;
+        self
+    }
+
+    pub fn with_total(mut self, total: Option<Money>) -> Self {
+        self.total = total;
+        self
+    }
+
+    pub fn record(&self) -> [String; 7] {
+        let (amount, currency) = match &self.total {
+            Some(total) => (total.amount.to_string(), total.currency.to_string()),
+            None => (String::new(), String::new()),
+        };
+        [
+            self.transaction_id.to_string(),
+            self.state.to_string(),
+            self.timestamp.to_string(),
+            self.pid.to_string(),
+            amount,
+            currency,
+            self.attempt.to_string(),
+        ]
+    }
+
+    // None para los headers; las filas viejas con solo el id quedan en estado desconocido.
+    // Las de antes de la moneda no tienen el total, y las de antes del intento son del primero
+    pub fn parse(record: &csv::StringRecord) -> Option<LedgerEntry> {
+        let transaction_id = u64::from_str(record.get(0)?).ok()?;
+        if record.len() == 1 {
+            return Some(LedgerEntry {
+                transaction_id,
+                state: FinalState::Unknown,
+                timestamp: 0,
+                pid: 0,
+                total: None,
+                attempt: 0,
+            });
+        }
+        let total = match (record.get(4), record.get(5)) {
+            (Some(amount), Some(currency)) if !amount.is_empty() => Some(Money::new(
+                u64::from_str(amount).ok()?,
+                Currency::from_str(currency).ok()?,
+            )),
+            _ => None,
+        };
+        let attempt = match record.get(6) {
+            Some(attempt) => u32::from_str(attempt).ok()?,
+            None => 0,
+        };
+        Some(LedgerEntry {
+            transaction_id,
+            state: FinalState::from_str(record.get(1)?).ok()?,
+            timestamp: u64::from_str(record.get(2)?).ok()?,
+            pid: u8::from_str(record.get(3)?).ok()?,
+            total,
+            attempt,
+        })
+    }
+}
+
+// El archivo de transacciones terminadas: una fila por transaccion con como termino, cuando
+// y quien la termino. Es lo unico que se mira para saber si una fila ya se proceso
+pub struct DoneLedger {
+    path: String,
+    file: Writer<File>,
+    // filas escritas desde la ultima compactacion
+    appended: usize,
+}
+
+impl DoneLedger {
+    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let mut file = Writer::from_writer(file);
+        if is_new {
+            file.write_record(HEADER)?;
+            file.flush()?;
+        }
+        let mut ledger = DoneLedger {
+            path: path.to_string(),
+            file,
+            appended: 0,
+        };
+        // los archivos del formato viejo se pasan al nuevo antes de agregarles filas
+        if !is_new && !DoneLedger::has_header(path)? {
+            ledger.rewrite()?;
+        }
+        Ok(ledger)
+    }
+
+    fn has_header(path: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)?;
+        Ok(match reader.records().next() {
+            Some(record) => record?.iter().eq(HEADER),
+            None => false,
+        })
+    }
+
+    // si una transaccion aparece mas de una vez vale la ultima fila
+    pub fn load(path: &str) -> Result<BTreeMap<u64, LedgerEntry>, Box<dyn std::error::Error>> {
+        let mut entries = BTreeMap::new();
+        let mut reader = match ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+        {
+            Ok(reader) => reader,
+            Err(e) => match e.kind() {
+                csv::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok(entries)
+                }
+                _ => return Err(e.into()),
+            },
+        };
+        for record in reader.records() {
+            if let Some(entry) = LedgerEntry::parse(&record?) {
+                entries.insert(entry.transaction_id, entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn record(&mut self, entry: &LedgerEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.file.write_record(entry.record())?;
+        self.file.flush()?;
+        self.appended += 1;
+        Ok(())
+    }
+
+    // reescribe el archivo con una sola fila por transaccion y un solo header. Se escribe
+    // aparte y se renombra, asi una caida a mitad de camino deja el archivo anterior entero
+    pub fn compact(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.appended == 0 {
+            return Ok(());
+        }
+        self.rewrite()
+    }
+
+    fn rewrite(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = DoneLedger::load(&self.path)?;
+        let tmp_path = format!("{}.tmp", self.path);
+        let mut writer = Writer::from_path(&tmp_path)?;
+        writer.write_record(HEADER)?;
+        for entry in entries.values() {
+            writer.write_record(entry.record())?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = Writer::from_writer(OpenOptions::new().append(true).open(&self.path)?);
+        self.appended = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::done_ledger::{DoneLedger, FinalState, LedgerEntry};
+    use alglobo_common_utils::money::{Money, DEFAULT_CURRENCY};
+
+    #[test]
+    fn test_compaction_keeps_the_last_row_of_each_transaction() {
+        let path = "test_done_ledger.csv";
+        // formato viejo: un header por cada vez que se abrio el archivo
+        std::fs::write(path, "id\n1\nid\n2\n").unwrap();
+
+        let mut ledger = DoneLedger::open(path).unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().starts_with(
+            "id,state,timestamp,pid,amount,currency,attempt\n1,unknown,0,0,,,0\n2,unknown,0,0,,,0\n"
+        ));
+        ledger
+            .record(&LedgerEntry::new(2, FinalState::Abort, 1))
+            .unwrap();
+        ledger
+            .record(&LedgerEntry::new(3, FinalState::Commit, 1))
+            .unwrap();
+        ledger
+            .record(
+                &LedgerEntry::new(2, FinalState::Commit, 2)
+                    .with_total(Some(Money::new(60, DEFAULT_CURRENCY))),
+            )
+            .unwrap();
+        ledger.compact().unwrap();
+        ledger.compact().unwrap();
+        ledger
+            .record(&LedgerEntry::new(4, FinalState::Abort, 2).with_attempt(3))
+            .unwrap();
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        let entries = DoneLedger::load(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(contents.matches("id,state").count(), 1);
+        assert!(!contents.contains("\nid\n"));
+        assert_eq!(contents.lines().count(), 5);
+        assert_eq!(entries[&1].state, FinalState::Unknown);
+        assert_eq!(entries[&2].state, FinalState::Commit);
+        assert_eq!(entries[&2].pid, 2);
+        assert_eq!(entries[&2].total, Some(Money::new(60, DEFAULT_CURRENCY)));
+        assert_eq!(entries[&3].total, None);
+        assert_eq!(entries[&4].state, FinalState::Abort);
+        assert_eq!(entries[&4].attempt, 3);
+        assert_eq!(entries[&2].attempt, 0);
+    }
+}
@@ -1,36 +1,91 @@
-use crate::transaction_coordinator::{TransactionCoordinator, TransactionUpdate};
+use crate::entity_sender::{AcknowledgeSequence, AnswerInquiry, EntitySender};
+use crate::transaction_coordinator::{InquireDecision, TransactionCoordinator, TransactionUpdate};
 use crate::LogMessage;
 use actix::{Actor, AsyncContext, Context, Handler, Message};
 use actix::{ActorFutureExt, Addr, ResponseActFuture, WrapFuture};
-use alglobo_common_utils::transaction_response::{
-    TransactionResponse, TRANSACTION_RESPONSE_PAYLOAD_SIZE,
-};
+use alglobo_common_utils::decision_inquiry::DecisionInquiry;
+use alglobo_common_utils::frame::{Frame, MessageType, MAX_FRAME_SIZE};
+use alglobo_common_utils::reliable_delivery::DuplicateFilter;
+use alglobo_common_utils::transaction_response::TransactionResponse;
 
 use crate::logger::LoggerActor;
+use alglobo_common_utils::entity_type::EntityType;
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::Arc;
 use tokio::net::UdpSocket;
 
+// cuantas secuencias recordamos para descartar respuestas repetidas
+const RECEIVED_CAPACITY: usize = 4096;
+
+// lo que nos puede mandar una entidad: la respuesta a algo que le mandamos, o la consulta
+// por una decision que no le llego
+enum Received {
+    Response(u64, TransactionResponse),
+    Inquiry(DecisionInquiry),
+}
+
 pub struct EntityReceiver {
     stream: Arc<UdpSocket>,
     logger: Addr<LoggerActor>,
     transaction_coordinator: Addr<TransactionCoordinator>,
+    entity_sender: Addr<EntitySender>,
+    entity_by_address: HashMap<SocketAddr, EntityType>,
+    received: DuplicateFilter<SocketAddr, ()>,
 }
 
 impl EntityReceiver {
     pub fn new(
         stream: Arc<UdpSocket>,
+        address_map: &HashMap<EntityType, String>,
         logger: Addr<LoggerActor>,
         transaction_coordinator: Addr<TransactionCoordinator>,
+        entity_sender: Addr<EntitySender>,
     ) -> Self {
         logger.do_send(LogMessage::new("Creating EntityReceiver...".to_string()));
+        // resolvemos las direcciones de antemano para saber que entidad nos responde
+        let mut entity_by_address = HashMap::new();
+        for (entity, addr) in address_map {
+            match addr.to_socket_addrs() {
+                Ok(resolved) => {
+                    for socket_addr in resolved {
+                        entity_by_address.insert(socket_addr, *entity);
+                    }
+                }
+                Err(e) => logger.do_send(LogMessage::error(format!(
+                    "ERROR resolviendo {} ({:?}): {}",
+                    addr, entity, e
+                ))),
+            }
+        }
         EntityReceiver {
             stream,
             logger,
             transaction_coordinator,
+            entity_sender,
+            entity_by_address,
+            received: DuplicateFilter::new(RECEIVED_CAPACITY),
         }
     }
 }
 
+impl EntityReceiver {
+    // le pregunta al coordinador y, si ya hay decision, se la manda a la entidad
+    fn answer(&self, entity: EntityType, inquiry: DecisionInquiry, ctx: &mut Context<Self>) {
+        let coordinator = self.transaction_coordinator.clone();
+        let sender = self.entity_sender.clone();
+        let fut = async move {
+            if let Ok(Some(state)) = coordinator
+                .send(InquireDecision::new(entity, inquiry))
+                .await
+            {
+                sender.do_send(AnswerInquiry::new(entity, inquiry, state));
+            }
+        };
+        ctx.spawn(fut.into_actor(self));
+    }
+}
+
 impl Actor for EntityReceiver {
     type Context = Context<Self>;
 }
@@ -39,29 +94,66 @@ impl Actor for EntityReceiver {
 #[rtype(result = "()")]
 pub struct ReceiveEntityResponse {}
 
-// las respuestas siempre tienen el id de la transaccion y el status (8 + 1 bytes)
+// las respuestas siempre tienen el id de la transaccion y el status (8 + 1 bytes), detras del header del frame
 impl Handler<ReceiveEntityResponse> for EntityReceiver {
     type Result = ResponseActFuture<Self, ()>;
 
     fn handle(&mut self, _msg: ReceiveEntityResponse, _: &mut Self::Context) -> Self::Result {
-        let mut buf = [0u8; TRANSACTION_RESPONSE_PAYLOAD_SIZE];
+        let mut buf = [0u8; MAX_FRAME_SIZE];
         let read_stream = self.stream.clone();
 
         let fut = async move {
-            if let Ok((_, _)) = read_stream.recv_from(&mut buf).await {
-                Ok(buf.to_vec())
+            if let Ok((size, addr)) = read_stream.recv_from(&mut buf).await {
+                Ok((buf[..size].to_vec(), addr))
             } else {
                 Err(())
             }
         };
 
         Box::pin(fut.into_actor(self).map(|r, me, ctx| {
-            if let Ok(vec) = r {
+            if let Ok((vec, addr)) = r {
                 me.logger
-                    .do_send(LogMessage::new(format!("Recibi: {:?}", vec.as_slice())));
-                let res: TransactionResponse = vec.into();
-                me.transaction_coordinator
-                    .do_send(TransactionUpdate::new(res));
+                    .do_send(LogMessage::debug(format!("Recibi: {:?}", vec.as_slice())));
+                let decoded = Frame::try_from(vec.as_slice()).and_then(|frame| {
+                    let sequence = frame.sequence;
+                    match frame.message_type {
+                        MessageType::Inquiry => {
+                            DecisionInquiry::try_from(frame).map(Received::Inquiry)
+                        }
+                        _ => TransactionResponse::try_from(frame)
+                            .map(|res| Received::Response(sequence, res)),
+                    }
+                });
+                match (me.entity_by_address.get(&addr), decoded) {
+                    (Some(entity), Ok(Received::Inquiry(inquiry))) => {
+                        me.answer(*entity, inquiry, ctx);
+                    }
+                    (Some(entity), Ok(Received::Response(sequence, res))) => {
+                        if sequence != 0 {
+                            // la respuesta confirma el mensaje aunque sea un duplicado
+                            me.entity_sender.do_send(AcknowledgeSequence::new(sequence));
+                            if me.received.get(&addr, sequence).is_some() {
+                                me.logger.do_send(LogMessage::new(format!(
+                                    "Respuesta duplicada de {:?} (secuencia {})",
+                                    entity, sequence
+                                )));
+                                ctx.address().do_send(ReceiveEntityResponse {});
+                                return;
+                            }
+                            me.received.insert(addr, sequence, ());
+                        }
+                        me.transaction_coordinator
+                            .do_send(TransactionUpdate::new(*entity, res));
+                    }
+                    (Some(entity), Err(e)) => me.logger.do_send(LogMessage::error(format!(
+                        "ERROR decodificando respuesta de {:?}: {}",
+                        entity, e
+                    ))),
+                    (None, _) => me.logger.do_send(LogMessage::new(format!(
+                        "Respuesta de direccion desconocida: {}",
+                        addr
+                    ))),
+                }
             }
             ctx.address().do_send(ReceiveEntityResponse {});
         }))
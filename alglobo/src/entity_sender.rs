@@ -1,20 +1,32 @@
+use crate::abort_reason::TransactionFailure;
 use crate::statistics_handler::{RegisterTransaction, StatisticsHandler, UnregisterTransaction};
-use crate::transaction_coordinator::{TransactionCoordinator, WaitTransactionStateResponse};
+use crate::transaction_coordinator::{
+    DeliveryFailed, PreCommitted, TransactionCoordinator, TransactionEnded,
+    WaitTransactionStateResponse,
+};
 use crate::{FileReader, LogMessage};
 use actix::{Actor, ActorFutureExt, Context, Handler, Message, ResponseActFuture, WrapFuture};
 use actix::{Addr, AsyncContext};
+use alglobo_common_utils::decision_inquiry::DecisionInquiry;
+use alglobo_common_utils::entity_payload::EntityPayload;
 use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::frame::Frame;
+use alglobo_common_utils::money::{Money, DEFAULT_CURRENCY};
+use alglobo_common_utils::reliable_delivery::RetransmissionQueue;
 use alglobo_common_utils::transaction_request::TransactionRequest;
 use alglobo_common_utils::transaction_state::TransactionState;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::file_reader::FindTransaction;
-use crate::file_writer::{FileWriter, RegisterDoneTransactionId};
+use crate::file_reader::TransactionResolved;
 use crate::logger::LoggerActor;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 
+const RETRANSMIT_TICK_MS: u64 = 100;
+// pasado el timeout del coordinador no tiene sentido seguir reenviando un prepare
+const PREPARE_MAX_ATTEMPTS: u32 = 5;
+
 pub struct EntitySender {
     stream: Arc<UdpSocket>,
     address_map: HashMap<EntityType, String>,
@@ -23,7 +35,11 @@ pub struct EntitySender {
     statistics_handler: Addr<StatisticsHandler>,
     transaction_timestamps: HashMap<u64, Instant>,
     file_reader: Option<Addr<FileReader>>,
-    file_writer: Option<Addr<FileWriter>>,
+    retransmission_queue: RetransmissionQueue,
+    // transacciones cuya decision todavia no confirmaron todos los participantes
+    awaiting_decision_ack: HashSet<u64>,
+    // en tres fases, el intento y los participantes de lo precommiteado, para el commit
+    awaiting_precommit_ack: HashMap<u64, (u32, HashSet<EntityType>)>,
 }
 
 impl EntitySender {
@@ -43,13 +59,119 @@ impl EntitySender {
             statistics_handler,
             transaction_timestamps: HashMap::new(),
             file_reader: None,
-            file_writer: None,
+            retransmission_queue: RetransmissionQueue::new(),
+            awaiting_decision_ack: HashSet::new(),
+            awaiting_precommit_ack: HashMap::new(),
+        }
+    }
+
+    // cuando no queda nada del precommit por confirmar el coordinador ya puede commitear
+    fn precommit_settled(&mut self, transaction_id: u64, ctx: &mut Context<Self>) {
+        if self.retransmission_queue.pending_for(transaction_id) > 0 {
+            return;
+        }
+        if let Some((attempt, participants)) = self.awaiting_precommit_ack.remove(&transaction_id) {
+            self.coordinator_addr.do_send(PreCommitted::new(
+                transaction_id,
+                attempt,
+                participants,
+                ctx.address(),
+            ));
+        }
+    }
+
+    // numera el mensaje y lo deja registrado para reenviarlo hasta que llegue la respuesta
+    fn track(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        address: String,
+        frame: Frame,
+        max_attempts: Option<u32>,
+    ) -> (String, Vec<u8>) {
+        let sequence = self.retransmission_queue.next_sequence();
+        let buffer: Vec<u8> = frame.sequenced(sequence).into();
+        self.retransmission_queue.track(
+            sequence,
+            (transaction_id, attempt),
+            address.clone(),
+            buffer.clone(),
+            max_attempts,
+            Instant::now(),
+        );
+        (address, buffer)
+    }
+}
+
+// la decision viaja como un payload sin costo ni datos de la reserva
+fn decision_payload(
+    transaction_id: u64,
+    attempt: u32,
+    transaction_state: TransactionState,
+) -> EntityPayload {
+    EntityPayload {
+        transaction_state,
+        transaction_id,
+        cost: Money::new(0, DEFAULT_CURRENCY),
+        attempt,
+        details: String::new(),
+    }
+}
+
+async fn send_all(
+    write_stream: Arc<UdpSocket>,
+    messages: Vec<(String, Vec<u8>)>,
+    logger: Addr<LoggerActor>,
+) {
+    for (addr, buffer) in messages {
+        // si falla no pasa nada, lo reenvia la cola de retransmision
+        if let Err(e) = write_stream.send_to(buffer.as_slice(), &addr).await {
+            logger.do_send(LogMessage::error(format!(
+                "[EntitySender] ERROR sending to {}: {}",
+                addr, e
+            )));
         }
     }
 }
 
 impl Actor for EntitySender {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_millis(RETRANSMIT_TICK_MS), |me, ctx| {
+            let due = me.retransmission_queue.due(Instant::now());
+            if !due.is_empty() {
+                me.logger.do_send(LogMessage::debug(format!(
+                    "[EntitySender] retransmitting {} messages",
+                    due.len()
+                )));
+                ctx.spawn(send_all(me.stream.clone(), due, me.logger.clone()).into_actor(me));
+            }
+            // solo los prepare y los precommit tienen limite de reenvios
+            for message in me.retransmission_queue.take_exhausted() {
+                if me
+                    .awaiting_precommit_ack
+                    .contains_key(&message.transaction_id)
+                {
+                    me.logger.do_send(LogMessage::error(format!(
+                        "[EntitySender] {} did not acknowledge the pre-commit of transaction {}",
+                        message.address, message.transaction_id
+                    )));
+                    me.precommit_settled(message.transaction_id, ctx);
+                    continue;
+                }
+                let entity = me
+                    .address_map
+                    .iter()
+                    .find(|(_, address)| **address == message.address)
+                    .map(|(entity, _)| *entity);
+                if let Some(entity) = entity {
+                    me.coordinator_addr
+                        .do_send(DeliveryFailed::new(message.transaction_id, entity));
+                }
+            }
+        });
+    }
 }
 
 #[derive(Message)]
@@ -68,32 +190,41 @@ impl Handler<PrepareTransaction> for EntitySender {
     type Result = ResponseActFuture<Self, ()>;
 
     fn handle(&mut self, msg: PrepareTransaction, ctx: &mut Self::Context) -> Self::Result {
+        let v = msg.transaction.get_entities_data();
+        let participants = v.iter().map(|(entity, _)| *entity).collect::<HashSet<_>>();
         // registramos primero que vamos a esperar a esta transaccion
-        let _r = self
-            .coordinator_addr
+        self.coordinator_addr
             .do_send(WaitTransactionStateResponse::new(
                 msg.transaction.get_transaction_id(),
+                msg.transaction.get_attempt(),
                 TransactionState::Wait,
                 TransactionState::Commit,
                 ctx.address(),
-                true,
+                participants,
             ));
-        self.logger.do_send(LogMessage::new(
+        self.logger.do_send(LogMessage::debug(
             "[EntitySender] broadcast_new_transaction".to_string(),
         ));
 
+        let transaction_id = msg.transaction.get_transaction_id();
+        let attempt = msg.transaction.get_attempt();
+        let messages = v
+            .into_iter()
+            .map(|(entity, data)| {
+                let addr = self.address_map[&entity].clone();
+                self.track(
+                    transaction_id,
+                    attempt,
+                    addr,
+                    Frame::from(data),
+                    Some(PREPARE_MAX_ATTEMPTS),
+                )
+            })
+            .collect::<Vec<(String, Vec<u8>)>>();
         let write_stream = self.stream.clone();
-        let addresses = self.address_map.clone();
-        let v = msg.transaction.get_entities_data();
+        let logger = self.logger.clone();
         let fut = async move {
-            for (entity, data) in v {
-                let addr = &addresses[&entity];
-                let data_buffer: Vec<u8> = data.into();
-                write_stream
-                    .send_to(data_buffer.as_slice(), addr)
-                    .await
-                    .expect(&*format!("{} failed", addr));
-            }
+            send_all(write_stream, messages, logger).await;
             msg
         };
 
@@ -111,16 +242,29 @@ impl Handler<PrepareTransaction> for EntitySender {
 #[rtype(result = "()")]
 pub struct BroadcastTransactionState {
     transaction_id: u64,
+    attempt: u32,
     transaction_state: TransactionState,
+    participants: HashSet<EntityType>,
+    // por que se aborto, para dejarlo en el archivo de fallidas
+    failure: Option<TransactionFailure>,
 }
 
 // este broadcast sirve para Abort o Commited (si se dispara este handler, significa que recibimos
-// o un commit o un abort para esa transaccion)
+// o un commit o un abort para esa transaccion), y en tres fases tambien para el PreCommit
 impl BroadcastTransactionState {
-    pub fn new(transaction_id: u64, transaction_state: TransactionState) -> Self {
+    pub fn new(
+        transaction_id: u64,
+        attempt: u32,
+        transaction_state: TransactionState,
+        participants: HashSet<EntityType>,
+        failure: Option<TransactionFailure>,
+    ) -> Self {
         BroadcastTransactionState {
             transaction_id,
+            attempt,
             transaction_state,
+            participants,
+            failure,
         }
     }
 }
@@ -128,45 +272,88 @@ impl BroadcastTransactionState {
 impl Handler<BroadcastTransactionState> for EntitySender {
     type Result = ResponseActFuture<Self, ()>;
 
-    fn handle(&mut self, msg: BroadcastTransactionState, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: BroadcastTransactionState, ctx: &mut Self::Context) -> Self::Result {
+        let precommit = matches!(msg.transaction_state, TransactionState::PreCommit);
         // si nos llamaron aca, la transaccion ya resolvió su estado (o fue abortada o commiteada)
         // esto es asi porque asumimos que no se puede fallar en la fase de commit (tal cual lo hace el algoritmo)
-        let instant = self
-            .transaction_timestamps
-            .get_mut(&msg.transaction_id)
-            .unwrap();
-        let duration = instant.elapsed();
-        self.statistics_handler
-            .do_send(UnregisterTransaction::new(msg.transaction_id, duration));
-
-        let temp_bytes = msg.transaction_id.to_be_bytes();
-        let send_buffer = temp_bytes.as_slice();
-        let state_buffer: u8 = msg.transaction_state.into();
-        let mut to_send = vec![state_buffer];
-        to_send.extend_from_slice(send_buffer);
+        // las decisiones recuperadas del log de un lider anterior no tienen timestamp, y lo
+        // precommiteado termina con el commit
+        let finished = match precommit {
+            true => None,
+            false => self.transaction_timestamps.remove(&msg.transaction_id),
+        };
+        if let Some(instant) = finished {
+            let duration = instant.elapsed();
+            self.statistics_handler
+                .do_send(UnregisterTransaction::new(msg.transaction_id, duration));
+        }
+
+        // los prepare de este intento que quedaban sin respuesta ya no importan; el abort de un
+        // intento anterior se sigue reenviando hasta que lo confirmen
+        self.retransmission_queue
+            .forget_attempt(msg.transaction_id, msg.attempt);
+        // solo le avisamos a las entidades que participaron de la transaccion
+        let addresses = self
+            .address_map
+            .iter()
+            .filter(|(entity, _)| msg.participants.contains(entity))
+            .map(|(_, addr)| addr.clone())
+            .collect::<Vec<String>>();
+        // la decision se reenvia hasta que todos la confirmen; el precommit, como el prepare,
+        // tiene un limite
+        let max_attempts = match precommit {
+            true => Some(PREPARE_MAX_ATTEMPTS),
+            false => None,
+        };
+        let messages = addresses
+            .into_iter()
+            .map(|addr| {
+                let decision =
+                    decision_payload(msg.transaction_id, msg.attempt, msg.transaction_state);
+                self.track(
+                    msg.transaction_id,
+                    msg.attempt,
+                    addr,
+                    Frame::from(decision),
+                    max_attempts,
+                )
+            })
+            .collect::<Vec<(String, Vec<u8>)>>();
+        if precommit {
+            self.awaiting_precommit_ack
+                .insert(msg.transaction_id, (msg.attempt, msg.participants.clone()));
+            self.precommit_settled(msg.transaction_id, ctx);
+        } else if messages.is_empty() {
+            self.coordinator_addr
+                .do_send(TransactionEnded::new(msg.transaction_id));
+        } else {
+            self.awaiting_decision_ack.insert(msg.transaction_id);
+        }
         let write_stream = self.stream.clone();
-        let addresses = self.address_map.clone();
+        let logger = self.logger.clone();
         let fut = async move {
-            for (_, addr) in addresses {
-                write_stream
-                    .send_to(to_send.as_slice(), addr)
-                    .await
-                    .unwrap();
-            }
+            send_all(write_stream, messages, logger).await;
             msg
         };
-        Box::pin(fut.into_actor(self).map(|msg, me, _| {
-            me.logger.do_send(LogMessage::new(format!(
+        Box::pin(fut.into_actor(self).map(move |msg, me, _| {
+            me.logger.do_send(LogMessage::debug(format!(
                 "[EntitySender] broadcast_state transaction id: {}",
                 msg.transaction_id
             )));
-            if let Some(writer) = &me.file_writer {
-                writer.do_send(RegisterDoneTransactionId::new(msg.transaction_id));
+            // al reader le avisamos todas, asi libera el lugar que ocupaba la transaccion
+            if precommit {
+                return;
             }
-            if let TransactionState::Abort = msg.transaction_state {
-                if let Some(reader) = &me.file_reader {
-                    reader.do_send(FindTransaction::new(msg.transaction_id));
-                }
+            if let Some(reader) = &me.file_reader {
+                let failure = match msg.transaction_state {
+                    TransactionState::Abort => msg.failure,
+                    _ => None,
+                };
+                reader.do_send(TransactionResolved::new(
+                    msg.transaction_id,
+                    msg.attempt,
+                    failure,
+                ));
             }
         }))
     }
@@ -174,25 +361,147 @@ impl Handler<BroadcastTransactionState> for EntitySender {
 
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct RegisterFileHandles {
+pub struct RegisterFileReader {
     file_reader_addr: Addr<FileReader>,
-    file_writer_addr: Addr<FileWriter>,
 }
 
-impl RegisterFileHandles {
-    pub fn new(file_reader_addr: Addr<FileReader>, file_writer_addr: Addr<FileWriter>) -> Self {
-        Self {
-            file_reader_addr,
-            file_writer_addr,
-        }
+impl RegisterFileReader {
+    pub fn new(file_reader_addr: Addr<FileReader>) -> Self {
+        Self { file_reader_addr }
     }
 }
 
-impl Handler<RegisterFileHandles> for EntitySender {
+impl Handler<RegisterFileReader> for EntitySender {
     type Result = ();
 
-    fn handle(&mut self, msg: RegisterFileHandles, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: RegisterFileReader, _: &mut Self::Context) -> Self::Result {
         self.file_reader = Some(msg.file_reader_addr);
-        self.file_writer = Some(msg.file_writer_addr);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AcknowledgeSequence {
+    sequence: u64,
+}
+
+impl AcknowledgeSequence {
+    pub fn new(sequence: u64) -> Self {
+        AcknowledgeSequence { sequence }
+    }
+}
+
+// la respuesta de una entidad confirma el mensaje con la misma secuencia
+impl Handler<AcknowledgeSequence> for EntitySender {
+    type Result = ();
+
+    fn handle(&mut self, msg: AcknowledgeSequence, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(message) = self.retransmission_queue.acknowledge(msg.sequence) {
+            let transaction_id = message.transaction_id;
+            self.precommit_settled(transaction_id, ctx);
+            // cuando todos confirmaron la decision la transaccion termino
+            if self.retransmission_queue.pending_for(transaction_id) == 0
+                && self.awaiting_decision_ack.remove(&transaction_id)
+            {
+                self.coordinator_addr
+                    .do_send(TransactionEnded::new(transaction_id));
+            }
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AnswerInquiry {
+    entity: EntityType,
+    inquiry: DecisionInquiry,
+    transaction_state: TransactionState,
+}
+
+impl AnswerInquiry {
+    pub fn new(
+        entity: EntityType,
+        inquiry: DecisionInquiry,
+        transaction_state: TransactionState,
+    ) -> Self {
+        AnswerInquiry {
+            entity,
+            inquiry,
+            transaction_state,
+        }
+    }
+}
+
+// la respuesta a una consulta no se reenvia: si se pierde la entidad vuelve a preguntar
+impl Handler<AnswerInquiry> for EntitySender {
+    type Result = ();
+
+    fn handle(&mut self, msg: AnswerInquiry, ctx: &mut Self::Context) -> Self::Result {
+        let addr = match self.address_map.get(&msg.entity) {
+            Some(addr) => addr.clone(),
+            None => return,
+        };
+        let decision = decision_payload(
+            msg.inquiry.transaction_id,
+            msg.inquiry.attempt,
+            msg.transaction_state,
+        );
+        let buffer: Vec<u8> = Frame::from(decision).into();
+        ctx.spawn(
+            send_all(
+                self.stream.clone(),
+                vec![(addr, buffer)],
+                self.logger.clone(),
+            )
+            .into_actor(self),
+        );
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueryTransactionState {
+    transaction_id: u64,
+    attempt: u32,
+    participants: HashSet<EntityType>,
+}
+
+impl QueryTransactionState {
+    pub fn new(transaction_id: u64, attempt: u32, participants: HashSet<EntityType>) -> Self {
+        QueryTransactionState {
+            transaction_id,
+            attempt,
+            participants,
+        }
+    }
+}
+
+// en tres fases le pregunta a cada participante como quedo una transaccion que el lider
+// anterior dejo sin decidir. Se reenvia hasta que conteste: sin saber si alguno recibio el
+// precommit no se puede decidir
+impl Handler<QueryTransactionState> for EntitySender {
+    type Result = ();
+
+    fn handle(&mut self, msg: QueryTransactionState, ctx: &mut Self::Context) -> Self::Result {
+        let addresses = self
+            .address_map
+            .iter()
+            .filter(|(entity, _)| msg.participants.contains(entity))
+            .map(|(_, addr)| addr.clone())
+            .collect::<Vec<String>>();
+        let messages = addresses
+            .into_iter()
+            .map(|addr| {
+                let inquiry = DecisionInquiry::new(msg.transaction_id, msg.attempt);
+                self.track(
+                    msg.transaction_id,
+                    msg.attempt,
+                    addr,
+                    Frame::from(inquiry),
+                    None,
+                )
+            })
+            .collect::<Vec<(String, Vec<u8>)>>();
+        ctx.spawn(send_all(self.stream.clone(), messages, self.logger.clone()).into_actor(self));
     }
 }
@@ -1,45 +1,230 @@
-use crate::file_writer::{FailedTransaction, FileWriter};
+use crate::abort_reason::TransactionFailure;
+use crate::checkpoint::{Checkpoint, CheckpointTracker};
+use crate::done_ledger::{DoneLedger, FinalState, LedgerEntry};
+use crate::file_writer::{
+    DeadLetterTransaction, FailedTransaction, FileWriter, RegisterDoneTransaction,
+    RejectedTransaction,
+};
+use crate::in_flight_limit::InFlightLimit;
+use crate::input_source::{self, InputFormat, InputSource, InputSpec, ATTEMPT_FIELD, FIELDS};
+use crate::replicator::{Replicate, ReplicationEntry, Replicator};
+use crate::retry_policy::RetryPolicy;
 use crate::transaction_dispatcher::{
-    ReceiveTransaction, SaveDoneTransactions, TransactionDispatcher,
+    parse_transaction, Dispatch, ReceiveTransaction, SaveDoneTransactions, TransactionDispatcher,
 };
-use std::collections::HashSet;
+use crate::transaction_validator::TransactionValidator;
 
 use crate::LogMessage;
-use actix::{Actor, Addr, Context, Handler, Message};
-use std::collections::HashMap;
-use std::fs::File;
+use actix::{
+    Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, ResponseActFuture,
+    WrapFuture,
+};
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
+use std::time::Duration;
 
 use crate::logger::LoggerActor;
 use actix::dev::MessageResponse;
-use csv::{Reader, StringRecord};
+use csv::{StringRecord, Writer};
 
-pub const DONE_TRANSACTIONS_PATH: &str = "done_transactions.csv";
+// donde se guarda el checkpoint y a quien se replica
+struct CheckpointStore {
+    path: String,
+    fingerprint: u64,
+    replicator: Addr<Replicator>,
+    saved_record: Option<u64>,
+}
 
 pub struct FileReader {
-    transaction_file_handle: Reader<File>,
+    transaction_file_path: String,
+    transaction_source: Box<dyn InputSource>,
+    // las filas que no pasan se rechazan antes de llegar al dispatcher
+    validator: TransactionValidator,
     transaction_dispatcher: Addr<TransactionDispatcher>,
+    // las filas en vuelo: se sacan cuando se decide la transaccion
     record_map: HashMap<u64, StringRecord>,
+    in_flight_limit: InFlightLimit,
+    // fila del archivo de cada id que todavia no termino (incluye las que esperan reintento)
+    row_of: HashMap<u64, u64>,
+    checkpoints: CheckpointTracker,
+    checkpoint_store: Option<CheckpointStore>,
     failed_transaction_logger: Addr<FileWriter>,
+    done_transactions_path: String,
+    // sin politica las abortadas solo quedan en el archivo de fallidas
+    retry_policy: Option<RetryPolicy>,
     logger: Addr<LoggerActor>,
 }
 
 impl FileReader {
     pub fn new(
-        transaction_file_path: String,
+        input: InputSpec,
         transaction_dispatcher: Addr<TransactionDispatcher>,
         failed_transaction_logger: Addr<FileWriter>,
+        done_transactions_path: String,
+        retry_policy: Option<RetryPolicy>,
+        in_flight_limit: InFlightLimit,
         logger: Addr<LoggerActor>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         logger.do_send(LogMessage::new("Creating FileReader...".to_string()));
         Ok(FileReader {
-            transaction_file_handle: Reader::from_path(transaction_file_path)?,
+            transaction_source: input_source::open(&input)?,
+            validator: TransactionValidator::allowing_duplicates(),
+            transaction_file_path: input.path,
             transaction_dispatcher,
             record_map: HashMap::new(),
+            in_flight_limit,
+            row_of: HashMap::new(),
+            checkpoints: CheckpointTracker::new(None),
+            checkpoint_store: None,
             failed_transaction_logger,
+            done_transactions_path,
+            retry_policy,
             logger,
         })
     }
+
+    // la transaccion ya no va a volver a intentarse: libera su fila para el checkpoint,
+    // queda en el registro de terminadas y como hecha por si el id se repite mas adelante
+    fn finish(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        state: FinalState,
+        record: Option<&StringRecord>,
+    ) {
+        if let Some(row) = self.row_of.remove(&transaction_id) {
+            self.checkpoints.resolved(row);
+        }
+        let total = record
+            .and_then(|record| parse_transaction(record).ok())
+            .and_then(|transaction| transaction.get_total());
+        self.failed_transaction_logger
+            .do_send(RegisterDoneTransaction::new(
+                transaction_id,
+                attempt,
+                state,
+                total,
+            ));
+        self.transaction_dispatcher
+            .do_send(SaveDoneTransactions::new(HashMap::from([(
+                transaction_id,
+                (state, attempt),
+            )])));
+    }
+
+    fn save_checkpoint(&mut self) {
+        let store = match &mut self.checkpoint_store {
+            Some(store) => store,
+            None => return,
+        };
+        let position = match self.checkpoints.watermark() {
+            Some(position) if store.saved_record != Some(position.record()) => position,
+            _ => return,
+        };
+        let checkpoint = Checkpoint::new(
+            self.transaction_file_path.clone(),
+            store.fingerprint,
+            position,
+        );
+        if let Err(e) = checkpoint.save(&store.path) {
+            self.logger.do_send(LogMessage::error(format!(
+                "FileReader: ERROR saving checkpoint: {}",
+                e
+            )));
+            return;
+        }
+        store.saved_record = Some(checkpoint.record);
+        store
+            .replicator
+            .do_send(Replicate::new(ReplicationEntry::Checkpoint(checkpoint)));
+    }
+
+    // la fila no paso la validacion: queda en el archivo de rechazadas y se sigue con la proxima
+    fn reject(&mut self, line: u64, row: u64, record: StringRecord, error: String) {
+        self.logger.do_send(LogMessage::error(format!(
+            "FileReader: rejected line {} of {}: {}",
+            line, self.transaction_file_path, error
+        )));
+        self.failed_transaction_logger
+            .do_send(RejectedTransaction::new(line, record, error));
+        self.checkpoints.resolved(row);
+    }
+
+    // vuelve a mandar la fila al dispatcher, y la guarda por si tambien falla este intento.
+    // Los reintentos no esperan lugar, pueden pasarse un poco del limite de en vuelo
+    fn retry(&mut self, record: StringRecord, attempt: u32) {
+        let record = with_attempt(&record, attempt);
+        // la fila ya se valido cuando se leyo
+        let transaction = match parse_transaction(&record) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                self.logger.do_send(LogMessage::error(format!(
+                    "FileReader: ERROR retrying {:?}: {}",
+                    record, e
+                )));
+                return;
+            }
+        };
+        self.logger.do_send(LogMessage::new(format!(
+            "FileReader: retrying transaction {} (attempt {})",
+            transaction.get_transaction_id(),
+            attempt
+        )));
+        self.record_map
+            .insert(transaction.get_transaction_id(), record);
+        self.transaction_dispatcher
+            .do_send(ReceiveTransaction::retry(transaction));
+    }
+}
+
+// el intento va en la ultima columna; las filas del archivo original no la tienen
+pub fn record_attempt(record: &StringRecord) -> Result<u32, std::num::ParseIntError> {
+    match record.get(ATTEMPT_FIELD) {
+        Some(attempt) => u32::from_str(attempt),
+        None => Ok(0),
+    }
+}
+
+// la misma fila con el intento dado
+fn with_attempt(record: &StringRecord, attempt: u32) -> StringRecord {
+    let mut result = record.iter().take(ATTEMPT_FIELD).collect::<StringRecord>();
+    result.push_field(&attempt.to_string());
+    result
+}
+
+// Copia las transacciones fallidas a un archivo nuevo con el intento siguiente,
+// asi los participantes no reusan el voto del intento que fallo. De cada id queda
+// solo el ultimo intento. Devuelve cuantas copio. Las columnas se buscan por nombre,
+// asi tambien sirve un archivo de fallidas de antes de que hubiera moneda
+pub fn prepare_replay_file(
+    failed_path: &str,
+    replay_path: &str,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut source = input_source::open(&InputSpec::new(
+        failed_path.to_string(),
+        Some(InputFormat::Csv),
+    ))?;
+    let mut latest: HashMap<String, usize> = HashMap::new();
+    let mut rows: Vec<(StringRecord, u32)> = vec![];
+    while let Some(record) = source.next_record()? {
+        let attempt = record_attempt(&record)?;
+        let id = record.get(0).unwrap_or_default().to_string();
+        match latest.get(&id) {
+            Some(&index) if rows[index].1 < attempt => rows[index] = (record, attempt),
+            Some(_) => {}
+            None => {
+                latest.insert(id, rows.len());
+                rows.push((record, attempt));
+            }
+        }
+    }
+    let mut writer = Writer::from_path(replay_path)?;
+    writer.write_record(FIELDS)?;
+    for (record, attempt) in &rows {
+        writer.write_record(&with_attempt(record, attempt + 1))?;
+    }
+    writer.flush()?;
+    Ok(rows.len() as u64)
 }
 
 impl Actor for FileReader {
@@ -49,8 +234,9 @@ impl Actor for FileReader {
 #[derive(MessageResponse)]
 pub enum ReadStatus {
     KeepReading,
+    // hay demasiadas en vuelo, hay que esperar a que se decida alguna
+    Full,
     Eof,
-    ParseError(csv::Error),
 }
 
 #[derive(Message, Clone, Copy)]
@@ -58,80 +244,365 @@ pub enum ReadStatus {
 pub struct ServeNextTransaction {}
 
 impl Handler<ServeNextTransaction> for FileReader {
-    type Result = ReadStatus;
+    type Result = ResponseActFuture<Self, ReadStatus>;
 
     fn handle(&mut self, _msg: ServeNextTransaction, _ctx: &mut Self::Context) -> Self::Result {
-        let mut record = StringRecord::new();
-        match self.transaction_file_handle.read_record(&mut record) {
-            Ok(any_left) => {
-                if any_left {
-                    if let Some(id) = record.get(0) {
-                        self.record_map
-                            .insert(u64::from_str(id).unwrap(), record.clone());
-                    }
-                    let response = ReceiveTransaction::new(record);
-                    self.logger.do_send(LogMessage::new(
-                        "FileReader: Sending to transaction_dispatcher".to_string(),
-                    ));
-                    self.transaction_dispatcher.do_send(response);
-                    return ReadStatus::KeepReading;
-                }
-                ReadStatus::Eof
+        if self.in_flight_limit.is_full(self.record_map.len()) {
+            return Box::pin(std::future::ready(ReadStatus::Full).into_actor(self));
+        }
+        let read = self.transaction_source.next_record();
+        let end = self.transaction_source.position();
+        let row = end.record();
+        let record = match read {
+            Ok(Some(record)) => record,
+            Ok(None) => return Box::pin(std::future::ready(ReadStatus::Eof).into_actor(self)),
+            Err(e) => {
+                // ni siquiera se pudo armar la fila, se rechaza lo que haya
+                self.checkpoints.served(row, end);
+                let line = self.transaction_source.line();
+                self.reject(line, row, StringRecord::new(), e);
+                return Box::pin(std::future::ready(ReadStatus::KeepReading).into_actor(self));
             }
-            Err(e) => ReadStatus::ParseError(e),
+        };
+        self.checkpoints.served(row, end);
+        let line = self.transaction_source.line();
+        let transaction = match self.validator.check(&record) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                self.reject(line, row, record, e);
+                return Box::pin(std::future::ready(ReadStatus::KeepReading).into_actor(self));
+            }
+        };
+        let id = transaction.get_transaction_id();
+        // si el id ya esta en vuelo el dispatcher no la va a preparar, y no hay que pisar
+        // lo que se guardo de la primera
+        let owned = !self.row_of.contains_key(&id);
+        if owned {
+            self.record_map.insert(id, record.clone());
+            self.row_of.insert(id, row);
         }
+        self.logger.do_send(LogMessage::debug(
+            "FileReader: Sending to transaction_dispatcher".to_string(),
+        ));
+        let dispatched = self
+            .transaction_dispatcher
+            .send(ReceiveTransaction::new(transaction));
+        Box::pin(dispatched.into_actor(self).map(move |dispatched, me, _| {
+            if matches!(dispatched, Ok(Dispatch::Prepared)) {
+                return ReadStatus::KeepReading;
+            }
+            // las que no se preparan no ocupan lugar
+            if owned {
+                me.record_map.remove(&id);
+                me.row_of.remove(&id);
+            }
+            match dispatched {
+                Ok(Dispatch::Rejected(e)) => me.reject(line, row, record, e),
+                _ => me.checkpoints.resolved(row),
+            }
+            ReadStatus::KeepReading
+        }))
     }
 }
 
-#[derive(Message, Clone, Copy)]
+// se decidio una transaccion: si se aborto trae el motivo
+#[derive(Message, Clone)]
 #[rtype(result = "()")]
-pub struct FindTransaction {
+pub struct TransactionResolved {
     pub transaction_id: u64,
+    pub attempt: u32,
+    pub failure: Option<TransactionFailure>,
 }
 
-impl FindTransaction {
-    pub fn new(transaction_id: u64) -> Self {
-        FindTransaction { transaction_id }
+impl TransactionResolved {
+    pub fn new(transaction_id: u64, attempt: u32, failure: Option<TransactionFailure>) -> Self {
+        TransactionResolved {
+            transaction_id,
+            attempt,
+            failure,
+        }
     }
 }
 
-impl Handler<FindTransaction> for FileReader {
+impl Handler<TransactionResolved> for FileReader {
     type Result = ();
 
-    fn handle(&mut self, msg: FindTransaction, _ctx: &mut Self::Context) -> Self::Result {
-        if let Some(record) = self.record_map.remove(&msg.transaction_id) {
-            self.failed_transaction_logger
-                .do_send(FailedTransaction::new(record));
-            self.logger.do_send(LogMessage::new(
-                "FileReader: found specific transaction".to_string(),
-            ));
-        } else {
-            self.logger.do_send(LogMessage::new(
-                "FileReader: couldnt find specific transaction".to_string(),
-            ));
+    fn handle(&mut self, msg: TransactionResolved, ctx: &mut Self::Context) -> Self::Result {
+        let record = match self.record_map.remove(&msg.transaction_id) {
+            Some(record) => record,
+            None => {
+                // las recuperadas del lider anterior no se leyeron de este archivo,
+                // no hay fila para reintentar asi que terminan aca
+                self.logger.do_send(LogMessage::debug(
+                    "FileReader: couldnt find specific transaction".to_string(),
+                ));
+                let state = match msg.failure {
+                    Some(_) => FinalState::Abort,
+                    None => FinalState::Commit,
+                };
+                return self.finish(msg.transaction_id, msg.attempt, state, None);
+            }
+        };
+        self.in_flight_limit.release();
+        let failure = match msg.failure {
+            Some(failure) => failure,
+            None => {
+                return self.finish(
+                    msg.transaction_id,
+                    msg.attempt,
+                    FinalState::Commit,
+                    Some(&record),
+                )
+            }
+        };
+        self.logger.do_send(LogMessage::debug(
+            "FileReader: found specific transaction".to_string(),
+        ));
+        let attempt = record_attempt(&record).unwrap_or_default();
+        match &self.retry_policy {
+            Some(policy) if policy.should_retry(attempt) => {
+                let backoff = policy.backoff(attempt);
+                self.logger.do_send(LogMessage::new(format!(
+                    "FileReader: transaction {} failed ({}), retrying in {:?}",
+                    msg.transaction_id, failure.reason, backoff
+                )));
+                self.failed_transaction_logger
+                    .do_send(FailedTransaction::new(record.clone(), failure));
+                ctx.run_later(backoff, move |me, _| me.retry(record, attempt + 1));
+            }
+            Some(_) => {
+                self.logger.do_send(LogMessage::new(format!(
+                    "FileReader: transaction {} exhausted its retries ({})",
+                    msg.transaction_id, failure.reason
+                )));
+                self.finish(
+                    msg.transaction_id,
+                    attempt,
+                    FinalState::Abort,
+                    Some(&record),
+                );
+                self.failed_transaction_logger
+                    .do_send(DeadLetterTransaction::new(record, failure));
+            }
+            None => {
+                self.finish(
+                    msg.transaction_id,
+                    attempt,
+                    FinalState::Abort,
+                    Some(&record),
+                );
+                self.failed_transaction_logger
+                    .do_send(FailedTransaction::new(record, failure));
+            }
+        }
+    }
+}
+
+// Arranca los checkpoints: si hay uno de este mismo archivo salta hasta ahi, y despues
+// guarda y replica periodicamente hasta donde esta resuelto. Devuelve la fila desde donde sigue
+#[derive(Message)]
+#[rtype(result = "Option<u64>")]
+pub struct EnableCheckpoints {
+    path: String,
+    interval: Duration,
+    replicator: Addr<Replicator>,
+}
+
+impl EnableCheckpoints {
+    pub fn new(path: String, interval: Duration, replicator: Addr<Replicator>) -> Self {
+        EnableCheckpoints {
+            path,
+            interval,
+            replicator,
         }
     }
 }
 
+impl FileReader {
+    fn resume_from(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let position = checkpoint.position();
+        self.transaction_source.seek(&position)?;
+        self.checkpoints = CheckpointTracker::new(Some(position));
+        Ok(())
+    }
+}
+
+impl Handler<EnableCheckpoints> for FileReader {
+    type Result = Option<u64>;
+
+    fn handle(&mut self, msg: EnableCheckpoints, ctx: &mut Self::Context) -> Self::Result {
+        let fingerprint = match self.transaction_source.fingerprint() {
+            Ok(Some(fingerprint)) => fingerprint,
+            Ok(None) => {
+                self.logger.do_send(LogMessage::new(format!(
+                    "FileReader: {} can't be read again, checkpoints are disabled",
+                    self.transaction_file_path
+                )));
+                return None;
+            }
+            Err(e) => {
+                self.logger.do_send(LogMessage::error(format!(
+                    "FileReader: ERROR reading {}: {}",
+                    self.transaction_file_path, e
+                )));
+                return None;
+            }
+        };
+        let resumed = match Checkpoint::load(&msg.path) {
+            Ok(Some(checkpoint))
+                if checkpoint.matches(&self.transaction_file_path, fingerprint) =>
+            {
+                match self.resume_from(&checkpoint) {
+                    Ok(()) => Some(checkpoint.record),
+                    Err(e) => {
+                        self.logger.do_send(LogMessage::error(format!(
+                            "FileReader: ERROR seeking to checkpoint: {}",
+                            e
+                        )));
+                        None
+                    }
+                }
+            }
+            Ok(_) => None,
+            Err(e) => {
+                self.logger.do_send(LogMessage::error(format!(
+                    "FileReader: ERROR loading checkpoint: {}",
+                    e
+                )));
+                None
+            }
+        };
+        self.checkpoint_store = Some(CheckpointStore {
+            path: msg.path,
+            fingerprint,
+            replicator: msg.replicator,
+            saved_record: resumed,
+        });
+        ctx.run_interval(msg.interval, |me, _| me.save_checkpoint());
+        resumed
+    }
+}
+
+// de cada una lo que mira el dispatcher: como termino y en que intento
+fn done_transactions(entries: BTreeMap<u64, LedgerEntry>) -> HashMap<u64, (FinalState, u32)> {
+    entries
+        .into_iter()
+        .map(|(id, entry)| (id, (entry.state, entry.attempt)))
+        .collect()
+}
+
 #[derive(Message)]
-#[rtype(result = "")]
+#[rtype(result = "()")]
 pub struct ReadDoneTransactions {}
 
 impl Handler<ReadDoneTransactions> for FileReader {
     type Result = ();
 
     fn handle(&mut self, _: ReadDoneTransactions, _: &mut Self::Context) -> Self::Result {
-        if let Ok(mut file) = Reader::from_path(DONE_TRANSACTIONS_PATH) {
-            let mut done_transactions = HashSet::new();
-            for id_as_str in file.records().flatten() {
-                if let Ok(id) = u64::from_str(id_as_str.as_slice()) {
-                    done_transactions.insert(id);
-                }
-            }
-            if !done_transactions.is_empty() {
-                self.transaction_dispatcher
-                    .do_send(SaveDoneTransactions::new(done_transactions));
-            }
+        match DoneLedger::load(&self.done_transactions_path) {
+            Ok(entries) if !entries.is_empty() => self
+                .transaction_dispatcher
+                .do_send(SaveDoneTransactions::new(done_transactions(entries))),
+            Ok(_) => {}
+            Err(e) => self.logger.do_send(LogMessage::error(format!(
+                "FileReader: ERROR reading done transactions: {}",
+                e
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::done_ledger::{DoneLedger, FinalState, LedgerEntry};
+    use crate::file_reader::{done_transactions, prepare_replay_file};
+    use crate::input_source::{self, InputFormat, InputSpec};
+    use crate::transaction_dispatcher::{parse_transaction, CollisionDetector, Dispatch};
+    use alglobo_common_utils::cluster_config::DuplicatePolicy;
+
+    #[test]
+    fn test_replay_file_bumps_the_last_attempt() {
+        let failed = "test_replay_failed.csv";
+        let replay = "test_replay_failed.csv.replay";
+        std::fs::write(
+            failed,
+            "id,hotel_cost,bank_cost,airline_cost,attempt,reason\n\
+             1,10,20,30,0,timeout\n\
+             2,5,0,0,3,Bank voted Abort\n\
+             1,10,20,30,1,timeout\n",
+        )
+        .unwrap();
+        let rows = prepare_replay_file(failed, replay).unwrap();
+        let content = std::fs::read_to_string(replay).unwrap();
+        let _ = std::fs::remove_file(failed);
+        let _ = std::fs::remove_file(replay);
+
+        assert_eq!(rows, 2);
+        assert_eq!(
+            content,
+            "id,hotel_cost,bank_cost,airline_cost,currency,customer,property,check_in,check_out,flight,\
+             fare_class,attempt\n\
+             1,10,20,30,ARS,,,,,,,2\n\
+             2,5,0,0,ARS,,,,,,,4\n"
+        );
+    }
+
+    #[test]
+    fn test_replayed_failures_are_dispatched_again() {
+        let failed = "test_replay_dispatch_failed.csv";
+        let replay = "test_replay_dispatch_failed.csv.replay";
+        let done = "test_replay_dispatch_done.csv";
+        let _ = std::fs::remove_file(done);
+        std::fs::write(
+            failed,
+            "id,hotel_cost,bank_cost,airline_cost,attempt,reason\n\
+             1,10,20,30,0,timeout\n\
+             2,5,0,0,2,Bank voted Abort\n",
+        )
+        .unwrap();
+        // lo que dejo la corrida que fallo: las dos abortadas y una que si se commiteo
+        let mut ledger = DoneLedger::open(done).unwrap();
+        for entry in [
+            LedgerEntry::new(1, FinalState::Abort, 1),
+            LedgerEntry::new(2, FinalState::Abort, 1).with_attempt(2),
+            LedgerEntry::new(3, FinalState::Commit, 1),
+        ] {
+            ledger.record(&entry).unwrap();
         }
+        drop(ledger);
+        prepare_replay_file(failed, replay).unwrap();
+
+        let mut detector = CollisionDetector::new(DuplicatePolicy::Idempotent);
+        detector.mark_done(done_transactions(DoneLedger::load(done).unwrap()));
+        let mut source =
+            input_source::open(&InputSpec::new(replay.to_string(), Some(InputFormat::Csv)))
+                .unwrap();
+        let mut dispatched = vec![];
+        while let Some(record) = source.next_record().unwrap() {
+            let transaction = parse_transaction(&record).unwrap();
+            dispatched.push((
+                transaction.get_transaction_id(),
+                detector.check(&transaction),
+            ));
+        }
+        let _ = std::fs::remove_file(failed);
+        let _ = std::fs::remove_file(replay);
+        let _ = std::fs::remove_file(done);
+
+        assert_eq!(
+            dispatched,
+            vec![(1, Dispatch::Prepared), (2, Dispatch::Prepared)]
+        );
+        // la fila original, con el intento que ya se aborto, sigue sin volver a prepararse
+        let mut detector = CollisionDetector::new(DuplicatePolicy::Idempotent);
+        detector.mark_done(done_transactions(
+            [(2, LedgerEntry::new(2, FinalState::Abort, 1).with_attempt(2))].into(),
+        ));
+        let record =
+            csv::StringRecord::from(vec!["2", "5", "0", "0", "ARS", "", "", "", "", "", "", "2"]);
+        assert_eq!(
+            detector.check(&parse_transaction(&record).unwrap()),
+            Dispatch::AlreadyDone
+        );
     }
 }
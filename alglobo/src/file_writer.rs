@@ -1,65 +1,120 @@
+use crate::abort_reason::TransactionFailure;
+use crate::done_ledger::{DoneLedger, FinalState, LedgerEntry};
+use crate::input_source::{ATTEMPT_FIELD, FIELDS};
+use crate::replicator::{Replicate, ReplicationEntry, Replicator};
 use crate::LogMessage;
-use actix::{Actor, Addr, Context, Handler, Message};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
+use alglobo_common_utils::cluster_config::FileConfig;
+use alglobo_common_utils::money::Money;
 use std::fs::{File, OpenOptions};
+use std::time::Duration;
 
-use crate::file_reader::DONE_TRANSACTIONS_PATH;
 use crate::logger::LoggerActor;
 use csv::{StringRecord, Writer};
 
-const HEADER_ID: &str = "id";
-const HEADER_HOTEL: &str = "hotel_cost";
-const HEADER_BANK: &str = "bank_cost";
-const HEADER_AIRLINE: &str = "airline_cost";
+const HEADER_REASON: &str = "reason";
+const HEADER_VOTES: &str = "votes";
+const HEADER_TIMESTAMP: &str = "timestamp";
+const HEADER_LINE: &str = "line";
+const HEADER_ROW: &str = "row";
+const HEADER_ERROR: &str = "error";
+const REJECTED_HEADER: [&str; 3] = [HEADER_LINE, HEADER_ROW, HEADER_ERROR];
+
+// las columnas de la transaccion, con el intento siempre, y despues el motivo, los votos y
+// cuando se decidio
+fn failed_header() -> Vec<&'static str> {
+    let mut header = FIELDS.to_vec();
+    header.extend([HEADER_REASON, HEADER_VOTES, HEADER_TIMESTAMP]);
+    header
+}
+
+// la fila original, el intento en que fallo, por que, los votos y cuando se decidio
+fn failure_record(raw_transaction: &StringRecord, failure: &TransactionFailure) -> StringRecord {
+    // las filas del archivo original no traen el intento, que es el 0
+    let mut record = raw_transaction
+        .iter()
+        .take(ATTEMPT_FIELD)
+        .collect::<StringRecord>();
+    record.push_field(raw_transaction.get(ATTEMPT_FIELD).unwrap_or("0"));
+    record.push_field(&failure.reason.to_string());
+    record.push_field(&failure.votes_column());
+    record.push_field(&failure.timestamp_column());
+    record
+}
 
 pub struct FileWriter {
     failed_transaction_file: Writer<File>,
-    done_transaction_file: Writer<File>,
+    dead_letter_file: Writer<File>,
+    rejected_file: Writer<File>,
+    done_ledger: DoneLedger,
+    ledger_compaction_interval: Duration,
+    pid: u8,
+    replicator: Addr<Replicator>,
     logger: Addr<LoggerActor>,
 }
 
+// abre para agregar al final, escribiendo el header solo si el archivo es nuevo
+fn append_with_header(
+    path: &str,
+    header: &[&str],
+) -> Result<Writer<File>, Box<dyn std::error::Error>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_new = file.metadata()?.len() == 0;
+    let mut writer = Writer::from_writer(file);
+    if is_new {
+        writer.write_record(header)?;
+        writer.flush()?;
+    }
+    Ok(writer)
+}
+
 impl FileWriter {
     pub fn new(
-        failed_transaction_file_path: String,
+        files: &FileConfig,
+        pid: u8,
+        ledger_compaction_interval: Duration,
+        replicator: Addr<Replicator>,
         logger: Addr<LoggerActor>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         logger.do_send(LogMessage::new("Creating FileWriter...".to_string()));
-        let done_transaction_file = match OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open(DONE_TRANSACTIONS_PATH)
-        {
-            Ok(file) => file,
-            Err(_) => File::create(DONE_TRANSACTIONS_PATH).unwrap(),
-        };
-
-        let mut result = FileWriter {
-            failed_transaction_file: Writer::from_path(failed_transaction_file_path)?,
-            done_transaction_file: Writer::from_writer(done_transaction_file),
+        Ok(FileWriter {
+            failed_transaction_file: Writer::from_path(&files.failed_transactions)?,
+            // a diferencia de las fallidas, las que agotaron los reintentos y las rechazadas
+            // no se pisan entre lideres
+            dead_letter_file: append_with_header(
+                &files.dead_letter_transactions,
+                &failed_header(),
+            )?,
+            rejected_file: append_with_header(&files.rejected_transactions, &REJECTED_HEADER)?,
+            done_ledger: DoneLedger::open(&files.done_transactions(pid))?,
+            ledger_compaction_interval,
+            pid,
+            replicator,
             logger,
-        };
-        result
-            .done_transaction_file
-            .write_record(&[HEADER_ID])
-            .expect("could not write record to file");
-        result
-            .done_transaction_file
-            .flush()
-            .expect("could not flush");
-        Ok(result)
+        })
     }
 }
 
 impl Actor for FileWriter {
     type Context = Context<Self>;
 
-    fn started(&mut self, _: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         self.failed_transaction_file
-            .write_record(&[HEADER_ID, HEADER_HOTEL, HEADER_BANK, HEADER_AIRLINE])
+            .write_record(failed_header())
             .expect("could not write record to file");
 
         self.failed_transaction_file
             .flush()
             .expect("could not flush");
+
+        ctx.run_interval(self.ledger_compaction_interval, |me, _| {
+            if let Err(e) = me.done_ledger.compact() {
+                me.logger.do_send(LogMessage::error(format!(
+                    "ERROR compacting done transactions: {}",
+                    e
+                )));
+            }
+        });
     }
 }
 
@@ -67,11 +122,15 @@ impl Actor for FileWriter {
 #[rtype(result = "()")]
 pub struct FailedTransaction {
     raw_transaction: StringRecord,
+    failure: TransactionFailure,
 }
 
 impl FailedTransaction {
-    pub fn new(raw_transaction: StringRecord) -> Self {
-        FailedTransaction { raw_transaction }
+    pub fn new(raw_transaction: StringRecord, failure: TransactionFailure) -> Self {
+        FailedTransaction {
+            raw_transaction,
+            failure,
+        }
     }
 }
 
@@ -79,10 +138,8 @@ impl Handler<FailedTransaction> for FileWriter {
     type Result = ();
 
     fn handle(&mut self, msg: FailedTransaction, _ctx: &mut Self::Context) -> Self::Result {
-        if let Err(what) = self
-            .failed_transaction_file
-            .write_record(msg.raw_transaction.as_byte_record())
-        {
+        let record = failure_record(&msg.raw_transaction, &msg.failure);
+        if let Err(what) = self.failed_transaction_file.write_record(&record) {
             self.logger.do_send(LogMessage::new(format!(
                 "Saved failed transaction, with error message: {}",
                 what
@@ -95,30 +152,111 @@ impl Handler<FailedTransaction> for FileWriter {
 
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct RegisterDoneTransactionId {
-    transaction_id: u64,
+pub struct DeadLetterTransaction {
+    raw_transaction: StringRecord,
+    failure: TransactionFailure,
+}
+
+impl DeadLetterTransaction {
+    pub fn new(raw_transaction: StringRecord, failure: TransactionFailure) -> Self {
+        DeadLetterTransaction {
+            raw_transaction,
+            failure,
+        }
+    }
+}
+
+impl Handler<DeadLetterTransaction> for FileWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: DeadLetterTransaction, _ctx: &mut Self::Context) -> Self::Result {
+        let record = failure_record(&msg.raw_transaction, &msg.failure);
+        if let Err(what) = self.dead_letter_file.write_record(&record) {
+            self.logger.do_send(LogMessage::error(format!(
+                "ERROR saving dead letter transaction: {}",
+                what
+            )));
+        } else {
+            let _ = self.dead_letter_file.flush();
+        }
+    }
+}
+
+// una fila que no paso la validacion: no se prepara y queda con su linea y el motivo
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RejectedTransaction {
+    line: u64,
+    raw_transaction: StringRecord,
+    error: String,
 }
 
-impl RegisterDoneTransactionId {
-    pub fn new(transaction_id: u64) -> Self {
-        Self { transaction_id }
+impl RejectedTransaction {
+    pub fn new(line: u64, raw_transaction: StringRecord, error: String) -> Self {
+        RejectedTransaction {
+            line,
+            raw_transaction,
+            error,
+        }
     }
 }
 
-impl Handler<RegisterDoneTransactionId> for FileWriter {
+impl Handler<RejectedTransaction> for FileWriter {
     type Result = ();
 
-    fn handle(&mut self, msg: RegisterDoneTransactionId, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: RejectedTransaction, _ctx: &mut Self::Context) -> Self::Result {
+        let row = msg.raw_transaction.iter().collect::<Vec<&str>>().join(",");
         if let Err(what) = self
-            .done_transaction_file
-            .write_record([msg.transaction_id.to_string().as_str()])
+            .rejected_file
+            .write_record([msg.line.to_string(), row, msg.error])
         {
-            self.logger.do_send(LogMessage::new(format!(
-                "Failed to save done transaction id, with error message: {}",
+            self.logger.do_send(LogMessage::error(format!(
+                "ERROR saving rejected transaction: {}",
                 what
             )));
         } else {
-            let _ = self.done_transaction_file.flush();
+            let _ = self.rejected_file.flush();
+        }
+    }
+}
+
+// la transaccion termino y no se vuelve a intentar: queda en el registro y se replica
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterDoneTransaction {
+    transaction_id: u64,
+    attempt: u32,
+    state: FinalState,
+    // None si no se tiene la fila, como en las recuperadas del lider anterior
+    total: Option<Money>,
+}
+
+impl RegisterDoneTransaction {
+    pub fn new(transaction_id: u64, attempt: u32, state: FinalState, total: Option<Money>) -> Self {
+        Self {
+            transaction_id,
+            attempt,
+            state,
+            total,
+        }
+    }
+}
+
+impl Handler<RegisterDoneTransaction> for FileWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterDoneTransaction, _: &mut Self::Context) -> Self::Result {
+        let entry = LedgerEntry::new(msg.transaction_id, msg.state, self.pid)
+            .with_attempt(msg.attempt)
+            .with_total(msg.total);
+        if let Err(what) = self.done_ledger.record(&entry) {
+            self.logger.do_send(LogMessage::error(format!(
+                "ERROR saving done transaction {}: {}",
+                msg.transaction_id, what
+            )));
+            return;
         }
+        self.replicator
+            .do_send(Replicate::new(ReplicationEntry::Done(entry)));
     }
 }
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+// Tope de transacciones en vuelo. El que lee el archivo espera en wait() cuando se llena,
+// y se lo despierta con release() cada vez que se decide una transaccion
+#[derive(Clone)]
+pub struct InFlightLimit {
+    max_in_flight: usize,
+    capacity_freed: Arc<Notify>,
+}
+
+impl InFlightLimit {
+    pub fn new(max_in_flight: usize) -> Self {
+        InFlightLimit {
+            max_in_flight,
+            capacity_freed: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn is_full(&self, in_flight: usize) -> bool {
+        in_flight >= self.max_in_flight
+    }
+
+    // si nadie esta esperando queda guardado, asi no se pierde el aviso
+    pub fn release(&self) {
+        self.capacity_freed.notify_one();
+    }
+
+    pub async fn wait(&self) {
+        self.capacity_freed.notified().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_flight_limit::InFlightLimit;
+    use std::time::Duration;
+
+    #[actix_rt::test]
+    async fn test_release_before_wait_is_not_lost() {
+        let limit = InFlightLimit::new(2);
+        assert!(!limit.is_full(1));
+        assert!(limit.is_full(2));
+
+        limit.clone().release();
+        let waited = tokio::time::timeout(Duration::from_secs(1), limit.wait()).await;
+        assert!(waited.is_ok());
+    }
+}
@@ -0,0 +1,778 @@
+use crate::checkpoint::fingerprint;
+use alglobo_common_utils::money::{CURRENCY_CODE_SIZE, DEFAULT_CURRENCY};
+use csv::{Position, ReaderBuilder, StringRecord};
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Stdin};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// una entrada "-" se lee de stdin
+pub const STDIN: &str = "-";
+
+// columnas de las filas que circulan por el pipeline, en este orden. Si la entrada no trae
+// la moneda es DEFAULT_CURRENCY, y el cliente, la estadia en el hotel y el asiento en el avion
+// quedan vacios. El intento va ultimo y solo en las filas reintentadas
+pub const FIELDS: [&str; 12] = [
+    "id",
+    "hotel_cost",
+    "bank_cost",
+    "airline_cost",
+    "currency",
+    "customer",
+    "property",
+    "check_in",
+    "check_out",
+    "flight",
+    "fare_class",
+    "attempt",
+];
+const REQUIRED_FIELDS: usize = 4;
+pub const CURRENCY_FIELD: usize = 4;
+// de aca hasta el intento son opcionales
+pub const CUSTOMER_FIELD: usize = 5;
+pub const PROPERTY_FIELD: usize = 6;
+pub const CHECK_IN_FIELD: usize = 7;
+pub const CHECK_OUT_FIELD: usize = 8;
+pub const FLIGHT_FIELD: usize = 9;
+pub const FARE_CLASS_FIELD: usize = 10;
+pub const ATTEMPT_FIELD: usize = 11;
+const OPTIONAL_FIELDS: usize = ATTEMPT_FIELD - CUSTOMER_FIELD;
+
+// formato binario: cada registro es un largo u32 big endian y despues id, hotel_cost, bank_cost
+// y airline_cost como u64 big endian, la moneda como tres letras ascii y opcionalmente el
+// intento como u32. Los registros de antes de la moneda no la tienen. No traen cliente,
+// estadia ni asiento
+const FRAME_LENGTH_BYTES: usize = 4;
+const FRAME_COSTS: usize = 32;
+const FRAME_COSTS_AND_ATTEMPT: usize = 36;
+const FRAME_WITHOUT_ATTEMPT: usize = FRAME_COSTS + CURRENCY_CODE_SIZE;
+const FRAME_WITH_ATTEMPT: usize = FRAME_WITHOUT_ATTEMPT + 4;
+// un largo mas grande que esto es basura: el resto del stream no se puede interpretar
+const MAX_FRAME: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    JsonLines,
+    Binary,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::JsonLines),
+            "binary" => Ok(InputFormat::Binary),
+            _ => Err(format!(
+                "Formato de entrada invalido: {} (csv, jsonl o binary)",
+                format
+            )),
+        }
+    }
+}
+
+impl InputFormat {
+    // None si la extension no es de ningun formato conocido
+    fn from_extension(path: &Path) -> Option<InputFormat> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(InputFormat::Csv),
+            "jsonl" | "ndjson" => Some(InputFormat::JsonLines),
+            "bin" => Some(InputFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+// De donde leer las transacciones: un archivo, un directorio o stdin. Sin formato se deduce
+// de la extension de cada archivo, y stdin se lee como csv
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSpec {
+    pub path: String,
+    pub format: Option<InputFormat>,
+}
+
+impl InputSpec {
+    pub fn new(path: String, format: Option<InputFormat>) -> Self {
+        InputSpec { path, format }
+    }
+}
+
+// Una fuente de transacciones. Cada formato entrega las filas ya normalizadas a FIELDS,
+// que es lo que entiende el resto del pipeline
+pub trait InputSource {
+    // None al terminar la entrada
+    fn next_record(&mut self) -> Result<Option<StringRecord>, String>;
+
+    // donde termina la ultima fila leida
+    fn position(&self) -> Position;
+
+    // linea donde empieza la ultima fila leida, aunque haya tenido errores.
+    // En los formatos sin lineas es el numero de registro
+    fn line(&self) -> u64;
+
+    // vuelve a una posicion que antes devolvio position
+    fn seek(&mut self, position: &Position) -> Result<(), String>;
+
+    // identifica el contenido para no retomar un checkpoint de otra entrada.
+    // None si la entrada no se puede volver a leer, como stdin
+    fn fingerprint(&self) -> Result<Option<u64>, String>;
+}
+
+pub fn open(spec: &InputSpec) -> Result<Box<dyn InputSource>, String> {
+    if spec.path == STDIN {
+        let stream = Stream::Stdin(BufReader::new(io::stdin()));
+        return open_stream(stream, None, spec.format.unwrap_or(InputFormat::Csv));
+    }
+    let path = Path::new(&spec.path);
+    if path.is_dir() {
+        return Ok(Box::new(DirectorySource::new(path, spec.format)?));
+    }
+    open_file(path, spec.format)
+}
+
+fn open_file(path: &Path, format: Option<InputFormat>) -> Result<Box<dyn InputSource>, String> {
+    let format = format
+        .or_else(|| InputFormat::from_extension(path))
+        .unwrap_or(InputFormat::Csv);
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let path = path.to_string_lossy().to_string();
+    open_stream(Stream::File(BufReader::new(file)), Some(path), format)
+}
+
+fn open_stream(
+    stream: Stream,
+    path: Option<String>,
+    format: InputFormat,
+) -> Result<Box<dyn InputSource>, String> {
+    Ok(match format {
+        InputFormat::Csv => Box::new(CsvSource::new(stream, path)?),
+        InputFormat::JsonLines => Box::new(JsonLinesSource::new(stream, path)),
+        InputFormat::Binary => Box::new(BinarySource::new(stream, path)),
+    })
+}
+
+fn file_fingerprint(path: &Option<String>) -> Result<Option<u64>, String> {
+    match path {
+        Some(path) => fingerprint(path)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path, e)),
+        None => Ok(None),
+    }
+}
+
+// Lo que hay abajo de cada formato: un archivo, que se puede volver a recorrer, o stdin
+enum Stream {
+    File(BufReader<File>),
+    Stdin(BufReader<Stdin>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::File(file) => file.read(buf),
+            Stream::Stdin(stdin) => stdin.read(buf),
+        }
+    }
+}
+
+impl BufRead for Stream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Stream::File(file) => file.fill_buf(),
+            Stream::Stdin(stdin) => stdin.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            Stream::File(file) => file.consume(amount),
+            Stream::Stdin(stdin) => stdin.consume(amount),
+        }
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Stream::File(file) => file.seek(position),
+            Stream::Stdin(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "stdin can't be rewound",
+            )),
+        }
+    }
+}
+
+// CSV con header. Las columnas se buscan por nombre, asi pueden venir en cualquier orden
+pub struct CsvSource {
+    reader: csv::Reader<Stream>,
+    // en que columna del archivo esta cada uno de FIELDS
+    columns: Vec<Option<usize>>,
+    line: u64,
+    path: Option<String>,
+}
+
+impl CsvSource {
+    fn new(stream: Stream, path: Option<String>) -> Result<Self, String> {
+        // a las filas cortas les falta alguna columna, lo avisa next_record
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader(stream);
+        let header = reader.headers().map_err(|e| e.to_string())?.clone();
+        let columns = FIELDS
+            .iter()
+            .map(|field| header.iter().position(|column| column == *field))
+            .collect::<Vec<Option<usize>>>();
+        if let Some(missing) = FIELDS
+            .iter()
+            .zip(&columns)
+            .take(REQUIRED_FIELDS)
+            .find(|(_, column)| column.is_none())
+        {
+            return Err(format!("Missing column {} in the header", missing.0));
+        }
+        Ok(CsvSource {
+            reader,
+            columns,
+            line: 0,
+            path,
+        })
+    }
+}
+
+impl InputSource for CsvSource {
+    fn next_record(&mut self) -> Result<Option<StringRecord>, String> {
+        let mut raw = StringRecord::new();
+        let read = self.reader.read_record(&mut raw);
+        self.line = match &read {
+            Ok(_) => raw.position().map(|position| position.line()),
+            Err(e) => e.position().map(|position| position.line()),
+        }
+        .unwrap_or_else(|| self.reader.position().line());
+        if !read.map_err(|e| e.to_string())? {
+            return Ok(None);
+        }
+        let mut record = StringRecord::new();
+        for (field, column) in FIELDS.iter().zip(&self.columns) {
+            match column.and_then(|column| raw.get(column)) {
+                Some(value) => record.push_field(value),
+                None if record.len() < REQUIRED_FIELDS => {
+                    return Err(format!("Missing column {}", field))
+                }
+                None if record.len() == CURRENCY_FIELD => {
+                    record.push_field(DEFAULT_CURRENCY.code())
+                }
+                None if record.len() < ATTEMPT_FIELD => record.push_field(""),
+                None => {}
+            }
+        }
+        Ok(Some(record))
+    }
+
+    fn position(&self) -> Position {
+        self.reader.position().clone()
+    }
+
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    fn seek(&mut self, position: &Position) -> Result<(), String> {
+        self.reader
+            .seek(position.clone())
+            .map_err(|e| e.to_string())
+    }
+
+    fn fingerprint(&self) -> Result<Option<u64>, String> {
+        file_fingerprint(&self.path)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonTransaction {
+    id: u64,
+    hotel_cost: u64,
+    bank_cost: u64,
+    airline_cost: u64,
+    // la valida el validador, como en el csv
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default)]
+    customer: Option<u64>,
+    // las fechas tambien las valida el validador
+    #[serde(default)]
+    property: String,
+    #[serde(default)]
+    check_in: String,
+    #[serde(default)]
+    check_out: String,
+    #[serde(default)]
+    flight: String,
+    #[serde(default)]
+    fare_class: String,
+    #[serde(default)]
+    attempt: u32,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.code().to_string()
+}
+
+// la fila normalizada; el intento solo va si no es el original, como en el csv
+fn transaction_record(
+    id: u64,
+    costs: [u64; 3],
+    currency: &str,
+    optional: [String; OPTIONAL_FIELDS],
+    attempt: u32,
+) -> StringRecord {
+    let mut record = StringRecord::new();
+    record.push_field(&id.to_string());
+    for cost in costs {
+        record.push_field(&cost.to_string());
+    }
+    record.push_field(currency);
+    for field in optional {
+        record.push_field(&field);
+    }
+    if attempt > 0 {
+        record.push_field(&attempt.to_string());
+    }
+    record
+}
+
+// Un objeto JSON por linea, con los mismos nombres que las columnas del csv. Las lineas
+// vacias se saltean
+pub struct JsonLinesSource {
+    stream: Stream,
+    position: Position,
+    path: Option<String>,
+}
+
+impl JsonLinesSource {
+    fn new(stream: Stream, path: Option<String>) -> Self {
+        JsonLinesSource {
+            stream,
+            position: Position::new(),
+            path,
+        }
+    }
+}
+
+impl InputSource for JsonLinesSource {
+    fn next_record(&mut self) -> Result<Option<StringRecord>, String> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Ok(None);
+            }
+            let byte = self.position.byte() + read as u64;
+            let line_number = self.position.line() + 1;
+            self.position.set_byte(byte).set_line(line_number);
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        let record = self.position.record() + 1;
+        self.position.set_record(record);
+        let transaction =
+            serde_json::from_str::<JsonTransaction>(line.trim()).map_err(|e| e.to_string())?;
+        Ok(Some(transaction_record(
+            transaction.id,
+            [
+                transaction.hotel_cost,
+                transaction.bank_cost,
+                transaction.airline_cost,
+            ],
+            &transaction.currency,
+            [
+                transaction
+                    .customer
+                    .map(|customer| customer.to_string())
+                    .unwrap_or_default(),
+                transaction.property,
+                transaction.check_in,
+                transaction.check_out,
+                transaction.flight,
+                transaction.fare_class,
+            ],
+            transaction.attempt,
+        )))
+    }
+
+    fn position(&self) -> Position {
+        self.position.clone()
+    }
+
+    // position apunta a la linea siguiente
+    fn line(&self) -> u64 {
+        self.position.line() - 1
+    }
+
+    fn seek(&mut self, position: &Position) -> Result<(), String> {
+        self.stream
+            .seek(SeekFrom::Start(position.byte()))
+            .map_err(|e| e.to_string())?;
+        self.position = position.clone();
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> Result<Option<u64>, String> {
+        file_fingerprint(&self.path)
+    }
+}
+
+// Registros binarios con el largo adelante, ver FRAME_LENGTH_BYTES. No hay lineas,
+// la posicion solo lleva bytes y registros
+pub struct BinarySource {
+    stream: Stream,
+    position: Position,
+    // se encontro un largo imposible y ya no se sabe donde empieza el registro siguiente
+    corrupted: bool,
+    path: Option<String>,
+}
+
+impl BinarySource {
+    fn new(stream: Stream, path: Option<String>) -> Self {
+        BinarySource {
+            stream,
+            position: Position::new(),
+            corrupted: false,
+            path,
+        }
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_be_bytes(value)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_be_bytes(value)
+}
+
+// si no son letras la fila la rechaza el validador
+fn read_currency(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(&bytes[FRAME_COSTS..FRAME_WITHOUT_ATTEMPT]).to_string()
+}
+
+impl InputSource for BinarySource {
+    fn next_record(&mut self) -> Result<Option<StringRecord>, String> {
+        if self.corrupted
+            || self
+                .stream
+                .fill_buf()
+                .map_err(|e| e.to_string())?
+                .is_empty()
+        {
+            return Ok(None);
+        }
+        let mut length = [0u8; FRAME_LENGTH_BYTES];
+        self.stream
+            .read_exact(&mut length)
+            .map_err(|e| e.to_string())?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_FRAME {
+            self.corrupted = true;
+            let record = self.position.record() + 1;
+            self.position.set_record(record);
+            return Err(format!(
+                "Record is {} bytes long, the rest of the input is ignored",
+                length
+            ));
+        }
+        let mut payload = vec![0u8; length];
+        self.stream
+            .read_exact(&mut payload)
+            .map_err(|e| e.to_string())?;
+        let byte = self.position.byte() + (FRAME_LENGTH_BYTES + length) as u64;
+        let record = self.position.record() + 1;
+        self.position.set_byte(byte).set_record(record);
+        let (currency, attempt) = match length {
+            FRAME_COSTS => (DEFAULT_CURRENCY.code().to_string(), 0),
+            FRAME_COSTS_AND_ATTEMPT => (
+                DEFAULT_CURRENCY.code().to_string(),
+                read_u32(&payload, FRAME_COSTS),
+            ),
+            FRAME_WITHOUT_ATTEMPT => (read_currency(&payload), 0),
+            FRAME_WITH_ATTEMPT => (
+                read_currency(&payload),
+                read_u32(&payload, FRAME_WITHOUT_ATTEMPT),
+            ),
+            _ => return Err(format!("Record is {} bytes long", length)),
+        };
+        Ok(Some(transaction_record(
+            read_u64(&payload, 0),
+            [
+                read_u64(&payload, 8),
+                read_u64(&payload, 16),
+                read_u64(&payload, 24),
+            ],
+            &currency,
+            Default::default(),
+            attempt,
+        )))
+    }
+
+    fn position(&self) -> Position {
+        self.position.clone()
+    }
+
+    fn line(&self) -> u64 {
+        self.position.record()
+    }
+
+    fn seek(&mut self, position: &Position) -> Result<(), String> {
+        self.stream
+            .seek(SeekFrom::Start(position.byte()))
+            .map_err(|e| e.to_string())?;
+        self.position = position.clone();
+        self.corrupted = false;
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> Result<Option<u64>, String> {
+        file_fingerprint(&self.path)
+    }
+}
+
+// Todos los archivos de un directorio, ordenados por nombre. Se listan al abrirlo: los que
+// aparezcan despues no se leen. La posicion cuenta filas de todo el directorio
+pub struct DirectorySource {
+    files: Vec<PathBuf>,
+    format: Option<InputFormat>,
+    current: Option<Box<dyn InputSource>>,
+    next_file: usize,
+    records: u64,
+    line: u64,
+}
+
+impl DirectorySource {
+    fn new(directory: &Path, format: Option<InputFormat>) -> Result<Self, String> {
+        let entries =
+            fs::read_dir(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+        let mut files = vec![];
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            // sin formato solo se toman las extensiones conocidas
+            if path.is_file() && (format.is_some() || InputFormat::from_extension(&path).is_some())
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(DirectorySource {
+            files,
+            format,
+            current: None,
+            next_file: 0,
+            records: 0,
+            line: 0,
+        })
+    }
+}
+
+impl InputSource for DirectorySource {
+    fn next_record(&mut self) -> Result<Option<StringRecord>, String> {
+        loop {
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    let path = match self.files.get(self.next_file) {
+                        Some(path) => path,
+                        None => return Ok(None),
+                    };
+                    self.next_file += 1;
+                    self.line = 0;
+                    self.current.insert(open_file(path, self.format)?)
+                }
+            };
+            let result = current.next_record();
+            self.line = current.line();
+            match result {
+                Ok(Some(record)) => {
+                    self.records += 1;
+                    return Ok(Some(record));
+                }
+                Ok(None) => self.current = None,
+                Err(e) => {
+                    // el error cuenta como fila, asi la cuenta no depende de como se leyo
+                    self.records += 1;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn position(&self) -> Position {
+        let mut position = match &self.current {
+            Some(current) => current.position(),
+            None => Position::new(),
+        };
+        position.set_record(self.records);
+        position
+    }
+
+    // dentro del archivo actual
+    fn line(&self) -> u64 {
+        self.line
+    }
+
+    // los archivos no comparten posiciones, asi que se vuelve a leer desde el principio
+    // salteando las filas que ya se habian leido
+    fn seek(&mut self, position: &Position) -> Result<(), String> {
+        self.current = None;
+        self.next_file = 0;
+        self.records = 0;
+        while self.records < position.record() {
+            match self.next_record() {
+                Ok(Some(_)) | Err(_) => {}
+                Ok(None) => return Err("The directory has less rows than the checkpoint".into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> Result<Option<u64>, String> {
+        let mut hasher = DefaultHasher::new();
+        for path in &self.files {
+            hasher.write(path.to_string_lossy().as_bytes());
+            let file = fingerprint(&path.to_string_lossy())
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            hasher.write_u64(file);
+        }
+        Ok(Some(hasher.finish()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_source::{open, InputFormat, InputSpec};
+    use csv::StringRecord;
+
+    fn frame(id: u64, costs: [u64; 3], currency: Option<&str>, attempt: Option<u32>) -> Vec<u8> {
+        let mut payload = id.to_be_bytes().to_vec();
+        for cost in costs {
+            payload.extend(cost.to_be_bytes());
+        }
+        if let Some(currency) = currency {
+            payload.extend(currency.as_bytes());
+        }
+        if let Some(attempt) = attempt {
+            payload.extend(attempt.to_be_bytes());
+        }
+        let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+        frame.extend(payload);
+        frame
+    }
+
+    fn read_all(spec: &InputSpec) -> Vec<Result<StringRecord, String>> {
+        let mut source = open(spec).unwrap();
+        let mut rows = vec![];
+        loop {
+            match source.next_record() {
+                Ok(Some(record)) => rows.push(Ok(record)),
+                Ok(None) => return rows,
+                Err(e) => rows.push(Err(e)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_format_yields_the_same_rows() {
+        let dir = "test_input_sources";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        // columnas en otro orden y una de mas
+        std::fs::write(
+            format!("{}/1.csv", dir),
+            "bank_cost,id,notes,airline_cost,hotel_cost,property,check_in,check_out\n\
+             2,1,x,3,1,Centro,2025-01-01,2025-01-03\n",
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/2.jsonl", dir),
+            "{\"id\":2,\"hotel_cost\":0,\"bank_cost\":5,\"airline_cost\":0,\"customer\":12}\n\n\
+             {\"id\":3,\"hotel_cost\":1,\"bank_cost\":1,\"airline_cost\":1,\"currency\":\"USD\",\
+             \"flight\":\"AR1140\",\"fare_class\":\"Y\",\"attempt\":2}\n",
+        )
+        .unwrap();
+        let mut binary = frame(4, [7, 0, 0], None, None);
+        binary.extend(frame(5, [0, 0, 9], Some("EUR"), Some(1)));
+        binary.extend(frame(6, [0, 0, 9], None, Some(2)));
+        std::fs::write(format!("{}/3.bin", dir), binary).unwrap();
+        std::fs::write(format!("{}/notes.txt", dir), "ignored").unwrap();
+
+        let spec = InputSpec::new(dir.to_string(), None);
+        let rows = read_all(&spec)
+            .into_iter()
+            .map(|row| row.unwrap().iter().collect::<Vec<&str>>().join(","))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            rows,
+            vec![
+                "1,1,2,3,ARS,,Centro,2025-01-01,2025-01-03,,",
+                "2,0,5,0,ARS,12,,,,,",
+                "3,1,1,1,USD,,,,,AR1140,Y,2",
+                "4,7,0,0,ARS,,,,,,",
+                "5,0,0,9,EUR,,,,,,,1",
+                "6,0,0,9,ARS,,,,,,,2"
+            ]
+        );
+
+        // retomar a mitad del directorio sigue desde la misma fila
+        let mut source = open(&spec).unwrap();
+        source.next_record().unwrap();
+        source.next_record().unwrap();
+        let position = source.position();
+        let fingerprint = source.fingerprint().unwrap();
+        let mut resumed = open(&spec).unwrap();
+        resumed.seek(&position).unwrap();
+        assert_eq!(resumed.fingerprint().unwrap(), fingerprint);
+        assert_eq!(resumed.next_record().unwrap().unwrap().get(0), Some("3"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_bad_rows_are_errors() {
+        let path = "test_input_bad_rows.jsonl";
+        std::fs::write(
+            path,
+            "{\"id\":1}\n{\"id\":2,\"hotel_cost\":1,\"bank_cost\":0,\"airline_cost\":0}\n",
+        )
+        .unwrap();
+        let rows = read_all(&InputSpec::new(path.to_string(), None));
+        let _ = std::fs::remove_file(path);
+        assert!(rows[0].as_ref().unwrap_err().contains("missing field"));
+        assert_eq!(rows[1].as_ref().unwrap().get(0), Some("2"));
+
+        let path = "test_input_bad_rows.bin";
+        let mut binary = vec![0, 0, 0, 3, 1, 2, 3];
+        binary.extend(super::tests::frame(6, [1, 1, 1], Some("ARS"), None));
+        binary.extend([0xff, 0xff, 0xff, 0xff]);
+        std::fs::write(path, binary).unwrap();
+        let rows = read_all(&InputSpec::new(path.to_string(), Some(InputFormat::Binary)));
+        let _ = std::fs::remove_file(path);
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].is_err());
+        assert_eq!(rows[1].as_ref().unwrap().get(0), Some("6"));
+        assert!(rows[2].is_err());
+
+        let path = "test_input_bad_rows.csv";
+        std::fs::write(path, "id,hotel_cost,bank_cost\n1,2,3\n").unwrap();
+        assert!(open(&InputSpec::new(path.to_string(), None)).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
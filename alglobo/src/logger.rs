@@ -1,10 +1,38 @@
 use actix::{Actor, Context, Handler, Message};
 use alglobo_common_utils::entity_logger::Logger;
+use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
 
 // Wrapper utilizado para evitar duplicar codigo
 // Se implementa el trait Deref para evitar hacer self.0 en todos lados
-pub struct LoggerActor(Logger);
+pub struct LoggerActor(Logger, LogLevel);
 
 impl Deref for LoggerActor {
     type Target = Logger;
@@ -22,8 +50,9 @@ impl DerefMut for LoggerActor {
 }
 
 impl LoggerActor {
-    pub fn new(file_path: &str) -> Self {
-        LoggerActor(Logger::new(file_path))
+    // se descartan los mensajes mas detallados que el nivel
+    pub fn new(file_path: &str, level: LogLevel) -> Self {
+        LoggerActor(Logger::new(file_path), level)
     }
 }
 
@@ -31,11 +60,29 @@ impl LoggerActor {
 #[rtype(result = "()")]
 pub struct LogMessage {
     message: String,
+    level: LogLevel,
 }
 
 impl LogMessage {
     pub fn new(log: String) -> Self {
-        LogMessage { message: log }
+        LogMessage {
+            message: log,
+            level: LogLevel::Info,
+        }
+    }
+
+    pub fn error(log: String) -> Self {
+        LogMessage {
+            message: log,
+            level: LogLevel::Error,
+        }
+    }
+
+    pub fn debug(log: String) -> Self {
+        LogMessage {
+            message: log,
+            level: LogLevel::Debug,
+        }
     }
 }
 
@@ -47,6 +94,8 @@ impl Handler<LogMessage> for LoggerActor {
     type Result = ();
 
     fn handle(&mut self, log_message: LogMessage, _ctx: &mut Self::Context) -> Self::Result {
-        self.log(log_message.message);
+        if log_message.level <= self.1 {
+            self.log(log_message.message);
+        }
     }
 }
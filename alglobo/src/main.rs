@@ -2,18 +2,30 @@ extern crate core;
 
 pub use alglobo_common_utils;
 
+mod abort_reason;
 mod beater_responder;
 mod bootstrapper;
+mod checkpoint;
+mod cli;
+mod cluster_status;
+mod decision_log;
+mod done_ledger;
 mod entity_receiver;
 mod entity_sender;
 mod file_reader;
 mod file_writer;
+mod in_flight_limit;
+mod input_source;
 mod logger;
 mod ok_timeout_handler;
 mod pinger_finder;
+mod rate_limiter;
+mod replicator;
+mod retry_policy;
 mod statistics_handler;
 mod transaction_coordinator;
 mod transaction_dispatcher;
+mod transaction_validator;
 
 use crate::logger::{LogMessage, LoggerActor};
 use file_reader::FileReader;
@@ -26,43 +38,150 @@ use crate::statistics_handler::StatisticsHandler;
 use crate::transaction_coordinator::TransactionCoordinator;
 use actix::Actor;
 use actix_rt::{Arbiter, System};
+use alglobo_common_utils::cluster_config::ClusterConfig;
 
 use crate::beater_responder::{BeaterResponder, Responder};
 use crate::bootstrapper::Bootstrapper;
+use crate::cli::{Command, ReplicaOptions};
+use crate::cluster_status::{query_cluster, ReplicaStatus};
+use crate::file_reader::prepare_replay_file;
+use crate::input_source::{InputFormat, InputSpec};
 use crate::ok_timeout_handler::OkTimeoutHandler;
 use crate::pinger_finder::{Find, Ping, PingerFinder};
+use crate::replicator::{ListenReplication, Replicator};
+use crate::transaction_validator::validate_file;
 use std::env::args;
+use std::fs;
+use std::process::exit;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 use tokio::net::UdpSocket;
 
-// Salen de la implementacion de Bully vista en clase
-fn id_to_ctrladdr(id: usize) -> String {
-    "localhost:1234".to_owned() + &*id.to_string()
+fn main() {
+    let argv = args().skip(1).collect::<Vec<String>>();
+    let command = match cli::parse(&argv) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("ERROR: {}\n\n{}", e, cli::USAGE);
+            exit(2);
+        }
+    };
+    let result = match command {
+        Command::Run { options, input } => {
+            load_config(&options).and_then(|config| run(options, config, input))
+        }
+        Command::ReplayFailed { options } => replay_failed(options),
+        Command::Status { config } => status(config),
+        Command::Validate { transaction_file } => validate(&transaction_file),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("ERROR: {}", e);
+        exit(1);
+    }
 }
 
-fn id_to_dataaddr(id: usize) -> String {
-    "localhost:1235".to_owned() + &*id.to_string()
+fn load_config(options: &ReplicaOptions) -> Result<ClusterConfig, String> {
+    let mut config = ClusterConfig::load_or_default(options.config.as_deref())
+        .map_err(|e| format!("leyendo la configuracion: {}", e))?;
+    if let Some(output_dir) = &options.output_dir {
+        fs::create_dir_all(output_dir).map_err(|e| format!("creando {}: {}", output_dir, e))?;
+        config.files.in_directory(output_dir);
+    }
+    Ok(config)
 }
 
-const PROCESSES: u8 = 4;
+// las fallidas se copian aparte, porque el lider vuelve a crear el archivo de fallidas al arrancar
+fn replay_failed(options: ReplicaOptions) -> Result<(), String> {
+    let config = load_config(&options)?;
+    let failed_path = config.files.failed_transactions.clone();
+    let replay_path = format!("{}.replay", failed_path);
+    let rows = prepare_replay_file(&failed_path, &replay_path)
+        .map_err(|e| format!("leyendo {}: {}", failed_path, e))?;
+    if rows == 0 {
+        return Err(format!("No hay transacciones fallidas en {}", failed_path));
+    }
+    println!(
+        "Reintentando {} transacciones fallidas desde {}",
+        rows, replay_path
+    );
+    run(
+        options,
+        config,
+        InputSpec::new(replay_path, Some(InputFormat::Csv)),
+    )
+}
 
-fn main() -> Result<(), ()> {
-    let actor_system = System::new();
-    let argv = args().collect::<Vec<String>>();
-    if argv.len() != 3 {
-        panic!("ERROR: Parametros incorrectos. ./alglobo <pid> <transaction_file>");
+fn status(config_path: Option<String>) -> Result<(), String> {
+    let config = ClusterConfig::load_or_default(config_path.as_deref())
+        .map_err(|e| format!("leyendo la configuracion: {}", e))?;
+    let statuses = query_cluster(&config).map_err(|e| e.to_string())?;
+    let mut any_alive = false;
+    for (pid, status) in statuses {
+        let address = config.control_address(pid).unwrap_or_default();
+        match status {
+            Some(ReplicaStatus {
+                leader: Some(leader),
+                ..
+            }) => {
+                any_alive = true;
+                println!("replica {} ({}): activa, lider {}", pid, address, leader);
+            }
+            Some(ReplicaStatus { leader: None, .. }) => {
+                any_alive = true;
+                println!("replica {} ({}): activa, eligiendo lider", pid, address);
+            }
+            None => println!("replica {} ({}): sin respuesta", pid, address),
+        }
+    }
+    if !any_alive {
+        return Err("Ninguna replica respondio".to_string());
+    }
+    Ok(())
+}
+
+fn validate(transaction_file: &str) -> Result<(), String> {
+    let report =
+        validate_file(transaction_file).map_err(|e| format!("{}: {}", transaction_file, e))?;
+    for error in &report.errors {
+        println!("linea {}: {}", error.line, error.message);
+    }
+    println!(
+        "{}: {} filas, {} con errores",
+        transaction_file,
+        report.rows,
+        report.errors.len()
+    );
+    if !report.errors.is_empty() {
+        return Err(format!(
+            "{} tiene {} filas invalidas",
+            transaction_file,
+            report.errors.len()
+        ));
     }
+    Ok(())
+}
 
-    let pid = argv[1].as_str().parse::<u8>().unwrap();
+fn run(options: ReplicaOptions, config: ClusterConfig, input: InputSpec) -> Result<(), String> {
+    let actor_system = System::new();
+    let pid = options.pid;
+    let config = Arc::new(config);
+    let replica = match config.replica(pid) {
+        Some(replica) => replica.clone(),
+        None => return Err(format!("la replica {} no esta en la configuracion", pid)),
+    };
+    let log_path = config.files.replica_log(pid);
+    let log_level = options.log_level;
     //Inicializacion del Actor Logger
     let (sx_l, tx_l) = mpsc::channel();
     let logger_sender = Arc::new(Mutex::new(sx_l));
     let logger_arbiter = Arbiter::new();
     let logger_execution = async move {
-        let logger_addr =
-            LoggerActor::new(format!("log_alglobo_replica_pid_{}.log", pid).as_str()).start();
+        let logger_addr = LoggerActor::new(&log_path, log_level).start();
         let _r = logger_sender.lock().unwrap().send(logger_addr);
     };
     logger_arbiter.spawn(logger_execution);
@@ -70,35 +189,73 @@ fn main() -> Result<(), ()> {
 
     logger_addr.do_send(LogMessage::new("Logger inicializado".to_string()));
 
-    let all_pids = (0..PROCESSES).collect::<Vec<u8>>();
-    let filtered_pids = all_pids
-        .clone()
-        .into_iter()
-        .filter(|&other_pid| other_pid > pid)
-        .collect();
+    let all_pids = config.pids();
 
     actor_system.block_on(async {
-        sleep(Duration::from_secs((PROCESSES - pid) as u64));
+        let bind = |address: String| async move {
+            UdpSocket::bind(&address)
+                .await
+                .map(Arc::new)
+                .map_err(|e| format!("bindeando en {}: {}", address, e))
+        };
+        // las de pid mas alto arrancan antes
+        let higher_pids = all_pids
+            .iter()
+            .filter(|&&other_pid| other_pid >= pid)
+            .count();
+        sleep(Duration::from_secs(higher_pids as u64));
 
-        let data_socket = Arc::new(UdpSocket::bind(id_to_dataaddr(pid as usize)).await.unwrap());
-        let coordinator_socket =
-            Arc::new(UdpSocket::bind(id_to_ctrladdr(pid as usize)).await.unwrap());
+        let data_socket = bind(replica.data_address).await?;
+        let coordinator_socket = bind(replica.control_address).await?;
+        let replication_socket = bind(replica.replication_address).await?;
+        let replication_peers = config
+            .replicas
+            .iter()
+            .filter(|other| other.id != pid)
+            .map(|other| other.replication_address.clone())
+            .collect::<Vec<String>>();
 
         let data_clone = data_socket.clone();
         let coordinator_clone = coordinator_socket.clone();
 
-        let bootstrapper = Bootstrapper::new(argv[2].to_string()).start();
-        let timeout_handler = OkTimeoutHandler::new(pid, bootstrapper, logger_addr.clone()).start();
+        // mientras no sea lider, cada replica va guardando el estado que le manda el lider
+        let replicator = match Replicator::new(
+            replication_peers,
+            replication_socket,
+            &config.files.decision_log(pid),
+            &config.files.done_transactions(pid),
+            Duration::from_secs(config.pipeline.ledger_compaction_interval_s),
+            &config.files.checkpoint(pid),
+            logger_addr.clone(),
+        ) {
+            Ok(replicator) => replicator,
+            Err(e) => {
+                logger_addr.do_send(LogMessage::error(format!("ERROR: {}", e)));
+                return Err(e.to_string());
+            }
+        }
+        .start();
+        replicator.do_send(ListenReplication {});
+
+        let bootstrapper = Bootstrapper::new(input, pid, replicator, config.clone())
+            .with_commit_protocol(options.commit_protocol)
+            .start();
+        let timeout_handler = OkTimeoutHandler::new(
+            pid,
+            bootstrapper,
+            Duration::from_secs(config.timeouts.election_s),
+            logger_addr.clone(),
+        )
+        .start();
         let timeout_handler_clone = timeout_handler.clone();
 
         let pinger_finder_addr = PingerFinder::new(
             Some(pid),
             pid,
-            all_pids,
-            filtered_pids,
             data_socket,
             coordinator_socket,
             timeout_handler,
+            config.clone(),
         )
         .start();
         let pinger_clone = pinger_finder_addr.clone();
@@ -108,6 +265,7 @@ fn main() -> Result<(), ()> {
             coordinator_clone,
             timeout_handler_clone,
             pinger_finder_addr,
+            config.clone(),
         )
         .start();
         let beater_clone = beater_responder_addr.clone();
@@ -116,16 +274,13 @@ fn main() -> Result<(), ()> {
 
         actix_rt::signal::ctrl_c()
             .await
-            .expect("Could not catch signal");
+            .map_err(|e| format!("esperando ctrl-c: {}", e))?;
         System::current().stop();
-    });
+        Ok(())
+    })?;
 
-    match actor_system.run() {
-        Ok(_) => {}
-        Err(e) => {
-            logger_addr.do_send(LogMessage::new(format!("ERROR: {}", e)));
-            return Err(());
-        }
-    }
-    Ok(())
+    actor_system.run().map_err(|e| {
+        logger_addr.do_send(LogMessage::error(format!("ERROR: {}", e)));
+        e.to_string()
+    })
 }
@@ -13,17 +13,25 @@ pub struct OkTimeoutHandler {
     receiver: Option<oneshot::Receiver<u8>>,
     pid: u8,
     bootstrapper: Addr<Bootstrapper>,
+    // cuanto se espera un Ok antes de declararse lider
+    election_timeout: Duration,
     logger: Addr<LoggerActor>,
 }
 
 impl OkTimeoutHandler {
-    pub fn new(pid: u8, bootstrapper: Addr<Bootstrapper>, logger: Addr<LoggerActor>) -> Self {
+    pub fn new(
+        pid: u8,
+        bootstrapper: Addr<Bootstrapper>,
+        election_timeout: Duration,
+        logger: Addr<LoggerActor>,
+    ) -> Self {
         let (tx, rx) = oneshot::channel();
         OkTimeoutHandler {
             sender: Some(tx),
             receiver: Some(rx),
             pid,
             bootstrapper,
+            election_timeout,
             logger,
         }
     }
@@ -63,9 +71,10 @@ impl Handler<WaitTimeout> for OkTimeoutHandler {
         let pid = self.pid;
         let bootstrapper = self.bootstrapper.clone();
         let logger = self.logger.clone();
+        let election_timeout = self.election_timeout;
 
         let fut = async move {
-            match timeout(Duration::from_secs(10), rx).await {
+            match timeout(election_timeout, rx).await {
                 Ok(_) => {
                     logger.do_send(LogMessage::new(format!(
                         "[PID {}] Recibi Ok, no soy coordinador",
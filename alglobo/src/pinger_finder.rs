@@ -1,10 +1,10 @@
 use crate::beater_responder::BeaterResponder;
 use crate::ok_timeout_handler::{OkTimeoutHandler, WaitTimeout};
-use crate::{id_to_ctrladdr, id_to_dataaddr};
 use actix::{
     Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, ResponseActFuture,
     WrapFuture,
 };
+use alglobo_common_utils::cluster_config::ClusterConfig;
 use futures::future::join_all;
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,9 +13,6 @@ use tokio::time::{sleep, timeout};
 
 const PING: &[u8] = "PING".as_bytes();
 const PING_PONG_SIZE: usize = 4;
-// generous amount of timeout
-const TIMEOUT_S: u64 = 10;
-const PING_RATE_S: u64 = 2;
 
 // FSM entre Ping y Find
 // en ningun estado soy lider, pero puedo pasar a serlo luego de Find
@@ -27,18 +24,25 @@ pub struct PingerFinder {
     data_socket: Arc<UdpSocket>,
     coordinator_socket: Arc<UdpSocket>,
     ok_timeout_handler_addr: Addr<OkTimeoutHandler>,
+    config: Arc<ClusterConfig>,
 }
 
 impl PingerFinder {
     pub fn new(
         leader: Option<u8>,
         pid: u8,
-        all_pids: Vec<u8>,
-        filtered_pids: Vec<u8>,
         data_socket: Arc<UdpSocket>,
         coordinator_socket: Arc<UdpSocket>,
         ok_timeout_handler_addr: Addr<OkTimeoutHandler>,
+        config: Arc<ClusterConfig>,
     ) -> Self {
+        let all_pids = config.pids();
+        // en la eleccion solo se le pregunta a los de id mas alto
+        let filtered_pids = all_pids
+            .iter()
+            .filter(|&&other_pid| other_pid > pid)
+            .copied()
+            .collect();
         PingerFinder {
             leader,
             pid,
@@ -47,6 +51,7 @@ impl PingerFinder {
             data_socket,
             coordinator_socket,
             ok_timeout_handler_addr,
+            config,
         }
     }
 }
@@ -74,13 +79,17 @@ impl Handler<Ping> for PingerFinder {
     fn handle(&mut self, msg: Ping, _: &mut Self::Context) -> Self::Result {
         let sock = self.data_socket.clone();
         let my_pid = self.pid;
+        // generous amount of timeout
+        let ping_timeout = Duration::from_secs(self.config.timeouts.ping_s);
+        let ping_rate = Duration::from_secs(self.config.timeouts.ping_rate_s);
+        let leader_addr = self.config.data_address(msg.ping_id);
         let fut = async move {
-            sock.send_to(PING, id_to_dataaddr(msg.ping_id as usize))
-                .await
-                .expect("rip");
+            // un lider que no esta en la configuracion es como uno caido
+            let leader_addr = leader_addr.ok_or(())?;
+            sock.send_to(PING, leader_addr).await.expect("rip");
             let mut buf = [0; PING_PONG_SIZE];
             let recv_fut = sock.recv_from(&mut buf);
-            match timeout(Duration::from_secs(TIMEOUT_S), recv_fut).await {
+            match timeout(ping_timeout, recv_fut).await {
                 Ok(_) => {
                     // avoid ping ddos
                     println!(
@@ -88,7 +97,7 @@ impl Handler<Ping> for PingerFinder {
                         my_pid,
                         String::from_utf8_lossy(buf.as_slice())
                     );
-                    sleep(Duration::from_secs(PING_RATE_S)).await;
+                    sleep(ping_rate).await;
                     Ok(())
                 }
                 Err(_) => Err(()),
@@ -145,16 +154,19 @@ impl Handler<Find> for PingerFinder {
         let timeout_handler_addr = self.ok_timeout_handler_addr.clone();
         let sock = self.coordinator_socket.clone();
         let my_pid = self.pid;
-        let filtered_pids = self.filtered_pids.clone();
         let send_buffer = vec![b'E', my_pid];
         let responder = msg.responder;
         let all_pids = self.all_pids.clone();
+        let addresses = self
+            .filtered_pids
+            .iter()
+            .filter_map(|pid| self.config.control_address(*pid))
+            .collect::<Vec<String>>();
 
         let fut = async move {
             let mut send_futures = vec![];
-            for pid in &filtered_pids {
-                send_futures
-                    .push(sock.send_to(send_buffer.as_slice(), id_to_ctrladdr(*pid as usize)));
+            for addr in &addresses {
+                send_futures.push(sock.send_to(send_buffer.as_slice(), addr));
             }
             // mandar al ok timeout handler que empiece a escuchar
             // esto esta antes de tal manera de evitar race conditions
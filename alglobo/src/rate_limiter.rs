@@ -0,0 +1,149 @@
+use alglobo_common_utils::entity_type::EntityType;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Token bucket: se recargan `rate` tokens por segundo, hasta juntar un segundo de rafaga
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        TokenBucket {
+            rate,
+            tokens: TokenBucket::capacity(rate),
+            last_refill: now,
+        }
+    }
+
+    // con menos de un token por segundo igual tiene que entrar uno
+    fn capacity(rate: f64) -> f64 {
+        rate.max(1.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(TokenBucket::capacity(self.rate));
+        self.last_refill = now;
+    }
+
+    // cuanto falta para que haya un token
+    fn wait_time(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    // cambiar el ritmo no regala ni quita lo ya acumulado, salvo que no entre en la rafaga nueva
+    fn set_rate(&mut self, rate: f64, now: Instant) {
+        self.refill(now);
+        self.rate = rate;
+        self.tokens = self.tokens.min(TokenBucket::capacity(rate));
+    }
+}
+
+// Limite de prepares por segundo a cada entidad. Las que no tienen bucket no se limitan
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: HashMap<EntityType, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        RateLimiter {
+            buckets: HashMap::new(),
+        }
+    }
+
+    // None saca el limite
+    pub fn set_rate(&mut self, entity: EntityType, rate: Option<f64>, now: Instant) {
+        match rate {
+            Some(rate) => match self.buckets.get_mut(&entity) {
+                Some(bucket) => bucket.set_rate(rate, now),
+                None => {
+                    self.buckets.insert(entity, TokenBucket::new(rate, now));
+                }
+            },
+            None => {
+                self.buckets.remove(&entity);
+            }
+        }
+    }
+
+    // una transaccion lleva un prepare a cada participante: se toman todos los tokens o ninguno.
+    // Si no alcanzan devuelve cuanto esperar antes de volver a probar
+    pub fn try_acquire(&mut self, entities: &[EntityType], now: Instant) -> Result<(), Duration> {
+        let mut wait = Duration::ZERO;
+        for entity in entities {
+            if let Some(bucket) = self.buckets.get_mut(entity) {
+                bucket.refill(now);
+                wait = wait.max(bucket.wait_time());
+            }
+        }
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+        for entity in entities {
+            if let Some(bucket) = self.buckets.get_mut(entity) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limiter::RateLimiter;
+    use alglobo_common_utils::entity_type::EntityType;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_bucket_limits_each_entity_separately() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new();
+        limiter.set_rate(EntityType::Airline, Some(2.0), now);
+
+        // la rafaga inicial es de un segundo
+        assert!(limiter.try_acquire(&[EntityType::Airline], now).is_ok());
+        assert!(limiter.try_acquire(&[EntityType::Airline], now).is_ok());
+        let wait = limiter
+            .try_acquire(&[EntityType::Airline, EntityType::Bank], now)
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        // el banco no tiene limite
+        for _ in 0..100 {
+            assert!(limiter.try_acquire(&[EntityType::Bank], now).is_ok());
+        }
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.try_acquire(&[EntityType::Airline], later).is_ok());
+        assert!(limiter.try_acquire(&[EntityType::Airline], later).is_err());
+    }
+
+    #[test]
+    fn test_rate_can_change_at_runtime() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new();
+        limiter.set_rate(EntityType::Hotel, Some(1.0), now);
+        assert!(limiter.try_acquire(&[EntityType::Hotel], now).is_ok());
+        assert!(limiter.try_acquire(&[EntityType::Hotel], now).is_err());
+
+        let later = now + Duration::from_millis(100);
+        limiter.set_rate(EntityType::Hotel, Some(10.0), later);
+        let wait = limiter
+            .try_acquire(&[EntityType::Hotel], later)
+            .unwrap_err();
+        assert!(wait > Duration::from_millis(89) && wait <= Duration::from_millis(90));
+
+        limiter.set_rate(EntityType::Hotel, None, later);
+        assert!(limiter.try_acquire(&[EntityType::Hotel], later).is_ok());
+    }
+}
@@ -0,0 +1,864 @@
+use crate::checkpoint::Checkpoint;
+use crate::decision_log::{DecisionLog, PendingDecision};
+use crate::done_ledger::{DoneLedger, LedgerEntry};
+use crate::logger::LoggerActor;
+use crate::LogMessage;
+use actix::{
+    Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, ResponseActFuture,
+    WrapFuture,
+};
+use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::reliable_delivery::{DuplicateFilter, RetransmissionQueue};
+use alglobo_common_utils::transaction_state::TransactionState;
+use csv::StringRecord;
+use futures::future::join_all;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+const PREPARED: &str = "prepared";
+const COMMIT: &str = "commit";
+const ABORT: &str = "abort";
+const PRECOMMIT: &str = "precommit";
+const ENDED: &str = "ended";
+const DONE: &str = "done";
+const CHECKPOINT: &str = "checkpoint";
+// checkpoint,<byte>,<linea>,<fila>,<huella>,<archivo>: el archivo va ultimo porque puede tener comas
+const CHECKPOINT_FIELDS: usize = 6;
+// done,<id>,<estado>,<timestamp>,<pid>,<monto>,<moneda>; las replicas de antes de la moneda
+// mandan solo hasta el pid
+const DONE_FIELDS: usize = 8;
+const DONE_WITHOUT_ATTEMPT_FIELDS: usize = 7;
+const DONE_WITHOUT_TOTAL_FIELDS: usize = 5;
+const FIELD_SEPARATOR: char = ',';
+const PARTICIPANT_SEPARATOR: char = ';';
+const REPLICATION_BUFFER_SIZE: usize = 512;
+const REPLICATE: &str = "replicate";
+const ACK: &str = "ack";
+const SYNC: &str = "sync";
+const SYNCED: &str = "synced";
+const RETRANSMIT_TICK_MS: u64 = 100;
+// con el backoff son casi dos minutos: una replica caida mas tiempo se pone al dia si queda
+// como lider
+const REPLICATION_MAX_ATTEMPTS: u32 = 20;
+// cuantas entradas recordamos para confirmar los reenvios sin volver a aplicarlas
+const RECEIVED_CAPACITY: usize = 4096;
+// cada cuanto un lider nuevo vuelve a pedir el log a las replicas que no contestaron todo,
+// y hasta cuando
+const SYNC_RETRY: Duration = Duration::from_millis(500);
+const SYNC_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Lo que el lider le manda a las replicas: las mismas entradas de su log de decisiones,
+// los ids que ya terminaron y hasta donde se resolvio el archivo de transacciones
+#[derive(Debug, Clone)]
+pub enum ReplicationEntry {
+    Prepared(u64, u32, HashSet<EntityType>),
+    Decided(u64, u32, TransactionState, HashSet<EntityType>),
+    Ended(u64, u32),
+    Done(LedgerEntry),
+    Checkpoint(Checkpoint),
+}
+
+impl From<ReplicationEntry> for Vec<u8> {
+    fn from(entry: ReplicationEntry) -> Self {
+        let join = |participants: HashSet<EntityType>| {
+            participants
+                .iter()
+                .map(|entity| entity.to_string())
+                .collect::<Vec<String>>()
+                .join(&PARTICIPANT_SEPARATOR.to_string())
+        };
+        let line = match entry {
+            ReplicationEntry::Prepared(id, attempt, participants) => {
+                format!("{},{},{},{}", PREPARED, id, join(participants), attempt)
+            }
+            ReplicationEntry::Decided(id, attempt, TransactionState::Commit, participants) => {
+                format!("{},{},{},{}", COMMIT, id, join(participants), attempt)
+            }
+            ReplicationEntry::Decided(id, attempt, TransactionState::PreCommit, participants) => {
+                format!("{},{},{},{}", PRECOMMIT, id, join(participants), attempt)
+            }
+            ReplicationEntry::Decided(id, attempt, _, participants) => {
+                format!("{},{},{},{}", ABORT, id, join(participants), attempt)
+            }
+            ReplicationEntry::Ended(id, attempt) => format!("{},{},,{}", ENDED, id, attempt),
+            ReplicationEntry::Done(entry) => format!("{},{}", DONE, entry.record().join(",")),
+            ReplicationEntry::Checkpoint(checkpoint) => format!(
+                "{},{},{},{},{},{}",
+                CHECKPOINT,
+                checkpoint.byte,
+                checkpoint.line,
+                checkpoint.record,
+                checkpoint.fingerprint,
+                checkpoint.input
+            ),
+        };
+        line.into_bytes()
+    }
+}
+
+impl TryFrom<&[u8]> for ReplicationEntry {
+    type Error = String;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        let line = String::from_utf8_lossy(buffer);
+        if line.starts_with(CHECKPOINT) {
+            return parse_checkpoint(&line);
+        }
+        if line.starts_with(DONE) {
+            return parse_done(&line);
+        }
+        let fields = line.split(FIELD_SEPARATOR).collect::<Vec<&str>>();
+        if fields.len() != 4 {
+            return Err(format!("Malformed replication entry: {}", line));
+        }
+        let id = u64::from_str(fields[1]).map_err(|e| e.to_string())?;
+        let attempt = u32::from_str(fields[3]).map_err(|e| e.to_string())?;
+        let participants = fields[2]
+            .split(PARTICIPANT_SEPARATOR)
+            .filter_map(|entity| EntityType::from_str(entity).ok())
+            .collect::<HashSet<EntityType>>();
+        match fields[0] {
+            PREPARED => Ok(ReplicationEntry::Prepared(id, attempt, participants)),
+            COMMIT => Ok(ReplicationEntry::Decided(
+                id,
+                attempt,
+                TransactionState::Commit,
+                participants,
+            )),
+            PRECOMMIT => Ok(ReplicationEntry::Decided(
+                id,
+                attempt,
+                TransactionState::PreCommit,
+                participants,
+            )),
+            ABORT => Ok(ReplicationEntry::Decided(
+                id,
+                attempt,
+                TransactionState::Abort,
+                participants,
+            )),
+            ENDED => Ok(ReplicationEntry::Ended(id, attempt)),
+            kind => Err(format!("Unknown replication entry: {}", kind)),
+        }
+    }
+}
+
+fn parse_checkpoint(line: &str) -> Result<ReplicationEntry, String> {
+    let fields = line
+        .splitn(CHECKPOINT_FIELDS, FIELD_SEPARATOR)
+        .collect::<Vec<&str>>();
+    if fields.len() != CHECKPOINT_FIELDS || fields[0] != CHECKPOINT {
+        return Err(format!("Malformed replication entry: {}", line));
+    }
+    let number = |field: &str| u64::from_str(field).map_err(|e| e.to_string());
+    Ok(ReplicationEntry::Checkpoint(Checkpoint {
+        byte: number(fields[1])?,
+        line: number(fields[2])?,
+        record: number(fields[3])?,
+        fingerprint: number(fields[4])?,
+        input: fields[5].to_string(),
+    }))
+}
+
+fn parse_done(line: &str) -> Result<ReplicationEntry, String> {
+    let fields = line.split(FIELD_SEPARATOR).collect::<Vec<&str>>();
+    let malformed = || format!("Malformed replication entry: {}", line);
+    if ![
+        DONE_FIELDS,
+        DONE_WITHOUT_ATTEMPT_FIELDS,
+        DONE_WITHOUT_TOTAL_FIELDS,
+    ]
+    .contains(&fields.len())
+        || fields[0] != DONE
+    {
+        return Err(malformed());
+    }
+    LedgerEntry::parse(&StringRecord::from(fields[1..].to_vec()))
+        .map(ReplicationEntry::Done)
+        .ok_or_else(malformed)
+}
+
+// Lo que viaja entre replicas: las entradas van numeradas y se reenvian hasta que la replica
+// confirma esa secuencia. Un lider nuevo ademas le pide a las demas lo que tienen sin terminar,
+// que le llega como "synced,<indice>,<total>,<entrada>" (sin entradas, una sola con total 0)
+#[derive(Debug, Clone)]
+pub enum ReplicationMessage {
+    Entry(u64, ReplicationEntry),
+    Ack(u64),
+    Sync,
+    Synced(usize, usize, Option<ReplicationEntry>),
+}
+
+impl From<ReplicationMessage> for Vec<u8> {
+    fn from(message: ReplicationMessage) -> Self {
+        let line = |entry: ReplicationEntry| String::from_utf8_lossy(&Vec::from(entry)).to_string();
+        let message = match message {
+            ReplicationMessage::Entry(sequence, entry) => {
+                format!("{},{},{}", REPLICATE, sequence, line(entry))
+            }
+            ReplicationMessage::Ack(sequence) => format!("{},{}", ACK, sequence),
+            ReplicationMessage::Sync => SYNC.to_string(),
+            ReplicationMessage::Synced(index, total, entry) => format!(
+                "{},{},{},{}",
+                SYNCED,
+                index,
+                total,
+                entry.map(line).unwrap_or_default()
+            ),
+        };
+        message.into_bytes()
+    }
+}
+
+impl TryFrom<&[u8]> for ReplicationMessage {
+    type Error = String;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        let line = String::from_utf8_lossy(buffer);
+        let malformed = || format!("Malformed replication message: {}", line);
+        let number = |field: Option<&str>| {
+            field
+                .and_then(|field| u64::from_str(field).ok())
+                .ok_or_else(malformed)
+        };
+        // la entrada va ultima porque puede tener comas
+        let fields = line.splitn(4, FIELD_SEPARATOR).collect::<Vec<&str>>();
+        match fields[0] {
+            REPLICATE => {
+                let fields = line.splitn(3, FIELD_SEPARATOR).collect::<Vec<&str>>();
+                let entry = fields.get(2).ok_or_else(malformed)?;
+                Ok(ReplicationMessage::Entry(
+                    number(fields.get(1).copied())?,
+                    ReplicationEntry::try_from(entry.as_bytes())?,
+                ))
+            }
+            ACK => Ok(ReplicationMessage::Ack(number(fields.get(1).copied())?)),
+            SYNC if fields.len() == 1 => Ok(ReplicationMessage::Sync),
+            SYNCED => {
+                let entry = match fields.get(3) {
+                    Some(&"") => None,
+                    Some(entry) => Some(ReplicationEntry::try_from(entry.as_bytes())?),
+                    None => return Err(malformed()),
+                };
+                Ok(ReplicationMessage::Synced(
+                    number(fields.get(1).copied())? as usize,
+                    number(fields.get(2).copied())? as usize,
+                    entry,
+                ))
+            }
+            _ => Err(malformed()),
+        }
+    }
+}
+
+// lo que una replica tiene sin terminar viaja como la entrada que lo dejo asi
+impl From<PendingDecision> for ReplicationEntry {
+    fn from(decision: PendingDecision) -> Self {
+        match decision.transaction_state {
+            TransactionState::Wait => ReplicationEntry::Prepared(
+                decision.transaction_id,
+                decision.attempt,
+                decision.participants,
+            ),
+            state => ReplicationEntry::Decided(
+                decision.transaction_id,
+                decision.attempt,
+                state,
+                decision.participants,
+            ),
+        }
+    }
+}
+
+fn pending_decision(entry: ReplicationEntry) -> Option<PendingDecision> {
+    let (transaction_id, attempt, transaction_state, participants) = match entry {
+        ReplicationEntry::Prepared(id, attempt, participants) => {
+            (id, attempt, TransactionState::Wait, participants)
+        }
+        ReplicationEntry::Decided(id, attempt, state, participants) => {
+            (id, attempt, state, participants)
+        }
+        _ => return None,
+    };
+    Some(PendingDecision {
+        transaction_id,
+        attempt,
+        transaction_state,
+        participants,
+    })
+}
+
+async fn send_all(
+    socket: Arc<UdpSocket>,
+    messages: Vec<(String, Vec<u8>)>,
+    logger: Addr<LoggerActor>,
+) {
+    let sends = messages
+        .iter()
+        .map(|(addr, buffer)| socket.send_to(buffer.as_slice(), addr));
+    for result in join_all(sends).await {
+        // si falla no pasa nada, lo reenvia la cola de retransmision
+        if let Err(e) = result {
+            logger.do_send(LogMessage::error(format!(
+                "[REPLICATOR] ERROR sending: {}",
+                e
+            )));
+        }
+    }
+}
+
+// Lo que van contestando las replicas cuando el lider nuevo pide su log
+struct SyncState {
+    responses: HashMap<SocketAddr, (usize, HashMap<usize, ReplicationEntry>)>,
+    done: Option<oneshot::Sender<()>>,
+}
+
+impl SyncState {
+    fn complete(&self) -> usize {
+        self.responses
+            .values()
+            .filter(|(total, entries)| entries.len() == *total)
+            .count()
+    }
+
+    // lo que llego, aunque alguna replica no haya contestado todo
+    fn decisions(self) -> Vec<PendingDecision> {
+        self.responses
+            .into_values()
+            .flat_map(|(_, entries)| entries.into_values())
+            .filter_map(pending_decision)
+            .collect()
+    }
+}
+
+// Del lado del lider reenvia cada cambio de estado del coordinador a las demas replicas.
+// Del lado de las replicas escucha esos cambios y los escribe en sus propios archivos,
+// asi si quedan como lider arrancan con el mismo estado que tenia el anterior
+pub struct Replicator {
+    // direcciones de replicacion de las demas replicas
+    peers: Vec<String>,
+    socket: Arc<UdpSocket>,
+    decision_log: DecisionLog,
+    done_ledger: DoneLedger,
+    ledger_compaction_interval: Duration,
+    checkpoint_path: String,
+    retransmission_queue: RetransmissionQueue,
+    received: DuplicateFilter<SocketAddr, ()>,
+    sync: Option<SyncState>,
+    logger: Addr<LoggerActor>,
+}
+
+impl Replicator {
+    pub fn new(
+        peers: Vec<String>,
+        socket: Arc<UdpSocket>,
+        decision_log_path: &str,
+        done_transactions_path: &str,
+        ledger_compaction_interval: Duration,
+        checkpoint_path: &str,
+        logger: Addr<LoggerActor>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        logger.do_send(LogMessage::new("Creating Replicator...".to_string()));
+        Ok(Replicator {
+            peers,
+            socket,
+            decision_log: DecisionLog::new(decision_log_path)?,
+            done_ledger: DoneLedger::open(done_transactions_path)?,
+            ledger_compaction_interval,
+            checkpoint_path: checkpoint_path.to_string(),
+            retransmission_queue: RetransmissionQueue::new(),
+            received: DuplicateFilter::new(RECEIVED_CAPACITY),
+            sync: None,
+            logger,
+        })
+    }
+
+    fn request_sync(&mut self, ctx: &mut Context<Self>) {
+        let messages = self
+            .peers
+            .iter()
+            .map(|peer| (peer.clone(), Vec::from(ReplicationMessage::Sync)))
+            .collect();
+        ctx.spawn(send_all(self.socket.clone(), messages, self.logger.clone()).into_actor(self));
+    }
+
+    fn receive(&mut self, message: ReplicationMessage, from: SocketAddr, ctx: &mut Context<Self>) {
+        let mut replies = vec![];
+        match message {
+            ReplicationMessage::Entry(sequence, entry) => {
+                // un reenvio de algo ya aplicado solo se vuelve a confirmar
+                if self.received.get(&from, sequence).is_none() {
+                    if let Err(e) = self.apply(entry) {
+                        // sin el ack el lider la vuelve a mandar
+                        self.logger
+                            .do_send(LogMessage::error(format!("[REPLICATOR] ERROR: {}", e)));
+                        return;
+                    }
+                    self.received.insert(from, sequence, ());
+                }
+                replies.push(ReplicationMessage::Ack(sequence));
+            }
+            ReplicationMessage::Ack(sequence) => {
+                self.retransmission_queue.acknowledge(sequence);
+            }
+            ReplicationMessage::Sync => {
+                // si esta replica fue lider, el log lo fue escribiendo su coordinador
+                if let Err(e) = self.decision_log.reload() {
+                    self.logger.do_send(LogMessage::error(format!(
+                        "[REPLICATOR] ERROR reading the decision log: {}",
+                        e
+                    )));
+                }
+                let pending = self.decision_log.pending();
+                let total = pending.len();
+                if total == 0 {
+                    replies.push(ReplicationMessage::Synced(0, 0, None));
+                }
+                for (index, decision) in pending.into_iter().enumerate() {
+                    replies.push(ReplicationMessage::Synced(
+                        index,
+                        total,
+                        Some(decision.into()),
+                    ));
+                }
+            }
+            ReplicationMessage::Synced(index, total, entry) => {
+                let peers = self.peers.len();
+                if let Some(sync) = &mut self.sync {
+                    let (known_total, entries) = sync
+                        .responses
+                        .entry(from)
+                        .or_insert_with(|| (total, HashMap::new()));
+                    // si cambio lo que tiene la replica empezamos de nuevo con ella
+                    if *known_total != total {
+                        *known_total = total;
+                        entries.clear();
+                    }
+                    if let Some(entry) = entry {
+                        entries.insert(index, entry);
+                    }
+                    if sync.complete() >= peers {
+                        if let Some(done) = sync.done.take() {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            }
+        }
+        let replies = replies
+            .into_iter()
+            .map(|reply| (from.to_string(), Vec::from(reply)))
+            .collect::<Vec<(String, Vec<u8>)>>();
+        if !replies.is_empty() {
+            ctx.spawn(send_all(self.socket.clone(), replies, self.logger.clone()).into_actor(self));
+        }
+    }
+
+    // Las entradas se reenvian hasta que llega el ack, asi que pueden llegar desordenadas: lo que
+    // ya quedo atras de lo que hay aca (un prepared despues de la decision, una decision despues
+    // del ended, un checkpoint anterior al guardado) no se aplica
+    fn apply(&mut self, entry: ReplicationEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let advances = match &entry {
+            ReplicationEntry::Prepared(id, attempt, _) => {
+                self.decision_log
+                    .advances(*id, *attempt, Some(TransactionState::Wait))
+            }
+            ReplicationEntry::Decided(id, attempt, state, _) => {
+                self.decision_log.advances(*id, *attempt, Some(*state))
+            }
+            ReplicationEntry::Ended(id, attempt) => self.decision_log.advances(*id, *attempt, None),
+            ReplicationEntry::Done(_) => true,
+            ReplicationEntry::Checkpoint(checkpoint) => {
+                match Checkpoint::load(&self.checkpoint_path)? {
+                    Some(saved) => checkpoint.supersedes(&saved),
+                    None => true,
+                }
+            }
+        };
+        if !advances {
+            self.logger.do_send(LogMessage::debug(format!(
+                "[REPLICATOR] ignoring outdated entry {:?}",
+                entry
+            )));
+            return Ok(());
+        }
+        match entry {
+            ReplicationEntry::Prepared(id, attempt, participants) => {
+                self.decision_log.prepared(id, attempt, &participants)?
+            }
+            ReplicationEntry::Decided(id, attempt, state, participants) => self
+                .decision_log
+                .decided(id, attempt, state, &participants)?,
+            ReplicationEntry::Ended(id, attempt) => self.decision_log.ended(id, attempt)?,
+            ReplicationEntry::Done(entry) => self.done_ledger.record(&entry)?,
+            ReplicationEntry::Checkpoint(checkpoint) => checkpoint.save(&self.checkpoint_path)?,
+        }
+        Ok(())
+    }
+}
+
+impl Actor for Replicator {
+    type Context = Context<Self>;
+
+    // el lider compacta su registro desde el FileWriter y su log desde el coordinador, las
+    // replicas desde aca
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_millis(RETRANSMIT_TICK_MS), |me, ctx| {
+            let due = me.retransmission_queue.due(Instant::now());
+            if !due.is_empty() {
+                ctx.spawn(send_all(me.socket.clone(), due, me.logger.clone()).into_actor(me));
+            }
+            for message in me.retransmission_queue.take_exhausted() {
+                me.logger.do_send(LogMessage::error(format!(
+                    "[REPLICATOR] {} did not acknowledge a replication entry, it will catch up if it becomes leader",
+                    message.address
+                )));
+            }
+        });
+        ctx.run_interval(self.ledger_compaction_interval, |me, _| {
+            if let Err(e) = me.done_ledger.compact() {
+                me.logger.do_send(LogMessage::error(format!(
+                    "[REPLICATOR] ERROR compacting done transactions: {}",
+                    e
+                )));
+            }
+            if let Err(e) = me.decision_log.compact() {
+                me.logger.do_send(LogMessage::error(format!(
+                    "[REPLICATOR] ERROR compacting the decision log: {}",
+                    e
+                )));
+            }
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Replicate {
+    entry: ReplicationEntry,
+}
+
+impl Replicate {
+    pub fn new(entry: ReplicationEntry) -> Self {
+        Replicate { entry }
+    }
+}
+
+impl Handler<Replicate> for Replicator {
+    type Result = ResponseActFuture<Self, ()>;
+
+    fn handle(&mut self, msg: Replicate, _: &mut Self::Context) -> Self::Result {
+        // cada replica confirma su copia, asi que cada una lleva su propia secuencia
+        let peers = self.peers.clone();
+        let messages = peers
+            .into_iter()
+            .map(|peer| {
+                let sequence = self.retransmission_queue.next_sequence();
+                let buffer: Vec<u8> = ReplicationMessage::Entry(sequence, msg.entry.clone()).into();
+                // la cola agrupa por transaccion e intento, pero aca no se usa
+                self.retransmission_queue.track(
+                    sequence,
+                    (0, 0),
+                    peer.clone(),
+                    buffer.clone(),
+                    Some(REPLICATION_MAX_ATTEMPTS),
+                    Instant::now(),
+                );
+                (peer, buffer)
+            })
+            .collect::<Vec<(String, Vec<u8>)>>();
+        let fut = send_all(self.socket.clone(), messages, self.logger.clone());
+        Box::pin(fut.into_actor(self))
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct SyncDecisionLog {}
+
+// Lo manda el lider nuevo antes de recuperar las decisiones pendientes: pide a las demas replicas
+// lo que tienen sin terminar, por si a esta no le llego alguna entrada, y lo agrega a su log.
+// Devuelve cuantas transacciones agrego
+impl Handler<SyncDecisionLog> for Replicator {
+    type Result = ResponseActFuture<Self, usize>;
+
+    fn handle(&mut self, _: SyncDecisionLog, ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        let done = match self.peers.is_empty() {
+            true => {
+                let _ = tx.send(());
+                None
+            }
+            false => Some(tx),
+        };
+        self.sync = Some(SyncState {
+            responses: HashMap::new(),
+            done,
+        });
+        self.request_sync(ctx);
+        // las que no contestaron todo se lo volvemos a pedir
+        let retry = ctx.run_interval(SYNC_RETRY, |me, ctx| me.request_sync(ctx));
+        let fut = async move {
+            let _ = timeout(SYNC_TIMEOUT, rx).await;
+        };
+        Box::pin(fut.into_actor(self).map(move |_, me, ctx| {
+            ctx.cancel_future(retry);
+            let decisions = me.sync.take().map(SyncState::decisions).unwrap_or_default();
+            let merged = match me.decision_log.merge(decisions) {
+                Ok(merged) => merged,
+                Err(e) => {
+                    me.logger.do_send(LogMessage::error(format!(
+                        "[REPLICATOR] ERROR merging the decision log of the other replicas: {}",
+                        e
+                    )));
+                    0
+                }
+            };
+            // se compacta ya: de aca en adelante el log lo escribe el coordinador, que lo vuelve
+            // a leer al recuperar las pendientes
+            if let Err(e) = me.decision_log.compact() {
+                me.logger.do_send(LogMessage::error(format!(
+                    "[REPLICATOR] ERROR compacting the decision log: {}",
+                    e
+                )));
+            }
+            merged
+        }))
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ListenReplication {}
+
+impl Handler<ListenReplication> for Replicator {
+    type Result = ResponseActFuture<Self, ()>;
+
+    fn handle(&mut self, msg: ListenReplication, _: &mut Self::Context) -> Self::Result {
+        let socket = self.socket.clone();
+
+        let fut = async move {
+            let mut buf = [0u8; REPLICATION_BUFFER_SIZE];
+            match socket.recv_from(&mut buf).await {
+                Ok((size, from)) => {
+                    ReplicationMessage::try_from(&buf[..size]).map(|message| (message, from))
+                }
+                Err(e) => Err(e.to_string()),
+            }
+        };
+        Box::pin(fut.into_actor(self).map(|res, me, ctx| {
+            match res {
+                Ok((message, from)) => me.receive(message, from, ctx),
+                Err(e) => me
+                    .logger
+                    .do_send(LogMessage::error(format!("[REPLICATOR] ERROR: {}", e))),
+            }
+            ctx.address().do_send(msg)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::Checkpoint;
+    use crate::decision_log::DecisionLog;
+    use crate::done_ledger::{FinalState, LedgerEntry};
+    use crate::logger::{LogLevel, LoggerActor};
+    use crate::replicator::{ReplicationEntry, ReplicationMessage, Replicator};
+    use actix::Actor;
+    use alglobo_common_utils::entity_type::EntityType;
+    use alglobo_common_utils::money::{Money, DEFAULT_CURRENCY};
+    use alglobo_common_utils::transaction_state::TransactionState;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    #[test]
+    fn test_replication_message_roundtrip() {
+        let checkpoint = Checkpoint {
+            input: "dir,with,commas/transactions.csv".to_string(),
+            fingerprint: 1,
+            byte: 2,
+            line: 3,
+            record: 4,
+        };
+        let buffer: Vec<u8> =
+            ReplicationMessage::Entry(12, ReplicationEntry::Checkpoint(checkpoint.clone())).into();
+        match ReplicationMessage::try_from(buffer.as_slice()) {
+            Ok(ReplicationMessage::Entry(12, ReplicationEntry::Checkpoint(c))) => {
+                assert_eq!(c, checkpoint)
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        let buffer: Vec<u8> = ReplicationMessage::Ack(12).into();
+        assert!(matches!(
+            ReplicationMessage::try_from(buffer.as_slice()),
+            Ok(ReplicationMessage::Ack(12))
+        ));
+        let buffer: Vec<u8> = ReplicationMessage::Sync.into();
+        assert!(matches!(
+            ReplicationMessage::try_from(buffer.as_slice()),
+            Ok(ReplicationMessage::Sync)
+        ));
+        let participants = HashSet::from([EntityType::Bank]);
+        let buffer: Vec<u8> =
+            ReplicationMessage::Synced(1, 2, Some(ReplicationEntry::Prepared(5, 1, participants)))
+                .into();
+        assert!(matches!(
+            ReplicationMessage::try_from(buffer.as_slice()),
+            Ok(ReplicationMessage::Synced(
+                1,
+                2,
+                Some(ReplicationEntry::Prepared(5, 1, _))
+            ))
+        ));
+        let buffer: Vec<u8> = ReplicationMessage::Synced(0, 0, None).into();
+        assert!(matches!(
+            ReplicationMessage::try_from(buffer.as_slice()),
+            Ok(ReplicationMessage::Synced(0, 0, None))
+        ));
+
+        // una entrada sin numerar, como las mandaban las versiones anteriores
+        assert!(ReplicationMessage::try_from("ended,5,,0".as_bytes()).is_err());
+        let buffer: Vec<u8> = ReplicationMessage::Entry(3, ReplicationEntry::Ended(5, 2)).into();
+        assert!(matches!(
+            ReplicationMessage::try_from(buffer.as_slice()),
+            Ok(ReplicationMessage::Entry(3, ReplicationEntry::Ended(5, 2)))
+        ));
+    }
+
+    #[test]
+    fn test_replication_entry_roundtrip() {
+        let participants = HashSet::from([EntityType::Hotel, EntityType::Airline]);
+        let buffer: Vec<u8> =
+            ReplicationEntry::Decided(7, 2, TransactionState::Commit, participants.clone()).into();
+
+        match ReplicationEntry::try_from(buffer.as_slice()) {
+            Ok(ReplicationEntry::Decided(7, 2, TransactionState::Commit, p)) => {
+                assert_eq!(p, participants)
+            }
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        let buffer: Vec<u8> =
+            ReplicationEntry::Decided(8, 0, TransactionState::PreCommit, participants.clone())
+                .into();
+        assert!(matches!(
+            ReplicationEntry::try_from(buffer.as_slice()),
+            Ok(ReplicationEntry::Decided(
+                8,
+                0,
+                TransactionState::PreCommit,
+                _
+            ))
+        ));
+
+        let checkpoint = Checkpoint {
+            input: "dir,with,commas/transactions.csv".to_string(),
+            fingerprint: 99,
+            byte: 1024,
+            line: 30,
+            record: 29,
+        };
+        let buffer: Vec<u8> = ReplicationEntry::Checkpoint(checkpoint.clone()).into();
+        match ReplicationEntry::try_from(buffer.as_slice()) {
+            Ok(ReplicationEntry::Checkpoint(c)) => assert_eq!(c, checkpoint),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+
+        let done = LedgerEntry::new(u64::MAX, FinalState::Abort, 3).with_attempt(2);
+        let buffer: Vec<u8> = ReplicationEntry::Done(done.clone()).into();
+        match ReplicationEntry::try_from(buffer.as_slice()) {
+            Ok(ReplicationEntry::Done(entry)) => assert_eq!(entry, done),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        let done = LedgerEntry::new(1, FinalState::Commit, 0)
+            .with_total(Some(Money::new(1500, DEFAULT_CURRENCY)));
+        let buffer: Vec<u8> = ReplicationEntry::Done(done.clone()).into();
+        match ReplicationEntry::try_from(buffer.as_slice()) {
+            Ok(ReplicationEntry::Done(entry)) => assert_eq!(entry, done),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        // una replica de antes de la moneda
+        match ReplicationEntry::try_from("done,4,commit,10,1".as_bytes()) {
+            Ok(ReplicationEntry::Done(entry)) => assert_eq!(entry.total, None),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        // una replica de antes del intento
+        match ReplicationEntry::try_from("done,4,abort,10,1,60,ARS".as_bytes()) {
+            Ok(ReplicationEntry::Done(entry)) => assert_eq!(entry.attempt, 0),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        assert!(ReplicationEntry::try_from("garbage".as_bytes()).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_late_entries_do_not_go_back() {
+        let decision_log = "test_replicator_late_decisions.csv";
+        let done = "test_replicator_late_done.csv";
+        let checkpoint_path = "test_replicator_late_checkpoint.csv";
+        let log = "test_replicator_late.log";
+        for path in [decision_log, done, checkpoint_path] {
+            let _ = std::fs::remove_file(path);
+        }
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let mut replicator = Replicator::new(
+            vec![],
+            socket,
+            decision_log,
+            done,
+            Duration::from_secs(60),
+            checkpoint_path,
+            LoggerActor::new(log, LogLevel::Error).start(),
+        )
+        .unwrap();
+        let participants = HashSet::from([EntityType::Bank]);
+        let checkpoint = |record| Checkpoint {
+            input: "transactions.csv".to_string(),
+            fingerprint: 1,
+            byte: record * 10,
+            line: record + 1,
+            record,
+        };
+        // el prepared se reenvio y llego despues de la decision, y la decision despues del ended
+        let entries = vec![
+            ReplicationEntry::Decided(1, 0, TransactionState::Commit, participants.clone()),
+            ReplicationEntry::Prepared(1, 0, participants.clone()),
+            ReplicationEntry::Prepared(2, 0, participants.clone()),
+            ReplicationEntry::Ended(2, 0),
+            ReplicationEntry::Decided(2, 0, TransactionState::Abort, participants.clone()),
+            // un intento nuevo si avanza
+            ReplicationEntry::Prepared(2, 1, participants.clone()),
+            ReplicationEntry::Ended(2, 0),
+            ReplicationEntry::Checkpoint(checkpoint(7)),
+            ReplicationEntry::Checkpoint(checkpoint(3)),
+        ];
+        for entry in entries {
+            replicator.apply(entry).unwrap();
+        }
+        let pending = DecisionLog::new(decision_log).unwrap().pending();
+        let saved = Checkpoint::load(checkpoint_path).unwrap();
+        for path in [decision_log, done, checkpoint_path, log] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let states = pending
+            .iter()
+            .map(|decision| {
+                format!(
+                    "{}:{}:{:?}",
+                    decision.transaction_id, decision.attempt, decision.transaction_state
+                )
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(states, vec!["1:0:Commit", "2:1:Wait"]);
+        assert_eq!(saved, Some(checkpoint(7)));
+    }
+}
@@ -0,0 +1,60 @@
+use alglobo_common_utils::cluster_config::RetryConfig;
+use std::time::Duration;
+
+// Decide si una transaccion abortada se vuelve a intentar y cuanto esperar antes
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &RetryConfig) -> Self {
+        RetryPolicy {
+            max_attempts: config.max_attempts,
+            initial_backoff: Duration::from_secs(config.initial_backoff_s),
+            max_backoff: Duration::from_secs(config.max_backoff_s),
+        }
+    }
+
+    // los intentos se cuentan desde 0, el original
+    pub fn should_retry(&self, failed_attempt: u32) -> bool {
+        failed_attempt + 1 < self.max_attempts
+    }
+
+    // espera antes de mandar el intento siguiente al que fallo
+    pub fn backoff(&self, failed_attempt: u32) -> Duration {
+        let factor = 2u32.checked_pow(failed_attempt).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retry_policy::RetryPolicy;
+    use alglobo_common_utils::cluster_config::RetryConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff_doubles_until_the_cap() {
+        let policy = RetryPolicy::new(&RetryConfig {
+            enabled: true,
+            max_attempts: 3,
+            initial_backoff_s: 5,
+            max_backoff_s: 30,
+        });
+        assert_eq!(policy.backoff(0), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(30));
+        assert_eq!(policy.backoff(64), Duration::from_secs(30));
+
+        assert!(policy.should_retry(0));
+        assert!(policy.should_retry(1));
+        assert!(!policy.should_retry(2));
+    }
+}
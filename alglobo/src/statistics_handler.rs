@@ -2,6 +2,7 @@ use std::collections::HashSet;
 
 use std::time::Duration;
 
+use actix::dev::MessageResponse;
 use actix::{
     Actor, ActorFutureExt, AsyncContext, Context, Handler, Message, ResponseActFuture, WrapFuture,
 };
@@ -13,6 +14,11 @@ pub struct StatisticsHandler {
     total_transactions: u64,
     current_finished_transactions: u64,
     elapsed_time: Duration,
+    // filas con un id que ya habia aparecido
+    duplicates_ignored: u64,
+    duplicates_rejected: u64,
+    // ya las habia terminado un lider anterior
+    already_done: u64,
 }
 
 impl StatisticsHandler {
@@ -22,6 +28,9 @@ impl StatisticsHandler {
             total_transactions: 0,
             current_finished_transactions: 0,
             elapsed_time: Duration::from_secs(0),
+            duplicates_ignored: 0,
+            duplicates_rejected: 0,
+            already_done: 0,
         }
     }
 }
@@ -54,8 +63,7 @@ impl Handler<RegisterTransaction> for StatisticsHandler {
         // si ya estaba y la estamos tratando de registrar de nuevo es un bug
         if self
             .transaction_id_timestamp_set
-            .get(&msg.transaction_id)
-            .is_some()
+            .contains(&msg.transaction_id)
         {
             return;
         }
@@ -86,10 +94,9 @@ impl Handler<UnregisterTransaction> for StatisticsHandler {
 
     fn handle(&mut self, msg: UnregisterTransaction, _: &mut Self::Context) -> Self::Result {
         // si tratamos de desregistrar una transaccion y no existe es un bug
-        if self
+        if !self
             .transaction_id_timestamp_set
-            .get(&msg.transaction_id)
-            .is_none()
+            .contains(&msg.transaction_id)
         {
             return;
         }
@@ -102,6 +109,61 @@ impl Handler<UnregisterTransaction> for StatisticsHandler {
     }
 }
 
+// que paso con una fila cuyo id ya se conocia
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    Ignored,
+    Rejected,
+    AlreadyDone,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterCollision {
+    collision: Collision,
+}
+
+impl RegisterCollision {
+    pub fn new(collision: Collision) -> Self {
+        RegisterCollision { collision }
+    }
+}
+
+impl Handler<RegisterCollision> for StatisticsHandler {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterCollision, _: &mut Self::Context) -> Self::Result {
+        match msg.collision {
+            Collision::Ignored => self.duplicates_ignored += 1,
+            Collision::Rejected => self.duplicates_rejected += 1,
+            Collision::AlreadyDone => self.already_done += 1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, MessageResponse)]
+pub struct CollisionCounts {
+    pub ignored: u64,
+    pub rejected: u64,
+    pub already_done: u64,
+}
+
+#[derive(Message)]
+#[rtype(result = "CollisionCounts")]
+pub struct GetCollisions {}
+
+impl Handler<GetCollisions> for StatisticsHandler {
+    type Result = CollisionCounts;
+
+    fn handle(&mut self, _: GetCollisions, _: &mut Self::Context) -> Self::Result {
+        CollisionCounts {
+            ignored: self.duplicates_ignored,
+            rejected: self.duplicates_rejected,
+            already_done: self.already_done,
+        }
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "f64")]
 pub struct GetMeanDuration {}
@@ -140,7 +202,7 @@ impl Handler<LogPeriodically> for StatisticsHandler {
                         me.elapsed_time.as_secs() as f64 / me.current_finished_transactions as f64
                     };
                     let tps = me.current_finished_transactions as f64 / me.elapsed_time.as_secs() as f64;
-                    println!("[STATS]\n\t- Total transactions: {}\n\t- Finished Transactions: {}\n\t- Mean time {}s\n\t- Finished transactions per second: {}\n", me.total_transactions, me.current_finished_transactions, mean_time, tps);
+                    println!("[STATS]\n\t- Total transactions: {}\n\t- Finished Transactions: {}\n\t- Mean time {}s\n\t- Finished transactions per second: {}\n\t- Duplicated ids ignored: {}\n\t- Duplicated ids rejected: {}\n\t- Already done: {}\n", me.total_transactions, me.current_finished_transactions, mean_time, tps, me.duplicates_ignored, me.duplicates_rejected, me.already_done);
                     ctx.address().do_send(LogPeriodically {})
                 })
         )
@@ -150,7 +212,8 @@ impl Handler<LogPeriodically> for StatisticsHandler {
 #[cfg(test)]
 mod tests {
     use crate::statistics_handler::{
-        GetMeanDuration, RegisterTransaction, StatisticsHandler, UnregisterTransaction,
+        Collision, CollisionCounts, GetCollisions, GetMeanDuration, RegisterCollision,
+        RegisterTransaction, StatisticsHandler, UnregisterTransaction,
     };
     use actix::Actor;
     use float_cmp::approx_eq;
@@ -257,4 +320,33 @@ mod tests {
 
         assert!(approx_eq!(f64, secs, 8.0, epsilon = 1e-9));
     }
+
+    #[actix_rt::test]
+    async fn test_collisions_are_counted_by_outcome() {
+        let addr = StatisticsHandler::new().start();
+        for collision in [
+            Collision::Ignored,
+            Collision::Rejected,
+            Collision::Rejected,
+            Collision::AlreadyDone,
+        ] {
+            addr.send(RegisterCollision::new(collision))
+                .await
+                .expect("fallo el envio de la colision");
+        }
+
+        let collisions = addr
+            .send(GetCollisions {})
+            .await
+            .expect("fallo envio de mensaje de log");
+
+        assert_eq!(
+            collisions,
+            CollisionCounts {
+                ignored: 1,
+                rejected: 2,
+                already_done: 1
+            }
+        );
+    }
 }
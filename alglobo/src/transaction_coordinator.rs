@@ -1,54 +1,224 @@
-use crate::entity_sender::BroadcastTransactionState;
+use crate::abort_reason::{AbortReason, TransactionFailure};
+use crate::decision_log::{DecisionLog, PendingDecision};
+use crate::entity_sender::{BroadcastTransactionState, QueryTransactionState};
 use crate::logger::LoggerActor;
+use crate::replicator::{Replicate, ReplicationEntry, Replicator};
 use crate::EntitySender;
 use crate::LogMessage;
 use actix::{
-    Actor, ActorFutureExt, Addr, Context, Handler, Message, ResponseActFuture, WrapFuture,
+    Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, MessageResult,
+    ResponseActFuture, WrapFuture,
 };
+use alglobo_common_utils::decision_inquiry::DecisionInquiry;
+use alglobo_common_utils::entity_type::EntityType;
 use alglobo_common_utils::transaction_response::TransactionResponse;
 use alglobo_common_utils::transaction_state::TransactionState;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::time::Duration;
 use tokio::sync::oneshot;
 use tokio::sync::oneshot::Sender;
 use tokio::time::timeout;
 
-const TIMEOUT_S: u64 = 30;
+// lo que espera cada transaccion: todos los votos, o el motivo por el que ya no tiene sentido esperarlos
+type VoteResult = Result<HashMap<EntityType, TransactionState>, AbortReason>;
+
+// Con dos fases las entidades que votaron quedan bloqueadas si el lider se cae antes de
+// mandarles la decision. En tres fases, antes del commit se les avisa que todos votaron a
+// favor (PreCommit): un lider nuevo, o una entidad que pregunta, puede terminarla sin el anterior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitProtocol {
+    TwoPhase,
+    ThreePhase,
+}
+
+impl FromStr for CommitProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "2pc" => Ok(CommitProtocol::TwoPhase),
+            "3pc" => Ok(CommitProtocol::ThreePhase),
+            _ => Err(format!("Unknown commit protocol: {}", s)),
+        }
+    }
+}
 
 pub struct TransactionCoordinator {
-    transaction_log: HashMap<u64, TransactionState>,
-    transaction_update_listening_channels: HashMap<u64, Sender<Vec<Option<TransactionState>>>>,
-    entity_states: HashMap<u64, Vec<Option<TransactionState>>>,
+    // ultimo intento visto de cada transaccion y su estado
+    transaction_log: HashMap<u64, (u32, TransactionState)>,
+    transaction_update_listening_channels: HashMap<u64, Sender<VoteResult>>,
+    // participantes que tienen que votar en cada transaccion (solo las entidades con costo > 0)
+    expected_participants: HashMap<u64, HashSet<EntityType>>,
+    entity_states: HashMap<u64, HashMap<EntityType, TransactionState>>,
+    decision_log: DecisionLog,
+    replicator: Addr<Replicator>,
+    // cuanto se esperan los votos antes de abortar
+    vote_timeout: Duration,
+    // hasta recuperar el log del lider anterior no sabemos como terminaron sus transacciones
+    recovered: bool,
+    commit_protocol: CommitProtocol,
+    // cada cuanto se saca del log lo que ya termino
+    log_compaction_interval: Option<Duration>,
+    // en tres fases, lo que el lider anterior dejo en prepared y todavia no se termino
+    terminating: HashMap<u64, Termination>,
+    // para avisar como terminaron; se conoce al recuperar el log
+    entity_sender: Option<Addr<EntitySender>>,
     logger: Addr<LoggerActor>,
 }
 
+// Una transaccion que quedo en prepared con el commit en tres fases: no se puede presumir abort
+// porque alguno de los participantes pudo haber recibido el precommit, asi que se les pregunta
+struct Termination {
+    attempt: u32,
+    participants: HashSet<EntityType>,
+    // los que todavia no contestaron
+    waiting: HashSet<EntityType>,
+}
+
 impl TransactionCoordinator {
-    pub fn new(logger: Addr<LoggerActor>) -> Self {
+    pub fn new(
+        logger: Addr<LoggerActor>,
+        decision_log: DecisionLog,
+        replicator: Addr<Replicator>,
+        vote_timeout: Duration,
+    ) -> Self {
         logger.do_send(LogMessage::new(
             "Creating TransactionCoordinator...".to_string(),
         ));
         TransactionCoordinator {
             transaction_log: HashMap::new(),
             transaction_update_listening_channels: HashMap::new(),
+            expected_participants: HashMap::new(),
             entity_states: HashMap::new(),
+            decision_log,
+            replicator,
+            vote_timeout,
+            recovered: false,
+            commit_protocol: CommitProtocol::TwoPhase,
+            log_compaction_interval: None,
+            terminating: HashMap::new(),
+            entity_sender: None,
             logger,
         }
     }
+
+    pub fn with_commit_protocol(mut self, commit_protocol: CommitProtocol) -> Self {
+        self.commit_protocol = commit_protocol;
+        self
+    }
+
+    pub fn with_log_compaction(mut self, interval: Duration) -> Self {
+        self.log_compaction_interval = Some(interval);
+        self
+    }
+}
+
+// Presumed abort: lo que no tenemos registrado nunca se commiteo, y un intento anterior al
+// ultimo que conocemos se aborto (solo se reintentan las abortadas). None si todavia estamos
+// esperando los votos de ese intento. Lo precommiteado ya no se puede abortar: si lo sabemos
+// nosotros o la entidad que pregunta, se commitea, y nunca se le presume abort a una entidad
+// precommiteada
+fn presumed_decision(
+    known: Option<(u32, TransactionState)>,
+    inquiry: &DecisionInquiry,
+) -> Option<TransactionState> {
+    match known {
+        Some((known_attempt, TransactionState::Wait)) if known_attempt == inquiry.attempt => {
+            match inquiry.precommitted {
+                true => Some(TransactionState::Commit),
+                false => None,
+            }
+        }
+        Some((known_attempt, TransactionState::PreCommit)) if known_attempt == inquiry.attempt => {
+            Some(TransactionState::Commit)
+        }
+        Some((known_attempt, state)) if known_attempt == inquiry.attempt => Some(state),
+        None if inquiry.precommitted => Some(TransactionState::Commit),
+        _ if inquiry.precommitted => None,
+        _ => Some(TransactionState::Abort),
+    }
+}
+
+impl TransactionCoordinator {
+    // decide una transaccion que habia quedado en prepared y se lo avisa a los participantes
+    fn terminate(&mut self, transaction_id: u64, transaction_state: TransactionState) {
+        let termination = match self.terminating.remove(&transaction_id) {
+            Some(termination) => termination,
+            None => return,
+        };
+        let attempt = termination.attempt;
+        self.logger.do_send(LogMessage::new(format!(
+            "[COORDINATOR] transaction {} (attempt {}) terminated: {:?}",
+            transaction_id, attempt, transaction_state
+        )));
+        if let Err(what) = self.decision_log.decided(
+            transaction_id,
+            attempt,
+            transaction_state,
+            &termination.participants,
+        ) {
+            self.logger.do_send(LogMessage::error(format!(
+                "[COORDINATOR] ERROR persisting decision of transaction {}: {}",
+                transaction_id, what
+            )));
+        }
+        self.replicator
+            .do_send(Replicate::new(ReplicationEntry::Decided(
+                transaction_id,
+                attempt,
+                transaction_state,
+                termination.participants.clone(),
+            )));
+        self.transaction_log
+            .insert(transaction_id, (attempt, transaction_state));
+        let failure = match transaction_state {
+            TransactionState::Abort => Some(TransactionFailure::new(
+                AbortReason::Recovered,
+                HashMap::new(),
+            )),
+            _ => None,
+        };
+        if let Some(entity_sender) = &self.entity_sender {
+            entity_sender.do_send(BroadcastTransactionState::new(
+                transaction_id,
+                attempt,
+                transaction_state,
+                termination.participants,
+                failure,
+            ));
+        }
+    }
 }
 
 impl Actor for TransactionCoordinator {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(interval) = self.log_compaction_interval {
+            ctx.run_interval(interval, |me, _| {
+                if let Err(e) = me.decision_log.compact() {
+                    me.logger.do_send(LogMessage::error(format!(
+                        "[COORDINATOR] ERROR compacting the decision log: {}",
+                        e
+                    )));
+                }
+            });
+        }
+    }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct TransactionUpdate {
+    entity: EntityType,
     transaction_response: TransactionResponse,
 }
 
 impl TransactionUpdate {
-    pub fn new(transaction_response: TransactionResponse) -> Self {
+    pub fn new(entity: EntityType, transaction_response: TransactionResponse) -> Self {
         TransactionUpdate {
+            entity,
             transaction_response,
         }
     }
@@ -58,72 +228,139 @@ impl Handler<TransactionUpdate> for TransactionCoordinator {
     type Result = ();
 
     fn handle(&mut self, msg: TransactionUpdate, _ctx: &mut Self::Context) -> Self::Result {
-        let v = match self
-            .entity_states
-            .get_mut(&msg.transaction_response.transaction_id)
-        {
-            Some(v) => v,
-            None => {
-                self.entity_states
-                    .insert(msg.transaction_response.transaction_id, Vec::new());
-                self.entity_states
-                    .get_mut(&msg.transaction_response.transaction_id)
-                    .unwrap()
+        let transaction_id = msg.transaction_response.transaction_id;
+        // la respuesta a la consulta por una transaccion que quedo en prepared: con que uno
+        // haya recibido el precommit se commitea, y si ninguno lo recibio se aborta
+        if let Some(termination) = self.terminating.get_mut(&transaction_id) {
+            let decision = match msg.transaction_response.transaction_state {
+                TransactionState::PreCommit | TransactionState::Commit => {
+                    Some(TransactionState::Commit)
+                }
+                TransactionState::Abort => Some(TransactionState::Abort),
+                _ => {
+                    termination.waiting.remove(&msg.entity);
+                    match termination.waiting.is_empty() {
+                        true => Some(TransactionState::Abort),
+                        false => None,
+                    }
+                }
+            };
+            if let Some(decision) = decision {
+                self.terminate(transaction_id, decision);
             }
+            return;
+        }
+        if let TransactionState::Error = msg.transaction_response.transaction_state {
+            // la entidad nos avisa que recibio algo que no respeta el protocolo
+            // si todavia estamos votando cuenta como un voto en contra
+            self.logger.do_send(LogMessage::new(format!(
+                "[COORDINATOR] Protocol error reported by {:?} for transaction {}",
+                msg.entity, transaction_id
+            )));
+        }
+        // unico caso en el que hay que guardar el voto es cuando estamos esperando
+        // ergo, esto solo vale en la fase de Prepare (las respuestas al commit/abort se descartan)
+        let participants = match self.expected_participants.get(&transaction_id) {
+            Some(participants) => participants,
+            None => return,
         };
-        v.push(Some(msg.transaction_response.transaction_state));
-        if v.len() == 3 && (v.iter().all(|opt| opt.is_some())) {
-            // unico caso en el que hay que mandar por el canal es cuando estamos esperando
-            // ergo, esto solo vale en la fase de Prepare
-            if let None | Some(&TransactionState::Wait) = self
-                .transaction_log
-                .get(&msg.transaction_response.transaction_id)
-            {
-                self.logger.do_send(LogMessage::new(format!(
-                    "[COORDINATOR] States for transaction {}: {:?}",
-                    msg.transaction_response.transaction_id, v
-                )));
-                // si llegue acá mando el estado nuevo de la transaccion
-                // este unwrap es correcto ya que sabemos que el vector tiene 3 options con some
-                let tx = self
-                    .transaction_update_listening_channels
-                    .remove(&msg.transaction_response.transaction_id);
-                let v = self
-                    .entity_states
-                    .remove(&msg.transaction_response.transaction_id);
-                if let (Some(vec), Some(tx)) = (v, tx) {
-                    // si fallo se droppeo el receiver, con lo cual se llego al timeout, y por ende se aborto la transaccion
-                    let _ = tx.send(vec);
-                }
+        if !participants.contains(&msg.entity) {
+            self.logger.do_send(LogMessage::new(format!(
+                "[COORDINATOR] Ignoring vote from {:?}, not a participant of transaction {}",
+                msg.entity, transaction_id
+            )));
+            return;
+        }
+        let votes = self.entity_states.entry(transaction_id).or_default();
+        votes.insert(msg.entity, msg.transaction_response.transaction_state);
+        if votes.len() == participants.len() {
+            self.logger.do_send(LogMessage::new(format!(
+                "[COORDINATOR] States for transaction {}: {:?}",
+                transaction_id, votes
+            )));
+            // si llegue acá votaron todos los participantes, mando los votos
+            self.expected_participants.remove(&transaction_id);
+            let tx = self
+                .transaction_update_listening_channels
+                .remove(&transaction_id);
+            let votes = self.entity_states.remove(&transaction_id);
+            if let (Some(votes), Some(tx)) = (votes, tx) {
+                // si fallo se droppeo el receiver, con lo cual se llego al timeout, y por ende se aborto la transaccion
+                let _ = tx.send(Ok(votes));
             }
         }
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DeliveryFailed {
+    transaction_id: u64,
+    entity: EntityType,
+}
+
+impl DeliveryFailed {
+    pub fn new(transaction_id: u64, entity: EntityType) -> Self {
+        DeliveryFailed {
+            transaction_id,
+            entity,
+        }
+    }
+}
+
+// el EntitySender agoto los reenvios del prepare a una entidad: no hace falta esperar al timeout
+impl Handler<DeliveryFailed> for TransactionCoordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: DeliveryFailed, _ctx: &mut Self::Context) -> Self::Result {
+        let waiting = self
+            .expected_participants
+            .get(&msg.transaction_id)
+            .is_some_and(|participants| participants.contains(&msg.entity));
+        if !waiting {
+            return;
+        }
+        self.logger.do_send(LogMessage::new(format!(
+            "[COORDINATOR] Could not deliver prepare of transaction {} to {:?}",
+            msg.transaction_id, msg.entity
+        )));
+        self.expected_participants.remove(&msg.transaction_id);
+        if let Some(tx) = self
+            .transaction_update_listening_channels
+            .remove(&msg.transaction_id)
+        {
+            let _ = tx.send(Err(AbortReason::SendFailure(msg.entity)));
+        }
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct WaitTransactionStateResponse {
     pub transaction_id: u64,
+    pub attempt: u32,
     pub transaction_state: TransactionState,
     pub expected_transaction_state: TransactionState,
     pub sender_addr: Addr<EntitySender>,
-    pub should_await_next_response: bool,
+    pub participants: HashSet<EntityType>,
 }
 
 impl WaitTransactionStateResponse {
     pub fn new(
         transaction_id: u64,
+        attempt: u32,
         transaction_state: TransactionState,
         expected_transaction_state: TransactionState,
         sender_addr: Addr<EntitySender>,
-        should_await_next_response: bool,
+        participants: HashSet<EntityType>,
     ) -> Self {
         WaitTransactionStateResponse {
             transaction_id,
+            attempt,
             transaction_state,
             expected_transaction_state,
             sender_addr,
-            should_await_next_response,
+            participants,
         }
     }
 }
@@ -141,68 +378,487 @@ impl Handler<WaitTransactionStateResponse> for TransactionCoordinator {
         _ctx: &mut Self::Context,
     ) -> Self::Result {
         // si la contenia, entonces ya registramos esta transaccion
-        // con lo cual no hace falta esperar a timeout (asumiendo que no se falla en la fase de commit)
-        let log_clone = self.logger.clone();
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            self.transaction_log.entry(msg.transaction_id)
-        {
+        // con lo cual no hace falta esperar a timeout (asumiendo que no se falla en la fase de commit).
+        // Un reintento de una transaccion fallida trae un intento mas alto y se vuelve a esperar
+        let already_registered = matches!(
+            self.transaction_log.get(&msg.transaction_id),
+            Some((attempt, _)) if *attempt >= msg.attempt
+        );
+        if !already_registered {
             let (tx, rx) = oneshot::channel();
-            e.insert(msg.transaction_state);
-            self.transaction_update_listening_channels
-                .insert(msg.transaction_id, tx);
+            self.transaction_log
+                .insert(msg.transaction_id, (msg.attempt, msg.transaction_state));
+            if let Err(what) =
+                self.decision_log
+                    .prepared(msg.transaction_id, msg.attempt, &msg.participants)
+            {
+                self.logger.do_send(LogMessage::error(format!(
+                    "[COORDINATOR] ERROR persisting prepare of transaction {}: {}",
+                    msg.transaction_id, what
+                )));
+            }
+            self.replicator
+                .do_send(Replicate::new(ReplicationEntry::Prepared(
+                    msg.transaction_id,
+                    msg.attempt,
+                    msg.participants.clone(),
+                )));
+            if msg.participants.is_empty() {
+                // no hay nadie que tenga que votar, la transaccion se resuelve sola
+                let _ = tx.send(Ok(HashMap::new()));
+            } else {
+                self.transaction_update_listening_channels
+                    .insert(msg.transaction_id, tx);
+                self.expected_participants
+                    .insert(msg.transaction_id, msg.participants.clone());
+            }
+            let vote_timeout = self.vote_timeout;
             let fut = async move {
-                match timeout(Duration::from_secs(TIMEOUT_S), rx).await {
-                    Ok(x) => {
-                        if let Ok(mut v) = x {
-                            let all_states_match = v.iter().all(|opt| {
-                                std::mem::discriminant(&msg.expected_transaction_state)
-                                    == std::mem::discriminant(opt.as_ref().unwrap())
-                            });
-                            if all_states_match {
-                                let state = v.remove(0).unwrap();
-                                msg.sender_addr.do_send(BroadcastTransactionState::new(
-                                    msg.transaction_id,
-                                    state,
-                                ));
-                                (msg.transaction_id, state)
-                            } else {
-                                msg.sender_addr.do_send(BroadcastTransactionState::new(
-                                    msg.transaction_id,
-                                    TransactionState::Abort,
-                                ));
-                                (msg.transaction_id, TransactionState::Abort)
-                            }
-                        } else {
-                            msg.sender_addr.do_send(BroadcastTransactionState::new(
-                                msg.transaction_id,
+                let result = match timeout(vote_timeout, rx).await {
+                    Ok(Ok(result)) => Some(result),
+                    // se cerro el canal o se vencio el timeout: nos quedamos sin todos los votos
+                    Ok(Err(_)) | Err(_) => None,
+                };
+                (msg, result)
+            };
+            Box::pin(fut.into_actor(self).map(|(msg, result), me, _| {
+                let id = msg.transaction_id;
+                let attempt = msg.attempt;
+                let (state, failure) = match result {
+                    Some(Ok(votes)) => {
+                        match AbortReason::from_votes(&votes, msg.expected_transaction_state) {
+                            None => (msg.expected_transaction_state, None),
+                            Some(reason) => (
                                 TransactionState::Abort,
-                            ));
-                            (msg.transaction_id, TransactionState::Abort)
+                                Some(TransactionFailure::new(reason, votes)),
+                            ),
                         }
                     }
-                    Err(_) => {
-                        log_clone.do_send(LogMessage::new(format!(
+                    Some(Err(reason)) => {
+                        let votes = me.entity_states.remove(&id).unwrap_or_default();
+                        (
+                            TransactionState::Abort,
+                            Some(TransactionFailure::new(reason, votes)),
+                        )
+                    }
+                    None => {
+                        me.logger.do_send(LogMessage::new(format!(
                             "[COORDINATOR] Timeout reached for transaction {}",
-                            msg.transaction_id
+                            id
                         )));
-                        msg.sender_addr.do_send(BroadcastTransactionState::new(
-                            msg.transaction_id,
+                        let votes = me.entity_states.remove(&id).unwrap_or_default();
+                        let mut missing = msg
+                            .participants
+                            .iter()
+                            .filter(|entity| !votes.contains_key(entity))
+                            .copied()
+                            .collect::<Vec<EntityType>>();
+                        missing.sort_by_key(|entity| entity.to_string());
+                        (
                             TransactionState::Abort,
-                        ));
-                        (msg.transaction_id, TransactionState::Abort)
+                            Some(TransactionFailure::new(AbortReason::Timeout(missing), votes)),
+                        )
                     }
-                }
-            };
-            Box::pin(fut.into_actor(self).map(|(id, state), me, _| {
-                me.logger.do_send(LogMessage::new(format!(
-                    "[COORDINATOR] transaction {} final state: {:?}",
+                };
+                // en tres fases el commit pasa antes por el precommit
+                let state = match (me.commit_protocol, state) {
+                    (CommitProtocol::ThreePhase, TransactionState::Commit)
+                        if !msg.participants.is_empty() =>
+                    {
+                        TransactionState::PreCommit
+                    }
+                    (_, state) => state,
+                };
+                // la decision tiene que quedar en disco antes de avisarle a las entidades
+                let (state, failure) =
+                    match me.decision_log.decided(id, attempt, state, &msg.participants) {
+                        Ok(_) => (state, failure),
+                        Err(what) => {
+                            me.logger.do_send(LogMessage::error(format!(
+                                "[COORDINATOR] ERROR persisting decision of transaction {}: {}, aborting",
+                                id, what
+                            )));
+                            // sin la decision en disco un lider nuevo presume abort, asi que abortamos
+                            let votes = failure.map(|failure| failure.votes).unwrap_or_default();
+                            (
+                                TransactionState::Abort,
+                                Some(TransactionFailure::new(AbortReason::NotPersisted, votes)),
+                            )
+                        }
+                    };
+                me.replicator.do_send(Replicate::new(ReplicationEntry::Decided(
                     id,
-                    state.clone()
+                    attempt,
+                    state,
+                    msg.participants.clone(),
                 )));
-                me.transaction_log.insert(id, state);
+                match (&failure, state) {
+                    (_, TransactionState::PreCommit) => me.logger.do_send(LogMessage::new(
+                        format!("[COORDINATOR] transaction {} pre-committed", id),
+                    )),
+                    (Some(failure), _) => me.logger.do_send(LogMessage::new(format!(
+                        "[COORDINATOR] transaction {} final state: {:?} ({})",
+                        id, state, failure.reason
+                    ))),
+                    (None, _) => me.logger.do_send(LogMessage::new(format!(
+                        "[COORDINATOR] transaction {} final state: {:?}",
+                        id, state
+                    ))),
+                }
+                msg.sender_addr.do_send(BroadcastTransactionState::new(
+                    id,
+                    attempt,
+                    state,
+                    msg.participants,
+                    failure,
+                ));
+                // si hubo timeout quedan votos parciales, los limpiamos
+                me.expected_participants.remove(&id);
+                me.entity_states.remove(&id);
+                me.transaction_update_listening_channels.remove(&id);
+                me.transaction_log.insert(id, (attempt, state));
             }))
         } else {
             Box::pin(std::future::ready(()).into_actor(self))
         }
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "Option<TransactionState>")]
+pub struct InquireDecision {
+    entity: EntityType,
+    inquiry: DecisionInquiry,
+}
+
+impl InquireDecision {
+    pub fn new(entity: EntityType, inquiry: DecisionInquiry) -> Self {
+        InquireDecision { entity, inquiry }
+    }
+}
+
+// una entidad voto y no le llego la decision. None si todavia no hay nada que contestarle
+impl Handler<InquireDecision> for TransactionCoordinator {
+    type Result = Option<TransactionState>;
+
+    fn handle(&mut self, msg: InquireDecision, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.recovered {
+            return None;
+        }
+        let id = msg.inquiry.transaction_id;
+        let attempt = msg.inquiry.attempt;
+        let known = self.transaction_log.get(&id).copied();
+        let decision = presumed_decision(known, &msg.inquiry);
+        self.logger.do_send(LogMessage::new(format!(
+            "[COORDINATOR] {:?} asked for the decision of transaction {} (attempt {}): {:?}",
+            msg.entity, id, attempt, decision
+        )));
+        // una entidad precommiteada termina lo que quedo en prepared
+        if let (Some((_, TransactionState::Wait)), Some(TransactionState::Commit)) =
+            (known, decision)
+        {
+            self.terminate(id, TransactionState::Commit);
+        }
+        // un commit que no teniamos registrado lo decidio la entidad precommiteada: lo anotamos
+        // para contestarle lo mismo a las demas. Solo queda en disco si sabemos a quienes se les
+        // mando el prepare; si no, cada una pregunta y se lleva su respuesta
+        if let (None, Some(TransactionState::Commit)) = (known, decision) {
+            match self.decision_log.prepared_participants(id, attempt) {
+                Some(participants) => {
+                    if let Err(what) = self.decision_log.decided(
+                        id,
+                        attempt,
+                        TransactionState::Commit,
+                        &participants,
+                    ) {
+                        self.logger.do_send(LogMessage::error(format!(
+                            "[COORDINATOR] ERROR persisting decision of transaction {}: {}",
+                            id, what
+                        )));
+                    }
+                    self.replicator
+                        .do_send(Replicate::new(ReplicationEntry::Decided(
+                            id,
+                            attempt,
+                            TransactionState::Commit,
+                            participants,
+                        )));
+                }
+                None => self.logger.do_send(LogMessage::new(format!(
+                    "[COORDINATOR] transaction {} (attempt {}) has no prepared record, its commit is not persisted",
+                    id, attempt
+                ))),
+            }
+            self.transaction_log
+                .insert(id, (attempt, TransactionState::Commit));
+        }
+        decision
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PreCommitted {
+    transaction_id: u64,
+    attempt: u32,
+    participants: HashSet<EntityType>,
+    sender_addr: Addr<EntitySender>,
+}
+
+impl PreCommitted {
+    pub fn new(
+        transaction_id: u64,
+        attempt: u32,
+        participants: HashSet<EntityType>,
+        sender_addr: Addr<EntitySender>,
+    ) -> Self {
+        PreCommitted {
+            transaction_id,
+            attempt,
+            participants,
+            sender_addr,
+        }
+    }
+}
+
+// lo manda el EntitySender cuando todos confirmaron el precommit (o se agotaron sus reenvios:
+// votaron a favor, asi que igual se commitea)
+impl Handler<PreCommitted> for TransactionCoordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: PreCommitted, _ctx: &mut Self::Context) -> Self::Result {
+        let id = msg.transaction_id;
+        // si no queda en disco no pasa nada, el precommit ya se recupera como commit
+        if let Err(what) =
+            self.decision_log
+                .decided(id, msg.attempt, TransactionState::Commit, &msg.participants)
+        {
+            self.logger.do_send(LogMessage::error(format!(
+                "[COORDINATOR] ERROR persisting decision of transaction {}: {}",
+                id, what
+            )));
+        }
+        self.replicator
+            .do_send(Replicate::new(ReplicationEntry::Decided(
+                id,
+                msg.attempt,
+                TransactionState::Commit,
+                msg.participants.clone(),
+            )));
+        self.logger.do_send(LogMessage::new(format!(
+            "[COORDINATOR] transaction {} final state: {:?}",
+            id,
+            TransactionState::Commit
+        )));
+        self.transaction_log
+            .insert(id, (msg.attempt, TransactionState::Commit));
+        msg.sender_addr.do_send(BroadcastTransactionState::new(
+            id,
+            msg.attempt,
+            TransactionState::Commit,
+            msg.participants,
+            None,
+        ));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct TransactionEnded {
+    transaction_id: u64,
+}
+
+impl TransactionEnded {
+    pub fn new(transaction_id: u64) -> Self {
+        TransactionEnded { transaction_id }
+    }
+}
+
+// lo manda el EntitySender cuando termino de enviarle la decision a todas las entidades
+impl Handler<TransactionEnded> for TransactionCoordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: TransactionEnded, _ctx: &mut Self::Context) -> Self::Result {
+        let attempt = self
+            .decision_log
+            .attempt(msg.transaction_id)
+            .unwrap_or_default();
+        if let Err(what) = self.decision_log.ended(msg.transaction_id, attempt) {
+            self.logger.do_send(LogMessage::error(format!(
+                "[COORDINATOR] ERROR persisting end of transaction {}: {}",
+                msg.transaction_id, what
+            )));
+        }
+        self.replicator
+            .do_send(Replicate::new(ReplicationEntry::Ended(
+                msg.transaction_id,
+                attempt,
+            )));
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<PendingDecision>")]
+pub struct RecoverPendingDecisions {
+    entity_sender: Addr<EntitySender>,
+}
+
+impl RecoverPendingDecisions {
+    pub fn new(entity_sender: Addr<EntitySender>) -> Self {
+        RecoverPendingDecisions { entity_sender }
+    }
+}
+
+// Lee el log de decisiones que dejo el lider anterior y devuelve lo que quedo sin terminar
+impl Handler<RecoverPendingDecisions> for TransactionCoordinator {
+    type Result = MessageResult<RecoverPendingDecisions>;
+
+    fn handle(&mut self, msg: RecoverPendingDecisions, _ctx: &mut Self::Context) -> Self::Result {
+        self.entity_sender = Some(msg.entity_sender.clone());
+        // el replicator le acaba de agregar lo que tenian las demas replicas
+        if let Err(e) = self.decision_log.reload() {
+            self.logger.do_send(LogMessage::error(format!(
+                "[COORDINATOR] ERROR reading the decision log: {}",
+                e
+            )));
+        }
+        // las que solo llegaron a prepared se abortan (presumed abort), y las precommiteadas se
+        // commitean: todos votaron a favor. En tres fases las que quedaron en prepared no se
+        // pueden abortar sin mas, alguno pudo haber recibido el precommit: se les pregunta
+        let mut pending = self.decision_log.pending();
+        for decision in pending.iter_mut() {
+            decision.transaction_state = match decision.transaction_state {
+                TransactionState::Wait
+                    if matches!(self.commit_protocol, CommitProtocol::ThreePhase)
+                        && !decision.participants.is_empty() =>
+                {
+                    TransactionState::Wait
+                }
+                TransactionState::Wait => TransactionState::Abort,
+                TransactionState::PreCommit => TransactionState::Commit,
+                state => state,
+            };
+        }
+        for decision in &pending {
+            if matches!(decision.transaction_state, TransactionState::Wait) {
+                self.logger.do_send(LogMessage::new(format!(
+                    "[COORDINATOR] Recovered prepared transaction {} (attempt {}), asking the participants",
+                    decision.transaction_id, decision.attempt
+                )));
+                self.terminating.insert(
+                    decision.transaction_id,
+                    Termination {
+                        attempt: decision.attempt,
+                        participants: decision.participants.clone(),
+                        waiting: decision.participants.clone(),
+                    },
+                );
+                self.transaction_log.insert(
+                    decision.transaction_id,
+                    (decision.attempt, TransactionState::Wait),
+                );
+                msg.entity_sender.do_send(QueryTransactionState::new(
+                    decision.transaction_id,
+                    decision.attempt,
+                    decision.participants.clone(),
+                ));
+                continue;
+            }
+            self.logger.do_send(LogMessage::new(format!(
+                "[COORDINATOR] Recovered transaction {} with decision {:?}",
+                decision.transaction_id, decision.transaction_state
+            )));
+            // las que solo estaban en prepared se abortan, y eso tambien tiene que quedar en disco
+            let _ = self.decision_log.decided(
+                decision.transaction_id,
+                decision.attempt,
+                decision.transaction_state,
+                &decision.participants,
+            );
+            self.replicator
+                .do_send(Replicate::new(ReplicationEntry::Decided(
+                    decision.transaction_id,
+                    decision.attempt,
+                    decision.transaction_state,
+                    decision.participants.clone(),
+                )));
+            self.transaction_log.insert(
+                decision.transaction_id,
+                (decision.attempt, decision.transaction_state),
+            );
+        }
+        self.recovered = true;
+        MessageResult(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction_coordinator::{presumed_decision, CommitProtocol};
+    use alglobo_common_utils::decision_inquiry::DecisionInquiry;
+    use alglobo_common_utils::transaction_state::TransactionState;
+    use std::str::FromStr;
+
+    fn decision(known: Option<(u32, TransactionState)>, attempt: u32) -> String {
+        format!(
+            "{:?}",
+            presumed_decision(known, &DecisionInquiry::new(0, attempt))
+        )
+    }
+
+    fn precommitted(known: Option<(u32, TransactionState)>, attempt: u32) -> String {
+        let inquiry = DecisionInquiry::new(0, attempt).with_precommit(true);
+        format!("{:?}", presumed_decision(known, &inquiry))
+    }
+
+    #[test]
+    fn test_unknown_transactions_are_presumed_aborted() {
+        assert_eq!(
+            decision(Some((1, TransactionState::Commit)), 1),
+            "Some(Commit)"
+        );
+        assert_eq!(
+            decision(Some((1, TransactionState::Abort)), 1),
+            "Some(Abort)"
+        );
+        // todavia votando
+        assert_eq!(decision(Some((1, TransactionState::Wait)), 1), "None");
+        // un intento viejo, o uno que preparo otro lider y no nos llego
+        assert_eq!(
+            decision(Some((2, TransactionState::Commit)), 1),
+            "Some(Abort)"
+        );
+        assert_eq!(
+            decision(Some((1, TransactionState::Commit)), 2),
+            "Some(Abort)"
+        );
+        assert_eq!(decision(None, 0), "Some(Abort)");
+    }
+
+    #[test]
+    fn test_precommitted_transactions_are_committed() {
+        assert_eq!(
+            decision(Some((1, TransactionState::PreCommit)), 1),
+            "Some(Commit)"
+        );
+        // el lider nuevo no se entero, pero la entidad sabe que todos votaron a favor
+        assert_eq!(precommitted(None, 1), "Some(Commit)");
+        // quedo en prepared: la entidad precommiteada lo termina
+        assert_eq!(
+            precommitted(Some((1, TransactionState::Wait)), 1),
+            "Some(Commit)"
+        );
+        // a una entidad precommiteada nunca se le presume abort
+        assert_eq!(precommitted(Some((2, TransactionState::Wait)), 1), "None");
+        assert_eq!(
+            decision(Some((2, TransactionState::Wait)), 1),
+            "Some(Abort)"
+        );
+
+        assert_eq!(
+            CommitProtocol::from_str("3PC"),
+            Ok(CommitProtocol::ThreePhase)
+        );
+        assert!(CommitProtocol::from_str("4pc").is_err());
+    }
+}
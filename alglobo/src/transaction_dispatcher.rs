@@ -1,32 +1,144 @@
+use crate::done_ledger::FinalState;
 use crate::entity_sender::{EntitySender, PrepareTransaction};
+use crate::input_source::FIELDS;
+use crate::rate_limiter::RateLimiter;
+use crate::statistics_handler::{Collision, RegisterCollision, StatisticsHandler};
 use crate::LogMessage;
-use actix::{Actor, Addr, Context, Handler, Message};
+use actix::dev::MessageResponse;
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
+use alglobo_common_utils::cluster_config::DuplicatePolicy;
+use alglobo_common_utils::entity_type::EntityType;
 use alglobo_common_utils::transaction_request::TransactionRequest;
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 
 use crate::logger::LoggerActor;
 use csv::StringRecord;
 
-const HEADER_ID: &str = "id";
-const HEADER_HOTEL: &str = "hotel_cost";
-const HEADER_BANK: &str = "bank_cost";
-const HEADER_AIRLINE: &str = "airline_cost";
+// las filas reintentadas traen una columna mas con el numero de intento
+pub fn parse_transaction(record: &StringRecord) -> Result<TransactionRequest, csv::Error> {
+    let header = FIELDS[..record.len().min(FIELDS.len())].to_vec();
+    record.deserialize(Some(&StringRecord::from(header)))
+}
+
+// que hizo el dispatcher con una transaccion
+#[derive(Debug, PartialEq, Eq, MessageResponse)]
+pub enum Dispatch {
+    Prepared,
+    // ya la termino un lider anterior
+    AlreadyDone,
+    // repite una fila anterior y la politica dice que es la misma transaccion
+    Duplicate,
+    Rejected(String),
+}
+
+// Detecta ids que ya aparecieron, esten en vuelo o terminados
+pub struct CollisionDetector {
+    policy: DuplicatePolicy,
+    // lo que trajo cada id la primera vez en esta corrida
+    seen: HashMap<u64, TransactionRequest>,
+    // las que termino un lider anterior, con como terminaron y en que intento; de esas no se
+    // sabe que traian, y volver a encontrarlas es lo normal al releer la entrada despues de un
+    // cambio de lider
+    done: HashMap<u64, (FinalState, u32)>,
+}
+
+impl CollisionDetector {
+    pub fn new(policy: DuplicatePolicy) -> Self {
+        CollisionDetector {
+            policy,
+            seen: HashMap::new(),
+            done: HashMap::new(),
+        }
+    }
+
+    pub fn mark_done(&mut self, transactions: HashMap<u64, (FinalState, u32)>) {
+        self.done.extend(transactions);
+    }
+
+    pub fn check(&mut self, transaction: &TransactionRequest) -> Dispatch {
+        let id = transaction.get_transaction_id();
+        if let Some(first) = self.seen.get(&id) {
+            return match self.policy {
+                DuplicatePolicy::Idempotent if first == transaction => Dispatch::Duplicate,
+                DuplicatePolicy::Idempotent => Dispatch::Rejected(format!(
+                    "Transaction {} was already read with other costs",
+                    id
+                )),
+                DuplicatePolicy::Reject => {
+                    Dispatch::Rejected(format!("Duplicated transaction id: {}", id))
+                }
+            };
+        }
+        match self.done.get(&id) {
+            // una abortada vuelve con el intento siguiente cuando se reintentan las fallidas
+            Some((FinalState::Abort, attempt)) if transaction.get_attempt() > *attempt => {}
+            Some(_) => return Dispatch::AlreadyDone,
+            None => {}
+        }
+        self.seen.insert(id, transaction.clone());
+        Dispatch::Prepared
+    }
+}
 
 pub struct TransactionDispatcher {
     messenger: Addr<EntitySender>,
+    statistics: Addr<StatisticsHandler>,
     logger: Addr<LoggerActor>,
-    done_transactions: HashSet<u64>,
+    collisions: CollisionDetector,
+    rate_limiter: RateLimiter,
+    // las que esperan tokens, en el orden en que llegaron
+    waiting: VecDeque<TransactionRequest>,
+    drain_scheduled: bool,
 }
 
 impl TransactionDispatcher {
-    pub fn new(messenger: Addr<EntitySender>, logger: Addr<LoggerActor>) -> Self {
+    pub fn new(
+        messenger: Addr<EntitySender>,
+        statistics: Addr<StatisticsHandler>,
+        duplicate_policy: DuplicatePolicy,
+        logger: Addr<LoggerActor>,
+    ) -> Self {
         logger.do_send(LogMessage::new(
             "Creating TransactionDispatcher...".to_string(),
         ));
         TransactionDispatcher {
             messenger,
+            statistics,
             logger,
-            done_transactions: HashSet::new(),
+            collisions: CollisionDetector::new(duplicate_policy),
+            rate_limiter: RateLimiter::new(),
+            waiting: VecDeque::new(),
+            drain_scheduled: false,
+        }
+    }
+
+    // manda a preparar las que tienen tokens. Se respeta el orden del archivo, asi que una fila
+    // de una entidad lenta demora a las que vienen detras
+    fn drain(&mut self, ctx: &mut Context<Self>) {
+        while let Some(transaction) = self.waiting.front() {
+            let entities = transaction
+                .get_entities_data()
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<EntityType>>();
+            match self.rate_limiter.try_acquire(&entities, Instant::now()) {
+                Ok(()) => {
+                    if let Some(transaction) = self.waiting.pop_front() {
+                        self.messenger.do_send(PrepareTransaction::new(transaction));
+                    }
+                }
+                Err(wait) => {
+                    if !self.drain_scheduled {
+                        self.drain_scheduled = true;
+                        ctx.run_later(wait, |me, ctx| {
+                            me.drain_scheduled = false;
+                            me.drain(ctx);
+                        });
+                    }
+                    return;
+                }
+            }
         }
     }
 }
@@ -35,57 +147,72 @@ impl Actor for TransactionDispatcher {
     type Context = Context<Self>;
 }
 
+// Llega ya validada
 #[derive(Message)]
-#[rtype(result = "()")]
+#[rtype(result = "Dispatch")]
 pub struct ReceiveTransaction {
-    transaction: StringRecord,
+    transaction: TransactionRequest,
+    // la manda de nuevo el FileReader porque fallo un intento anterior
+    retry: bool,
 }
 
 impl ReceiveTransaction {
-    pub fn new(transaction: StringRecord) -> Self {
-        ReceiveTransaction { transaction }
+    pub fn new(transaction: TransactionRequest) -> Self {
+        ReceiveTransaction {
+            transaction,
+            retry: false,
+        }
     }
 
-    pub fn deserialize(&self, _: &Addr<LoggerActor>) -> TransactionRequest {
-        let header = StringRecord::from(vec![HEADER_ID, HEADER_HOTEL, HEADER_BANK, HEADER_AIRLINE]);
-        let raw_transaction = self.transaction.clone();
-        match raw_transaction.deserialize(Some(&header)) {
-            Ok(transaction) => transaction,
-            Err(e) => {
-                eprintln!("{}", e);
-                panic!()
-            }
+    pub fn retry(transaction: TransactionRequest) -> Self {
+        ReceiveTransaction {
+            transaction,
+            retry: true,
         }
     }
 }
 
 impl Handler<ReceiveTransaction> for TransactionDispatcher {
-    type Result = ();
-    fn handle(
-        &mut self,
-        raw_transaction: ReceiveTransaction,
-        _ctx: &mut Self::Context,
-    ) -> Self::Result {
-        let transaction = raw_transaction.deserialize(&self.logger);
-        // if transaction has not already been done, we go ahead and prepare it
-        if !self
-            .done_transactions
-            .contains(&transaction.get_transaction_id())
-        {
-            let msg = PrepareTransaction::new(transaction);
-            let _ = self.messenger.do_send(msg);
-        }
+    type Result = Dispatch;
+    fn handle(&mut self, msg: ReceiveTransaction, ctx: &mut Self::Context) -> Self::Result {
+        let transaction = msg.transaction;
+        // los reintentos del FileReader se preparan siempre: el id ya paso por el detector en el
+        // primer intento. Una fila del archivo con intento > 0 se revisa como cualquier otra, y
+        // pasa si la abortada que tiene registrada es de un intento anterior
+        let dispatch = if msg.retry {
+            Dispatch::Prepared
+        } else {
+            self.collisions.check(&transaction)
+        };
+        let collision = match &dispatch {
+            Dispatch::Prepared => {
+                self.waiting.push_back(transaction);
+                self.drain(ctx);
+                return dispatch;
+            }
+            Dispatch::AlreadyDone => Collision::AlreadyDone,
+            Dispatch::Duplicate => Collision::Ignored,
+            Dispatch::Rejected(_) => Collision::Rejected,
+        };
+        self.logger.do_send(LogMessage::new(format!(
+            "TransactionDispatcher: transaction {} not prepared: {:?}",
+            transaction.get_transaction_id(),
+            dispatch
+        )));
+        self.statistics.do_send(RegisterCollision::new(collision));
+        dispatch
     }
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct SaveDoneTransactions {
-    transactions: HashSet<u64>,
+    // como termino cada una y en que intento
+    transactions: HashMap<u64, (FinalState, u32)>,
 }
 
 impl SaveDoneTransactions {
-    pub fn new(transactions: HashSet<u64>) -> Self {
+    pub fn new(transactions: HashMap<u64, (FinalState, u32)>) -> Self {
         Self { transactions }
     }
 }
@@ -94,6 +221,88 @@ impl Handler<SaveDoneTransactions> for TransactionDispatcher {
     type Result = ();
 
     fn handle(&mut self, msg: SaveDoneTransactions, _: &mut Self::Context) -> Self::Result {
-        self.done_transactions = msg.transactions;
+        self.collisions.mark_done(msg.transactions);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetRateLimit {
+    entity: EntityType,
+    // prepares por segundo, None para sacar el limite
+    rate: Option<f64>,
+}
+
+impl SetRateLimit {
+    pub fn new(entity: EntityType, rate: Option<f64>) -> Self {
+        SetRateLimit { entity, rate }
+    }
+}
+
+// se puede mandar en cualquier momento, por ejemplo para ir subiendo la carga de a poco
+impl Handler<SetRateLimit> for TransactionDispatcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetRateLimit, ctx: &mut Self::Context) -> Self::Result {
+        self.logger.do_send(LogMessage::new(format!(
+            "TransactionDispatcher: rate limit for {} set to {:?}",
+            msg.entity, msg.rate
+        )));
+        self.rate_limiter
+            .set_rate(msg.entity, msg.rate, Instant::now());
+        self.drain(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::done_ledger::FinalState;
+    use crate::transaction_dispatcher::{parse_transaction, CollisionDetector, Dispatch};
+    use alglobo_common_utils::cluster_config::DuplicatePolicy;
+    use alglobo_common_utils::transaction_request::TransactionRequest;
+    use csv::StringRecord;
+    use std::collections::HashMap;
+
+    fn transaction(fields: [&str; 5]) -> TransactionRequest {
+        parse_transaction(&StringRecord::from(fields.to_vec())).unwrap()
+    }
+
+    #[test]
+    fn test_collisions_follow_the_policy() {
+        let mut detector = CollisionDetector::new(DuplicatePolicy::Idempotent);
+        detector.mark_done(HashMap::from([(7, (FinalState::Commit, 0))]));
+        assert_eq!(
+            detector.check(&transaction(["1", "10", "0", "0", "ARS"])),
+            Dispatch::Prepared
+        );
+        assert_eq!(
+            detector.check(&transaction(["1", "10", "0", "0", "ARS"])),
+            Dispatch::Duplicate
+        );
+        assert!(matches!(
+            detector.check(&transaction(["1", "10", "5", "0", "ARS"])),
+            Dispatch::Rejected(_)
+        ));
+        // los mismos montos en otra moneda son otra transaccion
+        assert!(matches!(
+            detector.check(&transaction(["1", "10", "0", "0", "USD"])),
+            Dispatch::Rejected(_)
+        ));
+        assert_eq!(
+            detector.check(&transaction(["7", "1", "1", "1", "ARS"])),
+            Dispatch::AlreadyDone
+        );
+
+        let mut detector = CollisionDetector::new(DuplicatePolicy::Reject);
+        assert_eq!(
+            detector.check(&transaction(["2", "0", "3", "0", "ARS"])),
+            Dispatch::Prepared
+        );
+        // termino en esta misma corrida: sigue contando como repetida
+        detector.mark_done(HashMap::from([(2, (FinalState::Abort, 0))]));
+        assert!(matches!(
+            detector.check(&transaction(["2", "0", "3", "0", "ARS"])),
+            Dispatch::Rejected(_)
+        ));
     }
 }
@@ -0,0 +1,294 @@
+use crate::input_source::{
+    self, InputSpec, CHECK_IN_FIELD, CHECK_OUT_FIELD, CURRENCY_FIELD, CUSTOMER_FIELD,
+    FARE_CLASS_FIELD, FIELDS, FLIGHT_FIELD, PROPERTY_FIELD,
+};
+use crate::transaction_dispatcher::parse_transaction;
+use alglobo_common_utils::money::Currency;
+use alglobo_common_utils::seat::Seat;
+use alglobo_common_utils::stay::{Date, Stay};
+use alglobo_common_utils::transaction_request::TransactionRequest;
+use csv::StringRecord;
+use std::collections::HashSet;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub rows: u64,
+    pub errors: Vec<RowError>,
+}
+
+// Chequeos de cada fila antes de mandarla a preparar: que se pueda parsear,
+// que el id no se repita y que alguna entidad tenga algo que reservar. Recibe las filas
+// normalizadas por input_source
+#[derive(Default)]
+pub struct TransactionValidator {
+    // None si de los ids repetidos se encarga otro
+    seen_ids: Option<HashSet<u64>>,
+}
+
+impl TransactionValidator {
+    pub fn new() -> Self {
+        TransactionValidator {
+            seen_ids: Some(HashSet::new()),
+        }
+    }
+
+    // en el pipeline los ids repetidos los resuelve el dispatcher segun la politica configurada
+    pub fn allowing_duplicates() -> Self {
+        TransactionValidator { seen_ids: None }
+    }
+
+    pub fn check(&mut self, record: &StringRecord) -> Result<TransactionRequest, String> {
+        // id y costos
+        for (index, name) in FIELDS[..CURRENCY_FIELD].iter().enumerate() {
+            match record.get(index) {
+                Some(value) => check_number(name, value)?,
+                None => return Err(format!("Missing column {}", name)),
+            }
+        }
+        match record.get(CURRENCY_FIELD) {
+            Some(code) => {
+                Currency::from_str(code).map_err(|_| {
+                    format!(
+                        "{} is not an ISO 4217 code: {}",
+                        FIELDS[CURRENCY_FIELD], code
+                    )
+                })?;
+            }
+            None => return Err(format!("Missing column {}", FIELDS[CURRENCY_FIELD])),
+        }
+        // sin cliente el banco no va a tener de donde cobrar, pero eso lo decide el banco
+        match record.get(CUSTOMER_FIELD) {
+            Some("") | None => {}
+            Some(customer) => check_number(FIELDS[CUSTOMER_FIELD], customer)?,
+        }
+        check_stay(record)?;
+        check_seat(record)?;
+        let transaction = parse_transaction(record).map_err(|e| e.to_string())?;
+        if let Some(seen_ids) = &mut self.seen_ids {
+            if !seen_ids.insert(transaction.get_transaction_id()) {
+                return Err(format!(
+                    "Duplicated transaction id: {}",
+                    transaction.get_transaction_id()
+                ));
+            }
+        }
+        if transaction.get_entities_data().is_empty() {
+            return Err(format!(
+                "Transaction {} has no costs",
+                transaction.get_transaction_id()
+            ));
+        }
+        if transaction.get_total().is_none() {
+            return Err(format!(
+                "Transaction {} costs more than {}",
+                transaction.get_transaction_id(),
+                u64::MAX
+            ));
+        }
+        Ok(transaction)
+    }
+}
+
+// los ids y los costos son enteros no negativos de 64 bits
+fn check_number(name: &str, value: &str) -> Result<(), String> {
+    u64::from_str(value)
+        .map(|_| ())
+        .map_err(|e| match e.kind() {
+            IntErrorKind::Empty => format!("{} is empty", name),
+            IntErrorKind::PosOverflow => format!("{} is too big: {}", name, value),
+            _ if value.starts_with('-') => format!("{} is negative: {}", name, value),
+            _ => format!("{} is not a number: {}", name, value),
+        })
+}
+
+// Ok(false) si no trae ninguna de las columnas, y error si trae algunas si y otras no
+fn check_complete(record: &StringRecord, fields: &[usize]) -> Result<bool, String> {
+    let field = |index: usize| record.get(index).unwrap_or_default();
+    if fields.iter().all(|index| field(*index).is_empty()) {
+        return Ok(false);
+    }
+    match fields.iter().find(|index| field(**index).is_empty()) {
+        Some(missing) => Err(format!("{} is empty", FIELDS[*missing])),
+        None => Ok(true),
+    }
+}
+
+// la estadia en el hotel va completa o no va. Sin estadia el hotel no tiene que reservar,
+// pero eso lo decide el hotel
+fn check_stay(record: &StringRecord) -> Result<(), String> {
+    if !check_complete(record, &[PROPERTY_FIELD, CHECK_IN_FIELD, CHECK_OUT_FIELD])? {
+        return Ok(());
+    }
+    let field = |index: usize| record.get(index).unwrap_or_default();
+    let date = |index: usize| {
+        Date::from_str(field(index))
+            .map_err(|_| format!("{} is not a date: {}", FIELDS[index], field(index)))
+    };
+    Stay::new(
+        field(PROPERTY_FIELD).to_string(),
+        date(CHECK_IN_FIELD)?,
+        date(CHECK_OUT_FIELD)?,
+    )
+    .map(|_| ())
+}
+
+// el asiento tambien: vuelo y clase juntos
+fn check_seat(record: &StringRecord) -> Result<(), String> {
+    if !check_complete(record, &[FLIGHT_FIELD, FARE_CLASS_FIELD])? {
+        return Ok(());
+    }
+    let field = |index: usize| record.get(index).unwrap_or_default();
+    Seat::new(
+        field(FLIGHT_FIELD).to_string(),
+        field(FARE_CLASS_FIELD).to_string(),
+    )
+    .map(|_| ())
+}
+
+// Recorre toda la entrada sin ejecutar nada y junta los errores con su numero de linea.
+// Acepta los mismos formatos que run
+pub fn validate_file(path: &str) -> Result<ValidationReport, Box<dyn std::error::Error>> {
+    let mut source = input_source::open(&InputSpec::new(path.to_string(), None))?;
+    let mut validator = TransactionValidator::new();
+    let mut report = ValidationReport {
+        rows: 0,
+        errors: vec![],
+    };
+    loop {
+        let message = match source.next_record() {
+            Ok(Some(record)) => validator.check(&record).err(),
+            Ok(None) => break,
+            Err(e) => Some(e),
+        };
+        report.rows += 1;
+        if let Some(message) = message {
+            report.errors.push(RowError {
+                line: source.line(),
+                message,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction_validator::validate_file;
+
+    #[test]
+    fn test_invalid_rows_are_reported_with_their_line() {
+        let path = "test_validate_transactions.csv";
+        std::fs::write(
+            path,
+            "id,hotel_cost,bank_cost,airline_cost\n\
+             0,1,2,3\n\
+             1,-5,2,3\n\
+             2,1,2\n\
+             0,1,1,1\n\
+             3,0,0,0\n\
+             4,99999999999999999999,0,0\n\
+             5,1,0,0,2\n",
+        )
+        .unwrap();
+        let report = validate_file(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(report.rows, 7);
+        let lines = report
+            .errors
+            .iter()
+            .map(|error| error.line)
+            .collect::<Vec<u64>>();
+        assert_eq!(lines, vec![3, 4, 5, 6, 7]);
+        assert_eq!(report.errors[0].message, "hotel_cost is negative: -5");
+        assert!(report.errors[2].message.contains("Duplicated"));
+        assert_eq!(report.errors[3].message, "Transaction 3 has no costs");
+        assert_eq!(
+            report.errors[4].message,
+            "hotel_cost is too big: 99999999999999999999"
+        );
+    }
+
+    #[test]
+    fn test_currencies_are_checked() {
+        let path = "test_validate_currencies.csv";
+        std::fs::write(
+            path,
+            "id,hotel_cost,bank_cost,airline_cost,currency,customer\n\
+             0,1,2,3,USD,4\n\
+             1,1,2,3,usd,\n\
+             2,1,2,3,,\n\
+             3,18446744073709551615,1,0,ARS,\n\
+             4,1,2,3,ARS,juan\n",
+        )
+        .unwrap();
+        let report = validate_file(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.errors.len(), 4);
+        assert_eq!(
+            report.errors[0].message,
+            "currency is not an ISO 4217 code: usd"
+        );
+        assert_eq!(report.errors[1].line, 4);
+        assert_eq!(
+            report.errors[2].message,
+            "Transaction 3 costs more than 18446744073709551615"
+        );
+        assert_eq!(report.errors[3].message, "customer is not a number: juan");
+    }
+
+    #[test]
+    fn test_stays_and_seats_are_checked() {
+        let path = "test_validate_bookings.csv";
+        std::fs::write(
+            path,
+            "id,hotel_cost,bank_cost,airline_cost,property,check_in,check_out,flight,fare_class\n\
+             0,1,0,0,Centro,2025-01-01,2025-01-03\n\
+             1,1,0,0,Centro,2025-01-01,\n\
+             2,1,0,0,Centro,2025-02-30,2025-03-02\n\
+             3,1,0,0,Centro,2025-01-03,2025-01-01\n\
+             4,1,0,0,,,\n\
+             5,0,0,1,,,,AR1140,\n\
+             6,0,0,1,,,,AR1140,Y;J\n",
+        )
+        .unwrap();
+        let report = validate_file(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(report.rows, 7);
+        let messages = report
+            .errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            messages,
+            vec![
+                "check_out is empty",
+                "check_in is not a date: 2025-02-30",
+                "check_out 2025-01-01 is not after check_in 2025-01-03",
+                "fare_class is empty",
+                "Invalid fare class: Y;J"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrong_header_is_rejected() {
+        let path = "test_validate_header.csv";
+        std::fs::write(path, "id,cost\n0,1\n").unwrap();
+        let result = validate_file(path);
+        let _ = std::fs::remove_file(path);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use alglobo_common_utils::journal::{self, Journal};
+use alglobo_common_utils::money::{Currency, Money};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+const HOLD: &str = "hold";
+const CAPTURE: &str = "capture";
+const RELEASE: &str = "release";
+
+// Una cuenta: lo que tiene y cuanto de eso esta retenido por transacciones sin decidir
+struct Account {
+    balance: Money,
+    held: u64,
+}
+
+impl Account {
+    // un journal que no corresponde a la semilla puede retener mas de lo que hay
+    fn available(&self) -> u64 {
+        self.balance.amount.saturating_sub(self.held)
+    }
+}
+
+// La plata que reservo un prepare, hasta que llegue la decision
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hold {
+    customer: u64,
+    amount: Money,
+}
+
+// Un cambio en las cuentas, tal como queda en el journal:
+// "hold,<id>,<intento>,<cliente>,<monto>,<moneda>", "capture,<id>,<intento>" o "release,<id>,<intento>"
+enum JournalEntry {
+    Hold(u64, u32, Hold),
+    Capture(u64, u32),
+    Release(u64, u32),
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalEntry::Hold(id, attempt, hold) => write!(
+                f,
+                "{},{},{},{},{},{}",
+                HOLD, id, attempt, hold.customer, hold.amount.amount, hold.amount.currency
+            ),
+            JournalEntry::Capture(id, attempt) => write!(f, "{},{},{}", CAPTURE, id, attempt),
+            JournalEntry::Release(id, attempt) => write!(f, "{},{},{}", RELEASE, id, attempt),
+        }
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields = line.split(',').collect::<Vec<&str>>();
+        let number = |index: usize| {
+            fields
+                .get(index)
+                .and_then(|field| u64::from_str(field).ok())
+                .ok_or_else(|| format!("Malformed journal line: {}", line))
+        };
+        let id = number(1)?;
+        let attempt = u32::try_from(number(2)?).map_err(|e| e.to_string())?;
+        match (fields[0], fields.len()) {
+            (HOLD, 6) => Ok(JournalEntry::Hold(
+                id,
+                attempt,
+                Hold {
+                    customer: number(3)?,
+                    amount: Money::new(number(4)?, Currency::from_str(fields[5])?),
+                },
+            )),
+            (CAPTURE, 3) => Ok(JournalEntry::Capture(id, attempt)),
+            (RELEASE, 3) => Ok(JournalEntry::Release(id, attempt)),
+            _ => Err(format!("Malformed journal line: {}", line)),
+        }
+    }
+}
+
+// Lo que queda en memoria de las cuentas: la semilla con el journal aplicado encima
+struct Ledger {
+    accounts: HashMap<u64, Account>,
+    // por transaccion e intento, como el log del participante
+    holds: HashMap<(u64, u32), Hold>,
+    // las que ya se cobraron, para distinguir un capture repetido de uno sin hold
+    captured: HashSet<(u64, u32)>,
+}
+
+impl Ledger {
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Hold(id, attempt, hold) => {
+                if let Some(account) = self.accounts.get_mut(&hold.customer) {
+                    account.held += hold.amount.amount;
+                    self.holds.insert((id, attempt), hold);
+                }
+            }
+            JournalEntry::Capture(id, attempt) => {
+                if let Some(hold) = self.holds.remove(&(id, attempt)) {
+                    if let Some(account) = self.accounts.get_mut(&hold.customer) {
+                        account.held -= hold.amount.amount;
+                        account.balance.amount =
+                            account.balance.amount.saturating_sub(hold.amount.amount);
+                    }
+                    self.captured.insert((id, attempt));
+                }
+            }
+            JournalEntry::Release(id, attempt) => {
+                if let Some(hold) = self.holds.remove(&(id, attempt)) {
+                    if let Some(account) = self.accounts.get_mut(&hold.customer) {
+                        account.held -= hold.amount.amount;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// "<cliente>,<saldo>,<moneda>"
+fn parse_account(line: &str) -> Result<(u64, Account), String> {
+    let fields = line.split(',').collect::<Vec<&str>>();
+    match fields[..] {
+        [customer, balance, currency] => Ok((
+            u64::from_str(customer).map_err(|e| format!("{}: {}", line, e))?,
+            Account {
+                balance: Money::new(
+                    u64::from_str(balance).map_err(|e| format!("{}: {}", line, e))?,
+                    Currency::from_str(currency)?,
+                ),
+                held: 0,
+            },
+        )),
+        _ => Err(format!("malformed account {}", line)),
+    }
+}
+
+// Las cuentas de los clientes. Arrancan del archivo semilla ("<cliente>,<saldo>,<moneda>", el
+// saldo en unidades minimas) y cada cambio se agrega al journal antes de aplicarse, asi al
+// reiniciar se vuelve al mismo estado leyendo la semilla y despues el journal
+pub struct Accounts {
+    ledger: Ledger,
+    journal: Journal,
+}
+
+impl Accounts {
+    pub fn load(seed_path: &str, journal_path: &str) -> Result<Self, String> {
+        let mut ledger = Ledger {
+            accounts: journal::read_seed(seed_path, parse_account)?
+                .into_iter()
+                .collect(),
+            holds: HashMap::new(),
+            captured: HashSet::new(),
+        };
+        let journal = Journal::load(journal_path, |entry| ledger.apply(entry))?;
+        Ok(Accounts { ledger, journal })
+    }
+
+    // lo que el cliente puede gastar
+    #[cfg(test)]
+    pub fn available(&self, customer: u64) -> Option<Money> {
+        self.ledger
+            .accounts
+            .get(&customer)
+            .map(|account| Money::new(account.available(), account.balance.currency))
+    }
+
+    // retiene el monto si alcanza. Repetirlo para la misma transaccion no retiene dos veces
+    pub fn hold(
+        &mut self,
+        transaction_id: u64,
+        attempt: u32,
+        customer: u64,
+        amount: Money,
+    ) -> Result<(), String> {
+        let hold = Hold { customer, amount };
+        if let Some(existing) = self.ledger.holds.get(&(transaction_id, attempt)) {
+            if *existing == hold {
+                return Ok(());
+            }
+            return Err(format!(
+                "Transaction {} already holds {} from {}",
+                transaction_id, existing.amount, existing.customer
+            ));
+        }
+        let account = self
+            .ledger
+            .accounts
+            .get(&customer)
+            .ok_or_else(|| format!("Unknown account {}", customer))?;
+        if account.balance.currency != amount.currency {
+            return Err(format!(
+                "Account {} is in {}, not {}",
+                customer, account.balance.currency, amount.currency
+            ));
+        }
+        if account.available() < amount.amount {
+            return Err(format!(
+                "Insufficient funds in account {}: {} available, {} needed",
+                customer,
+                account.available(),
+                amount
+            ));
+        }
+        self.write(JournalEntry::Hold(transaction_id, attempt, hold))
+    }
+
+    // cobra lo retenido. Repetirlo no cobra dos veces, pero sin nada retenido no hay que cobrar
+    pub fn capture(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.ledger.captured.contains(&key) {
+            return Ok(());
+        }
+        if !self.ledger.holds.contains_key(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) holds nothing to capture",
+                transaction_id, attempt
+            ));
+        }
+        self.write(JournalEntry::Capture(transaction_id, attempt))
+    }
+
+    // devuelve lo retenido. Si no se retuvo nada (el prepare no paso) no hace nada
+    pub fn release(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.ledger.captured.contains(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) was already captured",
+                transaction_id, attempt
+            ));
+        }
+        if !self.ledger.holds.contains_key(&key) {
+            return Ok(());
+        }
+        self.write(JournalEntry::Release(transaction_id, attempt))
+    }
+
+    fn write(&mut self, entry: JournalEntry) -> Result<(), String> {
+        let ledger = &mut self.ledger;
+        self.journal.write(entry, |entry| ledger.apply(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accounts::Accounts;
+    use alglobo_common_utils::money::{Currency, Money, DEFAULT_CURRENCY};
+    use std::str::FromStr;
+
+    fn ars(amount: u64) -> Money {
+        Money::new(amount, DEFAULT_CURRENCY)
+    }
+
+    #[test]
+    fn test_holds_are_captured_or_released() {
+        let seed = "test_accounts_seed.csv";
+        let journal = "test_accounts_journal.csv";
+        let _ = std::fs::remove_file(journal);
+        std::fs::write(seed, "customer,balance,currency\n1,1000,ARS\n2,50,USD\n").unwrap();
+        let mut accounts = Accounts::load(seed, journal).unwrap();
+
+        accounts.hold(10, 0, 1, ars(600)).unwrap();
+        // repetido no retiene de nuevo
+        accounts.hold(10, 0, 1, ars(600)).unwrap();
+        assert_eq!(accounts.available(1), Some(ars(400)));
+        assert!(accounts.hold(11, 0, 1, ars(500)).is_err());
+        accounts.hold(11, 0, 1, ars(300)).unwrap();
+        assert!(accounts.hold(12, 0, 2, ars(10)).is_err());
+        assert!(accounts.hold(12, 0, 3, ars(10)).is_err());
+
+        accounts.capture(10, 0).unwrap();
+        accounts.capture(10, 0).unwrap();
+        assert!(accounts.release(10, 0).is_err());
+        accounts.release(11, 0).unwrap();
+        assert!(accounts.capture(11, 0).is_err());
+        assert_eq!(accounts.available(1), Some(ars(400)));
+        drop(accounts);
+
+        // al reiniciar queda igual
+        let mut accounts = Accounts::load(seed, journal).unwrap();
+        let _ = std::fs::remove_file(seed);
+        let _ = std::fs::remove_file(journal);
+        assert_eq!(accounts.available(1), Some(ars(400)));
+        let usd = Currency::from_str("USD").unwrap();
+        assert_eq!(accounts.available(2), Some(Money::new(50, usd)));
+        accounts.hold(13, 0, 2, Money::new(50, usd)).unwrap();
+        assert_eq!(accounts.available(2), Some(Money::new(0, usd)));
+    }
+}
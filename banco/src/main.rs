@@ -1,108 +1,73 @@
-use alglobo_common_utils::entity_logger::Logger;
-use alglobo_common_utils::entity_payload::{EntityPayload, PAYLOAD_SIZE};
-use alglobo_common_utils::transaction_response::TransactionResponse;
-use alglobo_common_utils::transaction_state::TransactionState;
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
-use std::net::UdpSocket;
-use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
-use std::thread;
+mod accounts;
 
-fn logger(rx: Receiver<String>) {
-    let mut logger = Logger::new("banco.log");
-    for msg in rx {
-        logger.log(msg);
-    }
-}
-
-fn main() {
-    let sock = UdpSocket::bind("localhost:1235").unwrap();
-    let mut rng = thread_rng();
-    let mut log = HashMap::new();
-    let (tx, rx) = mpsc::channel();
-    let _ = tx.send("Banco inicializado en localhost:1235".to_string());
-
-    let _ = thread::spawn(|| logger(rx));
+use crate::accounts::Accounts;
+use alglobo_common_utils::cluster_config::ClusterConfig;
+use alglobo_common_utils::entity_payload::EntityPayload;
+use alglobo_common_utils::entity_type::EntityType;
+use alglobo_common_utils::participant::{Participant, ReservationHandler};
+use std::env::args;
+use std::str::FromStr;
+use std::time::Duration;
 
-    loop {
-        let mut buf = [0; PAYLOAD_SIZE];
+// El prepare retiene el costo de la cuenta del cliente, el commit lo cobra y el abort lo devuelve
+struct Bank {
+    accounts: Accounts,
+}
 
-        let (_, addr) = sock.recv_from(&mut buf).unwrap();
+impl ReservationHandler for Bank {
+    fn can_reserve(&mut self, payload: &EntityPayload) -> Result<(), String> {
+        let customer = u64::from_str(&payload.details).map_err(|_| {
+            format!(
+                "Transaction {} has no customer account",
+                payload.transaction_id
+            )
+        })?;
+        self.accounts.hold(
+            payload.transaction_id,
+            payload.attempt,
+            customer,
+            payload.cost,
+        )
+    }
 
-        let payload_deserialized: EntityPayload = buf.to_vec().into();
-        let _ = tx.send(format!("payload_deserialized: {:?}", payload_deserialized));
-        let transaction_id = payload_deserialized.transaction_id;
-        let response = match payload_deserialized.transaction_state {
-            TransactionState::Prepare => {
-                let _ = tx.send("TransactionState: Prepare".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) | Some(TransactionState::Commit) => {
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Abort) => {
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    None => {
-                        let x: f64 = rng.gen();
-                        if x > 0.1 {
-                            log.insert(transaction_id, TransactionState::Accept);
-                            let _ = tx.send("TransactionResponse: Commit".to_string());
-                            TransactionResponse::new(transaction_id, TransactionState::Commit)
-                        } else {
-                            log.insert(transaction_id, TransactionState::Abort);
-                            let _ = tx.send("FAILED. TransactionResponse: Abort".to_string());
-                            TransactionResponse::new(transaction_id, TransactionState::Abort)
-                        }
-                    }
-                    _ => panic!("Invalid transacciont state"),
-                }
-            }
-            TransactionState::Commit => {
-                let _ = tx.send("TransactionState: Commit".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) => {
-                        log.insert(transaction_id, TransactionState::Commit);
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Commit) => {
-                        let _ = tx.send("TransactionResponse: Commit".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Commit)
-                    }
-                    Some(TransactionState::Abort) | None => {
-                        let _ = tx.send("PANICK; TransactionState::Abort cannot be handled by two fase transactionality algorithm".to_string());
-                        panic!("This cannot be handled by two fase transactionality algorithm!");
-                    }
-                    _ => panic!("This cannot be handled by two fase transactionality algorithm!"),
-                }
-            }
-            TransactionState::Abort => {
-                let _ = tx.send("TransactionState: Abort".to_string());
-                match log.get(&transaction_id) {
-                    Some(TransactionState::Accept) => {
-                        log.insert(transaction_id, TransactionState::Abort);
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    Some(TransactionState::Abort) => {
-                        let _ = tx.send("TransactionResponse: Abort".to_string());
-                        TransactionResponse::new(transaction_id, TransactionState::Abort)
-                    }
-                    Some(TransactionState::Commit) | None => {
-                        println!("{} {:?}", transaction_id, log.get(&transaction_id));
-                        let _ = tx.send("PANICK; TransactionState::Commit cannot be handled by two fase transactionality algorithm".to_string());
-                        panic!("This cannot be handled by two fase transactionality algorithm!");
-                    }
-                    _ => panic!("This cannot be handled by two fase transactionality algorithm!"),
-                }
-            }
-            _ => panic!("TransactionState Unknow"),
-        };
+    fn commit(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        self.accounts.capture(transaction_id, attempt)
+    }
 
-        let response_payload: Vec<u8> = response.into();
-        let _ = sock.send_to(response_payload.as_slice(), addr);
+    fn abort(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        self.accounts.release(transaction_id, attempt)
     }
 }
+
+fn main() {
+    let config_path = args().nth(1);
+    let config = match ClusterConfig::load_or_default(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => panic!("ERROR leyendo la configuracion: {}", e),
+    };
+    let entity = match config.entity(EntityType::Bank) {
+        Some(entity) => entity,
+        None => panic!("ERROR: la configuracion no tiene la entidad Bank"),
+    };
+    let (seed_file, journal_file) = match (&entity.seed_file, &entity.journal_file) {
+        (Some(seed_file), Some(journal_file)) => (seed_file, journal_file),
+        _ => panic!("ERROR: la entidad Bank necesita seed_file y journal_file"),
+    };
+    let accounts = match Accounts::load(seed_file, journal_file) {
+        Ok(accounts) => accounts,
+        Err(e) => panic!("ERROR cargando las cuentas: {}", e),
+    };
+    Participant::new(
+        "Banco",
+        &entity.address,
+        &entity.log_file,
+        &entity.state_file,
+        Bank { accounts },
+    )
+    .with_currencies(entity.currencies.clone())
+    .with_decision_inquiry(
+        config.coordinator_addresses(),
+        Duration::from_secs(config.timeouts.decision_s),
+    )
+    .run();
+}
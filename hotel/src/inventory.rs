@@ -0,0 +1,302 @@
+use alglobo_common_utils::journal::{self, Journal};
+use alglobo_common_utils::stay::{Date, Stay};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+const RESERVE: &str = "reserve";
+const CONFIRM: &str = "confirm";
+const RELEASE: &str = "release";
+
+// Las habitaciones de un hotel en una noche, y cuantas ya estan ocupadas (reservadas o confirmadas)
+struct Night {
+    rooms: u32,
+    taken: u32,
+}
+
+// Un cambio en el inventario, tal como queda en el journal:
+// "reserve,<id>,<intento>,<check_in>,<check_out>,<hotel>", "confirm,<id>,<intento>" o
+// "release,<id>,<intento>". El hotel va ultimo porque puede tener comas
+enum JournalEntry {
+    Reserve(u64, u32, Stay),
+    Confirm(u64, u32),
+    Release(u64, u32),
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalEntry::Reserve(id, attempt, stay) => write!(
+                f,
+                "{},{},{},{},{},{}",
+                RESERVE, id, attempt, stay.check_in, stay.check_out, stay.property
+            ),
+            JournalEntry::Confirm(id, attempt) => write!(f, "{},{},{}", CONFIRM, id, attempt),
+            JournalEntry::Release(id, attempt) => write!(f, "{},{},{}", RELEASE, id, attempt),
+        }
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let malformed = || format!("Malformed journal line: {}", line);
+        let fields = line.splitn(6, ',').collect::<Vec<&str>>();
+        let id = fields
+            .get(1)
+            .and_then(|field| u64::from_str(field).ok())
+            .ok_or_else(malformed)?;
+        let attempt = fields
+            .get(2)
+            .and_then(|field| u32::from_str(field).ok())
+            .ok_or_else(malformed)?;
+        match fields[..] {
+            [RESERVE, _, _, check_in, check_out, property] => Ok(JournalEntry::Reserve(
+                id,
+                attempt,
+                Stay::new(
+                    property.to_string(),
+                    Date::from_str(check_in)?,
+                    Date::from_str(check_out)?,
+                )?,
+            )),
+            [CONFIRM, _, _] => Ok(JournalEntry::Confirm(id, attempt)),
+            [RELEASE, _, _] => Ok(JournalEntry::Release(id, attempt)),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+// Lo que queda en memoria del inventario: la semilla con el journal aplicado encima
+struct Rooms {
+    nights: HashMap<(String, Date), Night>,
+    // las reservas sin confirmar, por transaccion e intento como el log del participante
+    holds: HashMap<(u64, u32), Stay>,
+    // las ya confirmadas, para distinguir un confirm repetido de uno sin reserva
+    confirmed: HashSet<(u64, u32)>,
+}
+
+impl Rooms {
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Reserve(id, attempt, stay) => {
+                for night in stay.nights() {
+                    if let Some(night) = self.nights.get_mut(&(stay.property.clone(), night)) {
+                        night.taken += 1;
+                    }
+                }
+                self.holds.insert((id, attempt), stay);
+            }
+            JournalEntry::Confirm(id, attempt) => {
+                if self.holds.remove(&(id, attempt)).is_some() {
+                    self.confirmed.insert((id, attempt));
+                }
+            }
+            JournalEntry::Release(id, attempt) => {
+                if let Some(stay) = self.holds.remove(&(id, attempt)) {
+                    for night in stay.nights() {
+                        if let Some(night) = self.nights.get_mut(&(stay.property.clone(), night)) {
+                            night.taken -= 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// "<hotel>,<desde>,<hasta>,<habitaciones>", de atras para adelante porque el nombre del hotel
+// puede tener comas
+fn parse_rooms(line: &str) -> Result<(Stay, u32), String> {
+    let fields = line.rsplitn(4, ',').collect::<Vec<&str>>();
+    match fields[..] {
+        [rooms, to, from, property] => Ok((
+            Stay::new(
+                property.to_string(),
+                Date::from_str(from)?,
+                Date::from_str(to)?,
+            )?,
+            u32::from_str(rooms).map_err(|e| format!("{}: {}", line, e))?,
+        )),
+        _ => Err(format!("malformed rooms {}", line)),
+    }
+}
+
+// Las habitaciones de cada hotel por noche. Arrancan del archivo semilla
+// ("<hotel>,<desde>,<hasta>,<habitaciones>", las mismas habitaciones cada noche desde hasta
+// antes de hasta) y cada cambio se agrega al journal antes de aplicarse, como las cuentas del banco
+pub struct Inventory {
+    rooms: Rooms,
+    journal: Journal,
+}
+
+impl Inventory {
+    pub fn load(seed_path: &str, journal_path: &str) -> Result<Self, String> {
+        let mut nights = HashMap::new();
+        for (stay, rooms) in journal::read_seed(seed_path, parse_rooms)? {
+            for night in stay.nights() {
+                nights.insert((stay.property.clone(), night), Night { rooms, taken: 0 });
+            }
+        }
+        let mut rooms = Rooms {
+            nights,
+            holds: HashMap::new(),
+            confirmed: HashSet::new(),
+        };
+        let journal = Journal::load(journal_path, |entry| rooms.apply(entry))?;
+        Ok(Inventory { rooms, journal })
+    }
+
+    // las habitaciones que quedan libres esa noche
+    #[cfg(test)]
+    pub fn available(&self, property: &str, night: Date) -> Option<u32> {
+        self.rooms
+            .nights
+            .get(&(property.to_string(), night))
+            .map(|night| night.rooms - night.taken)
+    }
+
+    // reserva una habitacion todas las noches de la estadia si hay lugar en todas. Repetirlo
+    // para la misma transaccion no reserva dos veces
+    pub fn reserve(&mut self, transaction_id: u64, attempt: u32, stay: Stay) -> Result<(), String> {
+        if let Some(existing) = self.rooms.holds.get(&(transaction_id, attempt)) {
+            if *existing == stay {
+                return Ok(());
+            }
+            return Err(format!(
+                "Transaction {} already reserved {}",
+                transaction_id, existing
+            ));
+        }
+        for night in stay.nights() {
+            match self.rooms.nights.get(&(stay.property.clone(), night)) {
+                None => return Err(format!("{} has no rooms on {}", stay.property, night)),
+                Some(availability) if availability.taken >= availability.rooms => {
+                    return Err(format!("{} is full on {}", stay.property, night))
+                }
+                Some(_) => {}
+            }
+        }
+        self.write(JournalEntry::Reserve(transaction_id, attempt, stay))
+    }
+
+    // la reserva queda firme. Repetirlo no hace nada, pero sin reserva no hay que confirmar
+    pub fn confirm(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.rooms.confirmed.contains(&key) {
+            return Ok(());
+        }
+        if !self.rooms.holds.contains_key(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) has no reservation to confirm",
+                transaction_id, attempt
+            ));
+        }
+        self.write(JournalEntry::Confirm(transaction_id, attempt))
+    }
+
+    // devuelve las habitaciones reservadas. Si no se reservo nada (el prepare no paso) no hace nada
+    pub fn release(&mut self, transaction_id: u64, attempt: u32) -> Result<(), String> {
+        let key = (transaction_id, attempt);
+        if self.rooms.confirmed.contains(&key) {
+            return Err(format!(
+                "Transaction {} (attempt {}) was already confirmed",
+                transaction_id, attempt
+            ));
+        }
+        if !self.rooms.holds.contains_key(&key) {
+            return Ok(());
+        }
+        self.write(JournalEntry::Release(transaction_id, attempt))
+    }
+
+    fn write(&mut self, entry: JournalEntry) -> Result<(), String> {
+        let rooms = &mut self.rooms;
+        self.journal.write(entry, |entry| rooms.apply(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::Inventory;
+    use alglobo_common_utils::stay::{Date, Stay};
+    use std::str::FromStr;
+
+    fn stay(property: &str, check_in: &str, check_out: &str) -> Stay {
+        Stay::new(
+            property.to_string(),
+            Date::from_str(check_in).unwrap(),
+            Date::from_str(check_out).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn date(date: &str) -> Date {
+        Date::from_str(date).unwrap()
+    }
+
+    #[test]
+    fn test_rooms_are_reserved_confirmed_or_released() {
+        let seed = "test_inventory_seed.csv";
+        let journal = "test_inventory_journal.csv";
+        let _ = std::fs::remove_file(journal);
+        std::fs::write(
+            seed,
+            "property,from,to,rooms\nHotel, Centro,2025-01-01,2025-01-04,2\nPlaya,2025-01-01,2025-01-02,1\n",
+        )
+        .unwrap();
+        let mut inventory = Inventory::load(seed, journal).unwrap();
+
+        inventory
+            .reserve(10, 0, stay("Hotel, Centro", "2025-01-01", "2025-01-03"))
+            .unwrap();
+        // repetido no reserva de nuevo
+        inventory
+            .reserve(10, 0, stay("Hotel, Centro", "2025-01-01", "2025-01-03"))
+            .unwrap();
+        inventory
+            .reserve(11, 0, stay("Hotel, Centro", "2025-01-02", "2025-01-04"))
+            .unwrap();
+        assert_eq!(
+            inventory.available("Hotel, Centro", date("2025-01-02")),
+            Some(0)
+        );
+        // sin lugar la segunda noche
+        assert!(inventory
+            .reserve(12, 0, stay("Hotel, Centro", "2025-01-01", "2025-01-03"))
+            .is_err());
+        // fuera de las noches que ofrece
+        assert!(inventory
+            .reserve(12, 0, stay("Playa", "2025-01-01", "2025-01-03"))
+            .is_err());
+
+        inventory.confirm(10, 0).unwrap();
+        inventory.confirm(10, 0).unwrap();
+        assert!(inventory.release(10, 0).is_err());
+        inventory.release(11, 0).unwrap();
+        assert!(inventory.confirm(11, 0).is_err());
+        assert_eq!(
+            inventory.available("Hotel, Centro", date("2025-01-02")),
+            Some(1)
+        );
+        drop(inventory);
+
+        // al reiniciar queda igual
+        let mut inventory = Inventory::load(seed, journal).unwrap();
+        let _ = std::fs::remove_file(seed);
+        let _ = std::fs::remove_file(journal);
+        assert_eq!(
+            inventory.available("Hotel, Centro", date("2025-01-01")),
+            Some(1)
+        );
+        assert_eq!(
+            inventory.available("Hotel, Centro", date("2025-01-03")),
+            Some(2)
+        );
+        inventory
+            .reserve(13, 0, stay("Playa", "2025-01-01", "2025-01-02"))
+            .unwrap();
+        assert_eq!(inventory.available("Playa", date("2025-01-01")), Some(0));
+    }
+}
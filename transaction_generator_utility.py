@@ -3,19 +3,74 @@
 # QUICK & DIRTY UTILITY 
 
 import random
+from datetime import date, timedelta
 
-HEADER = 'id,hotel_cost,bank_cost,airline_cost\n'
+HEADER = 'id,hotel_cost,bank_cost,airline_cost,currency,customer,property,check_in,check_out,flight,fare_class\n'
 FILE_PATH = 'transactions.csv'
+# las cuentas del banco, con saldo para pagar mas o menos la mitad de sus transacciones
+ACCOUNTS_HEADER = 'customer,balance,currency\n'
+ACCOUNTS_PATH = 'banco_accounts.csv'
+NUM_CUSTOMERS = 1000
 NUM_TRANSACTIONS = 1000000
 MIN_COST = 1000
 MAX_COST = 2000
+# los costos van en centavos de esta moneda
+CURRENCY = 'ARS'
+# las habitaciones de los hoteles, para ocupar mas o menos la mitad de las noches pedidas
+ROOMS_HEADER = 'property,from,to,rooms\n'
+ROOMS_PATH = 'hotel_rooms.csv'
+NUM_PROPERTIES = 100
+FIRST_NIGHT = date(2025, 1, 1)
+NUM_NIGHTS = 60
+MAX_STAY = 5
+# los asientos de los vuelos, para vender mas o menos la mitad de los pedidos
+SEATS_HEADER = 'flight,fare_class,seats\n'
+SEATS_PATH = 'aerolinea_seats.csv'
+NUM_FLIGHTS = 200
+FARE_CLASSES = ['Y', 'J']
+
+def write_accounts():
+    per_customer = NUM_TRANSACTIONS // NUM_CUSTOMERS
+    with open(ACCOUNTS_PATH, 'w') as f:
+        f.write(ACCOUNTS_HEADER)
+        for customer in range(NUM_CUSTOMERS):
+            balance = per_customer * (MIN_COST + MAX_COST) // 4
+            f.write(f"{customer},{balance},{CURRENCY}\n")
+
+def write_rooms():
+    demand = NUM_TRANSACTIONS * (MAX_STAY + 1) // 2 // (NUM_PROPERTIES * NUM_NIGHTS)
+    last_night = FIRST_NIGHT + timedelta(days=NUM_NIGHTS + MAX_STAY)
+    with open(ROOMS_PATH, 'w') as f:
+        f.write(ROOMS_HEADER)
+        for property in range(NUM_PROPERTIES):
+            f.write(f"Hotel {property},{FIRST_NIGHT},{last_night},{demand // 2}\n")
+
+def write_seats():
+    demand = NUM_TRANSACTIONS // (NUM_FLIGHTS * len(FARE_CLASSES))
+    with open(SEATS_PATH, 'w') as f:
+        f.write(SEATS_HEADER)
+        for flight in range(NUM_FLIGHTS):
+            for fare_class in FARE_CLASSES:
+                f.write(f"AR{1000 + flight},{fare_class},{demand // 2}\n")
+
+def seat():
+    return f"AR{1000 + random.randrange(NUM_FLIGHTS)},{random.choice(FARE_CLASSES)}"
+
+def stay():
+    check_in = FIRST_NIGHT + timedelta(days=random.randrange(NUM_NIGHTS))
+    check_out = check_in + timedelta(days=random.randint(1, MAX_STAY))
+    return f"Hotel {random.randrange(NUM_PROPERTIES)},{check_in},{check_out}"
 
 def main():
     with open(FILE_PATH, 'w') as f:
         f.write(HEADER)
         for id in range(NUM_TRANSACTIONS):
             nums = [random.randint(MIN_COST, MAX_COST) for _ in range(3)]
-            f.write(f"{id},{nums[0]},{nums[1]},{nums[2]}\n")
+            customer = random.randrange(NUM_CUSTOMERS)
+            f.write(f"{id},{nums[0]},{nums[1]},{nums[2]},{CURRENCY},{customer},{stay()},{seat()}\n")
+    write_accounts()
+    write_rooms()
+    write_seats()
 
 
 if __name__ == '__main__':